@@ -1,8 +1,13 @@
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
+use crate::db_model::grave::GraveType;
 use crate::db_model::table::Table;
 
+// Learning cards due after this are due at an epoch second, before it
+// on a day
+const LEARNING_CUTOFF: i64 = 1_000_000_000;
+
 #[derive(
     Debug, Clone, Serialize_repr, Deserialize_repr, PartialEq,
 )]
@@ -131,10 +136,43 @@ impl Card {
             data: String::new(),
         }
     }
+
+    /// Queue the card belongs in given its type, the queue it returns
+    /// to when unsuspended or leaving a filtered deck.
+    pub fn queue_for_type(&self) -> CardQueue {
+        match self.card_type {
+            CardType::New => CardQueue::New,
+            CardType::Review => CardQueue::Review,
+            CardType::Learning | CardType::Relearning => {
+                if self.due > LEARNING_CUTOFF {
+                    CardQueue::Learning
+                } else {
+                    CardQueue::InLearning
+                }
+            }
+        }
+    }
+
+    pub fn is_suspended_or_buried(&self) -> bool {
+        matches!(
+            self.queue,
+            CardQueue::Suspended
+                | CardQueue::Buried
+                | CardQueue::UserSuspended
+        )
+    }
 }
 
 impl Table for Card {
-    fn table_name(&self) -> &'static str {
-        "cards"
+    const TABLE_NAME: &'static str = "cards";
+    const COLUMNS: &'static [&'static str] = &[
+        "id", "nid", "did", "ord", "mod", "usn", "type", "queue",
+        "due", "ivl", "factor", "reps", "lapses", "left", "odue",
+        "odid", "flags", "data",
+    ];
+    const GRAVE_TYPE: Option<GraveType> = Some(GraveType::Card);
+
+    fn id(&self) -> i64 {
+        self.id
     }
 }
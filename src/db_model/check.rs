@@ -0,0 +1,285 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::db_model::card::{Card, CardQueue, CardType};
+use crate::db_model::collection::Collection;
+use crate::db_model::grave::{Grave, GraveType};
+use crate::db_model::id::IdAllocator;
+use crate::db_model::model::{Model, ModelType};
+use crate::db_model::note::Note;
+
+/// Deck the cards of missing decks are moved to.
+pub const RECOVERED_DECK: &str = "Recovered";
+
+/// A problem found by `Collection::check`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckProblem {
+    DuplicateNoteId(i64),
+    DuplicateCardId(i64),
+    /// The note's model is not in the collection.
+    MissingModel {
+        note_id: i64,
+        model_id: usize,
+    },
+    /// The note has another number of fields than its model.
+    FieldCount {
+        note_id: i64,
+        expected: usize,
+        found: usize,
+    },
+    /// The card's note is not in the collection.
+    OrphanCard {
+        card_id: i64,
+        note_id: usize,
+    },
+    /// The card's deck is not in the collection.
+    MissingDeck {
+        card_id: i64,
+        deck_id: usize,
+    },
+    /// The card's model has no template with the ordinal.
+    InvalidOrdinal {
+        card_id: i64,
+        ordinal: u64,
+    },
+    /// The card's queue does not go with its type.
+    InvalidQueue {
+        card_id: i64,
+        card_type: CardType,
+        queue: CardQueue,
+    },
+}
+
+/// Problems found in a collection, and when they were fixed, the
+/// graves of the cards and notes deleted by the fixes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheckReport {
+    pub problems: Vec<CheckProblem>,
+    pub graves: Vec<Grave>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+fn is_valid_queue(card: &Card) -> bool {
+    if card.is_suspended_or_buried() {
+        return true;
+    }
+    // Filtered decks preview cards in the review queue
+    if card.original_deck_id != 0
+        && matches!(
+            card.queue,
+            CardQueue::Review | CardQueue::Preview
+        )
+    {
+        return true;
+    }
+    match card.card_type {
+        CardType::New => card.queue == CardQueue::New,
+        CardType::Learning | CardType::Relearning => matches!(
+            card.queue,
+            CardQueue::Learning | CardQueue::InLearning
+        ),
+        CardType::Review => card.queue == CardQueue::Review,
+    }
+}
+
+// Cloze notetypes make a card per cloze number with the one template
+fn is_valid_ordinal(model: &Model, ordinal: u64) -> bool {
+    model.model_type == ModelType::Cloze
+        || (ordinal as usize) < model.templates.len()
+}
+
+impl Collection {
+    fn model_by_id(&self, id: usize) -> Option<&Model> {
+        self.models
+            .iter()
+            .find(|(model_id, _)| *model_id == id)
+            .map(|(_, model)| model)
+    }
+
+    /// Looks for the problems Anki's Check Database fixes in the notes
+    /// and cards of the collection.
+    pub fn check(
+        &self,
+        notes: &[Note],
+        cards: &[Card],
+    ) -> CheckReport {
+        let mut problems = vec![];
+
+        let mut seen = HashSet::new();
+        for note in notes {
+            if !seen.insert(note.id) {
+                problems.push(CheckProblem::DuplicateNoteId(note.id));
+            }
+        }
+        let mut seen = HashSet::new();
+        for card in cards {
+            if !seen.insert(card.id) {
+                problems.push(CheckProblem::DuplicateCardId(card.id));
+            }
+        }
+
+        for note in notes {
+            match self.model_by_id(note.model_id) {
+                None => problems.push(CheckProblem::MissingModel {
+                    note_id: note.id,
+                    model_id: note.model_id,
+                }),
+                Some(model)
+                    if model.fields.len() != note.fields.len() =>
+                {
+                    problems.push(CheckProblem::FieldCount {
+                        note_id: note.id,
+                        expected: model.fields.len(),
+                        found: note.fields.len(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        // Reversed so the first of the notes sharing an id is kept
+        let notes_by_id = notes
+            .iter()
+            .rev()
+            .map(|note| (note.id, note))
+            .collect::<HashMap<_, _>>();
+        for card in cards {
+            let note = notes_by_id.get(&(card.note_id as i64));
+            let Some(note) = note else {
+                problems.push(CheckProblem::OrphanCard {
+                    card_id: card.id,
+                    note_id: card.note_id,
+                });
+                continue;
+            };
+            if self.model_by_id(note.model_id).is_some_and(|model| {
+                !is_valid_ordinal(model, card.ordinal)
+            }) {
+                problems.push(CheckProblem::InvalidOrdinal {
+                    card_id: card.id,
+                    ordinal: card.ordinal,
+                });
+            }
+            if self.deck(card.deck_id).is_none() {
+                problems.push(CheckProblem::MissingDeck {
+                    card_id: card.id,
+                    deck_id: card.deck_id,
+                });
+            }
+            if !is_valid_queue(card) {
+                problems.push(CheckProblem::InvalidQueue {
+                    card_id: card.id,
+                    card_type: card.card_type.clone(),
+                    queue: card.queue.clone(),
+                });
+            }
+        }
+
+        CheckReport {
+            problems,
+            graves: vec![],
+        }
+    }
+
+    /// Checks the collection and fixes the problems found as Anki does:
+    /// - duplicate cards get new ids, duplicate notes are dropped
+    /// - notes of missing models are deleted with their cards
+    /// - missing fields are added empty, extra fields are joined into
+    ///   the last one
+    /// - orphan cards and cards with invalid ordinals are deleted
+    /// - cards of missing decks move to the `Recovered` deck
+    /// - invalid queues are reset from the card type
+    ///
+    /// The report lists the problems found before fixing.
+    pub fn check_and_fix(
+        &mut self,
+        notes: &mut Vec<Note>,
+        cards: &mut Vec<Card>,
+        ids: &mut IdAllocator,
+        update_seq_number: i64,
+    ) -> Result<CheckReport, String> {
+        let mut report = self.check(notes, cards);
+        if report.is_ok() {
+            return Ok(report);
+        }
+        let grave = |id: i64, grave_type| {
+            Grave::new(id, grave_type, update_seq_number)
+        };
+
+        let mut seen = HashSet::new();
+        for card in cards.iter_mut() {
+            if !seen.insert(card.id) {
+                card.id = ids.next_id();
+                card.update_seq_number = -1;
+            }
+        }
+        // The cards of duplicate notes can't tell them apart, they stay
+        // with the first one. No grave, it would delete the first note
+        // when syncing.
+        let mut seen = HashSet::new();
+        notes.retain(|note| seen.insert(note.id));
+
+        notes.retain(|note| {
+            let keep = self.model_by_id(note.model_id).is_some();
+            if !keep {
+                report.graves.push(grave(note.id, GraveType::Note));
+            }
+            keep
+        });
+        for note in notes.iter_mut() {
+            let Some(model) = self.model_by_id(note.model_id) else {
+                continue;
+            };
+            let expected = model.fields.len();
+            if note.fields.len() == expected {
+                continue;
+            }
+            if note.fields.len() > expected && expected > 0 {
+                let extra = note.fields.split_off(expected - 1);
+                note.fields.push(extra.join("; "));
+            }
+            note.fields.resize(expected, String::new());
+            note.update_sort_field(model)?;
+            note.update_seq_number = -1;
+        }
+
+        let notes_by_id = notes
+            .iter()
+            .map(|note| (note.id, note))
+            .collect::<HashMap<_, _>>();
+        cards.retain(|card| {
+            let note = notes_by_id.get(&(card.note_id as i64));
+            let keep = note.is_some_and(|note| {
+                self.model_by_id(note.model_id).is_some_and(|model| {
+                    is_valid_ordinal(model, card.ordinal)
+                })
+            });
+            if !keep {
+                report.graves.push(grave(card.id, GraveType::Card));
+            }
+            keep
+        });
+
+        let mut recovered = None;
+        for card in cards.iter_mut() {
+            if self.deck(card.deck_id).is_none() {
+                let deck_id = match recovered {
+                    Some(deck_id) => deck_id,
+                    None => self.add_deck(RECOVERED_DECK, ids)?,
+                };
+                recovered = Some(deck_id);
+                card.deck_id = deck_id;
+                card.update_seq_number = -1;
+            }
+            if !is_valid_queue(card) {
+                card.queue = card.queue_for_type();
+                card.update_seq_number = -1;
+            }
+        }
+        Ok(report)
+    }
+}
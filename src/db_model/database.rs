@@ -0,0 +1,621 @@
+use std::path::Path;
+
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OpenFlags, Row};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::db_model::card::Card;
+use crate::db_model::collection::Collection;
+use crate::db_model::deck::Deck;
+use crate::db_model::deck_tree::CardRemoval;
+use crate::db_model::grave::{Grave, GraveType};
+use crate::db_model::model::Model;
+use crate::db_model::note::Note;
+use crate::db_model::revlog::ReviewLog;
+use crate::db_model::schema18;
+use crate::db_model::table::Table;
+
+// Version stored in col.ver for the layout created here
+pub const SCHEMA_VERSION: usize = 11;
+
+// Separator between the fields of a note in notes.flds
+pub const FIELD_SEPARATOR: char = '\x1f';
+
+// Id of the "Default" deck every collection has
+pub const DEFAULT_DECK_ID: i64 = 1;
+
+/// Layout of the collection database. Schema 11 keeps notetypes, decks
+/// and configs as JSON in the col table, schema 18 in tables of their
+/// own, as written by Anki 2.1.50 and later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaVersion {
+    #[default]
+    V11,
+    V18,
+}
+
+impl SchemaVersion {
+    pub fn number(self) -> usize {
+        match self {
+            SchemaVersion::V11 => SCHEMA_VERSION,
+            SchemaVersion::V18 => 18,
+        }
+    }
+
+    pub fn from_number(version: usize) -> Result<Self, String> {
+        match version {
+            11 => Ok(SchemaVersion::V11),
+            18 => Ok(SchemaVersion::V18),
+            _ => {
+                Err(format!("Unsupported schema version {}", version))
+            }
+        }
+    }
+}
+
+const SCHEMA: &str = r#"
+CREATE TABLE col (
+    id              integer primary key,
+    crt             integer not null,
+    mod             integer not null,
+    scm             integer not null,
+    ver             integer not null,
+    dty             integer not null,
+    usn             integer not null,
+    ls              integer not null,
+    conf            text not null,
+    models          text not null,
+    decks           text not null,
+    dconf           text not null,
+    tags            text not null
+);
+CREATE TABLE notes (
+    id              integer primary key,
+    guid            text not null,
+    mid             integer not null,
+    mod             integer not null,
+    usn             integer not null,
+    tags            text not null,
+    flds            text not null,
+    sfld            integer not null,
+    csum            integer not null,
+    flags           integer not null,
+    data            text not null
+);
+CREATE TABLE cards (
+    id              integer primary key,
+    nid             integer not null,
+    did             integer not null,
+    ord             integer not null,
+    mod             integer not null,
+    usn             integer not null,
+    type            integer not null,
+    queue           integer not null,
+    due             integer not null,
+    ivl             integer not null,
+    factor          integer not null,
+    reps            integer not null,
+    lapses          integer not null,
+    left            integer not null,
+    odue            integer not null,
+    odid            integer not null,
+    flags           integer not null,
+    data            text not null
+);
+CREATE TABLE revlog (
+    id              integer primary key,
+    cid             integer not null,
+    usn             integer not null,
+    ease            integer not null,
+    ivl             integer not null,
+    lastIvl         integer not null,
+    factor          integer not null,
+    time            integer not null,
+    type            integer not null
+);
+CREATE TABLE graves (
+    usn             integer not null,
+    oid             integer not null,
+    type            integer not null
+);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+"#;
+
+pub(crate) fn sql_error(err: rusqlite::Error) -> String {
+    err.to_string()
+}
+
+// JSON object keyed by the ids, the layout of col.models, col.decks
+// and col.dconf
+fn json_map<T: Serialize>(
+    items: &[(usize, T)],
+) -> Result<String, String> {
+    let mut map = serde_json::Map::new();
+    for (id, item) in items {
+        let value = serde_json::to_value(item).map_err(json_error)?;
+        map.insert(id.to_string(), value);
+    }
+    Ok(serde_json::Value::Object(map).to_string())
+}
+
+// Models of the collection as they are written, old clients pick the
+// cards of a note by their requirements so those follow the templates
+pub(crate) fn written_models(
+    collection: &Collection,
+) -> Result<Vec<(usize, Model)>, String> {
+    let mut models = collection.models.clone();
+    for (_, model) in models.iter_mut() {
+        model.update_requirements()?;
+    }
+    Ok(models)
+}
+
+pub(crate) fn json_error(err: serde_json::Error) -> String {
+    err.to_string()
+}
+
+// Parses the JSON object layout of col.models, col.decks and col.dconf
+fn parse_json_map<T: DeserializeOwned>(
+    text: &str,
+) -> Result<Vec<(usize, T)>, String> {
+    let map: Map<String, Value> =
+        serde_json::from_str(text).map_err(json_error)?;
+    map.into_iter()
+        .map(|(key, value)| {
+            let id = key
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid id '{}'", key))?;
+            let item =
+                serde_json::from_value(value).map_err(json_error)?;
+            Ok((id, item))
+        })
+        .collect()
+}
+
+// Row as a JSON object keyed by the column names, so the serde renames
+// of the db_model types apply to the columns
+pub(crate) fn row_to_json(
+    row: &Row,
+) -> Result<Map<String, Value>, String> {
+    let mut map = Map::new();
+    for (index, name) in
+        row.as_ref().column_names().iter().enumerate()
+    {
+        let value = match row.get_ref(index).map_err(sql_error)? {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(value) => json!(value),
+            ValueRef::Real(value) => json!(value),
+            ValueRef::Text(text) => Value::String(
+                String::from_utf8_lossy(text).into_owned(),
+            ),
+            ValueRef::Blob(_) => {
+                return Err(format!(
+                    "Unexpected blob in column {}",
+                    name
+                ))
+            }
+        };
+        map.insert(name.to_string(), value);
+    }
+    Ok(map)
+}
+
+pub(crate) fn select_json(
+    connection: &Connection,
+    sql: &str,
+) -> Result<Vec<Map<String, Value>>, String> {
+    let mut statement = connection.prepare(sql).map_err(sql_error)?;
+    let mut rows = statement.query([]).map_err(sql_error)?;
+    let mut result = vec![];
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        result.push(row_to_json(row)?);
+    }
+    Ok(result)
+}
+
+/// Splits notes.tags, which separates the tags with spaces.
+pub fn split_tags(tags: &str) -> Vec<String> {
+    tags.split_whitespace().map(str::to_string).collect()
+}
+
+pub fn split_fields(fields: &str) -> Vec<String> {
+    fields.split(FIELD_SEPARATOR).map(str::to_string).collect()
+}
+
+/// Tags as stored in notes.tags, space separated with a leading and a
+/// trailing space.
+pub fn join_tags(note: &Note) -> String {
+    if note.tags.is_empty() {
+        return String::new();
+    }
+    let tags = note
+        .tags
+        .iter()
+        .map(|tag| tag.name.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(" {} ", tags)
+}
+
+pub fn join_fields(note: &Note) -> String {
+    note.fields.join(&FIELD_SEPARATOR.to_string())
+}
+
+pub fn create_schema(connection: &Connection) -> Result<(), String> {
+    connection.execute_batch(SCHEMA).map_err(sql_error)
+}
+
+pub fn insert_collection(
+    connection: &Connection,
+    collection: &Collection,
+) -> Result<(), String> {
+    let config = serde_json::to_string(&collection.config)
+        .map_err(json_error)?;
+    let tags = if collection.tags.is_empty() {
+        "{}"
+    } else {
+        collection.tags.as_str()
+    };
+    connection
+        .execute(
+            "INSERT INTO col (id, crt, mod, scm, ver, dty, usn, ls, \
+             conf, models, decks, dconf, tags) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                collection.id,
+                collection.created,
+                collection.modified,
+                collection.scheme_mod_time,
+                SCHEMA_VERSION as i64,
+                collection.dirty,
+                collection.update_seq_number,
+                collection.last_sync_time,
+                config,
+                json_map(&written_models(collection)?)?,
+                json_map(&collection.decks)?,
+                json_map(&collection.deck_configs)?,
+                tags,
+            ],
+        )
+        .map_err(sql_error)?;
+    Ok(())
+}
+
+pub fn insert_note(
+    connection: &Connection,
+    note: &Note,
+) -> Result<(), String> {
+    note.insert(connection)
+}
+
+pub fn insert_card(
+    connection: &Connection,
+    card: &Card,
+) -> Result<(), String> {
+    card.insert(connection)
+}
+
+/// Writes a new schema 11 `collection.anki2` database at `path` holding
+/// the collection, its notes and its cards.
+pub fn write_collection(
+    path: &Path,
+    collection: &Collection,
+    notes: &[Note],
+    cards: &[Card],
+) -> Result<(), String> {
+    write_collection_version(
+        path,
+        collection,
+        notes,
+        cards,
+        SchemaVersion::V11,
+    )
+}
+
+/// Writes a new collection database in the given schema version.
+pub fn write_collection_version(
+    path: &Path,
+    collection: &Collection,
+    notes: &[Note],
+    cards: &[Card],
+    version: SchemaVersion,
+) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+
+    let mut connection = Connection::open(path).map_err(sql_error)?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    match version {
+        SchemaVersion::V11 => {
+            create_schema(&transaction)?;
+            insert_collection(&transaction, collection)?;
+        }
+        SchemaVersion::V18 => {
+            schema18::create_schema(&transaction)?;
+            schema18::insert_collection(&transaction, collection)?;
+        }
+    }
+    for note in notes {
+        insert_note(&transaction, note)?;
+    }
+    for card in cards {
+        insert_card(&transaction, card)?;
+    }
+    transaction.commit().map_err(sql_error)
+}
+
+pub fn select_collection(
+    connection: &Connection,
+) -> Result<Collection, String> {
+    let mut rows = select_json(
+        connection,
+        "SELECT id, crt, mod, scm, ver, dty, usn, ls, conf, models, \
+         decks, dconf, tags FROM col",
+    )?;
+    if rows.len() != 1 {
+        return Err(format!(
+            "Expected one col row, found {}",
+            rows.len()
+        ));
+    }
+    let mut row = rows.remove(0);
+    let mut text = |column: &str| match row.remove(column) {
+        Some(Value::String(text)) => Ok(text),
+        _ => Err(format!("col.{} is not text", column)),
+    };
+    let config =
+        serde_json::from_str(&text("conf")?).map_err(json_error)?;
+    let models = parse_json_map(&text("models")?)?;
+    let decks = parse_json_map(&text("decks")?)?;
+    let deck_configs = parse_json_map(&text("dconf")?)?;
+    let tags = text("tags")?;
+
+    let integer = |column: &str| {
+        row.get(column).and_then(Value::as_i64).ok_or_else(|| {
+            format!("col.{} is not an integer", column)
+        })
+    };
+    Ok(Collection {
+        id: integer("id")?,
+        created: integer("crt")?,
+        modified: integer("mod")?,
+        scheme_mod_time: integer("scm")?,
+        version: integer("ver")? as usize,
+        dirty: integer("dty")?,
+        update_seq_number: integer("usn")?,
+        last_sync_time: integer("ls")?,
+        config,
+        models,
+        decks,
+        deck_configs,
+        tags,
+    })
+}
+
+/// Notes with `flds` split into fields and `tags` into tags.
+pub fn select_notes(
+    connection: &Connection,
+) -> Result<Vec<Note>, String> {
+    Note::select_where(connection, "1", [])
+}
+
+pub fn select_cards(
+    connection: &Connection,
+) -> Result<Vec<Card>, String> {
+    Card::select_where(connection, "1", [])
+}
+
+pub fn insert_review_log(
+    connection: &Connection,
+    review_log: &ReviewLog,
+) -> Result<(), String> {
+    review_log.insert(connection)
+}
+
+pub fn select_review_logs(
+    connection: &Connection,
+) -> Result<Vec<ReviewLog>, String> {
+    ReviewLog::select_where(connection, "1", [])
+}
+
+/// Adds the review history to an existing collection database.
+pub fn write_review_logs(
+    path: &Path,
+    review_logs: &[ReviewLog],
+) -> Result<(), String> {
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+
+    let mut connection = Connection::open(path).map_err(sql_error)?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    for review_log in review_logs {
+        insert_review_log(&transaction, review_log)?;
+    }
+    transaction.commit().map_err(sql_error)
+}
+
+/// Reads the review history of a collection database.
+pub fn read_review_logs(
+    path: &Path,
+) -> Result<Vec<ReviewLog>, String> {
+    let connection = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )
+    .map_err(sql_error)?;
+    select_review_logs(&connection)
+}
+
+/// Schema version of the collection in the database.
+pub fn schema_version(
+    connection: &Connection,
+) -> Result<SchemaVersion, String> {
+    let version: usize = connection
+        .query_row("SELECT ver FROM col", [], |row| row.get(0))
+        .map_err(sql_error)?;
+    SchemaVersion::from_number(version)
+}
+
+/// Deletes the card, leaving a grave.
+pub fn delete_card(
+    connection: &Connection,
+    card_id: i64,
+    update_seq_number: i64,
+) -> Result<(), String> {
+    Card::delete_by_id(connection, card_id, update_seq_number)
+}
+
+/// Deletes the note and its cards, leaving graves for all of them.
+pub fn delete_note(
+    connection: &Connection,
+    note_id: i64,
+    update_seq_number: i64,
+) -> Result<(), String> {
+    for card in Card::select_where(connection, "nid = ?1", [note_id])?
+    {
+        delete_card(connection, card.id, update_seq_number)?;
+    }
+    Note::delete_by_id(connection, note_id, update_seq_number)
+}
+
+// Runs the changes in a savepoint, so they are all undone on error
+fn in_savepoint<T>(
+    connection: &Connection,
+    change: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    connection
+        .execute_batch("SAVEPOINT change")
+        .map_err(sql_error)?;
+    let result = change();
+    let end = match result {
+        Ok(_) => "RELEASE change",
+        Err(_) => "ROLLBACK TO change; RELEASE change",
+    };
+    connection.execute_batch(end).map_err(sql_error)?;
+    result
+}
+
+/// Deletes the deck and its subdecks as `Collection::remove_deck`
+/// does, with their cards and the notes left without cards, leaving
+/// graves for all of them. The default deck can't be deleted. Nothing
+/// changes on error.
+pub fn delete_deck(
+    connection: &Connection,
+    deck_id: i64,
+    update_seq_number: i64,
+) -> Result<(), String> {
+    let version = schema_version(connection)?;
+    let mut collection = match version {
+        SchemaVersion::V11 => select_collection(connection)?,
+        SchemaVersion::V18 => {
+            schema18::select_collection(connection)?
+        }
+    };
+    let mut notes = select_notes(connection)?;
+    let original_cards = select_cards(connection)?;
+    let mut cards = original_cards.clone();
+    let graves = collection.remove_deck(
+        deck_id as usize,
+        &mut notes,
+        &mut cards,
+        CardRemoval::Delete,
+        update_seq_number,
+    )?;
+
+    in_savepoint(connection, || {
+        // Cards the removal changed without deleting them
+        for card in &cards {
+            if !original_cards.contains(card) {
+                card.update(connection)?;
+            }
+        }
+        let mut removed_decks = vec![];
+        for grave in &graves {
+            match grave.grave_type {
+                GraveType::Card => Card::delete_by_id(
+                    connection,
+                    grave.object_id,
+                    update_seq_number,
+                )?,
+                GraveType::Note => Note::delete_by_id(
+                    connection,
+                    grave.object_id,
+                    update_seq_number,
+                )?,
+                GraveType::Deck => {
+                    removed_decks.push(grave.object_id)
+                }
+            }
+        }
+        match version {
+            SchemaVersion::V18 => {
+                for id in removed_decks {
+                    Deck::delete_by_id(
+                        connection,
+                        id,
+                        update_seq_number,
+                    )?;
+                }
+                Ok(())
+            }
+            // Schema 11 decks are entries of the col.decks JSON map
+            SchemaVersion::V11 => {
+                let decks: String = connection
+                    .query_row("SELECT decks FROM col", [], |row| {
+                        row.get(0)
+                    })
+                    .map_err(sql_error)?;
+                let mut decks: Map<String, Value> =
+                    serde_json::from_str(&decks)
+                        .map_err(json_error)?;
+                for id in removed_decks {
+                    decks.remove(&id.to_string());
+                    Grave::new(
+                        id,
+                        GraveType::Deck,
+                        update_seq_number,
+                    )
+                    .insert(connection)?;
+                }
+                connection
+                    .execute(
+                        "UPDATE col SET decks = ?1",
+                        [Value::Object(decks).to_string()],
+                    )
+                    .map_err(sql_error)?;
+                Ok(())
+            }
+        }
+    })
+}
+
+/// Reads the collection, notes and cards of a schema 11 or schema 18
+/// collection database.
+pub fn read_collection(
+    path: &Path,
+) -> Result<(Collection, Vec<Note>, Vec<Card>), String> {
+    let connection = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )
+    .map_err(sql_error)?;
+    let collection = match schema_version(&connection)? {
+        SchemaVersion::V11 => select_collection(&connection)?,
+        SchemaVersion::V18 => {
+            schema18::select_collection(&connection)?
+        }
+    };
+    let notes = select_notes(&connection)?;
+    let cards = select_cards(&connection)?;
+    Ok((collection, notes, cards))
+}
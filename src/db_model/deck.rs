@@ -1,6 +1,10 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Row;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
+use super::grave::GraveType;
+use super::schema18;
 use super::table::Table;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -33,6 +37,9 @@ pub struct Deck {
     pub modified: i64,
     #[serde(rename = "desc")]
     pub description: String,
+    // Only filtered decks have the keys of the options
+    #[serde(flatten)]
+    pub filtered_options: Option<FilteredDeckOptions>,
 }
 
 impl Deck {
@@ -53,8 +60,26 @@ impl Deck {
             id: 0,
             modified: 0,
             description: String::new(),
+            filtered_options: None,
         }
     }
+
+    /// A filtered deck, its cards are gathered from other decks by the
+    /// options' searches.
+    pub fn new_filtered(
+        name: String,
+        options: FilteredDeckOptions,
+    ) -> Self {
+        Self {
+            filtered: 1,
+            filtered_options: Some(options),
+            ..Self::new(name)
+        }
+    }
+
+    pub fn is_filtered(&self) -> bool {
+        self.filtered != 0
+    }
 }
 
 impl Default for Deck {
@@ -63,27 +88,150 @@ impl Default for Deck {
     }
 }
 
+/// Order of the cards a filtered deck search gathers.
+#[derive(
+    Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
+#[repr(u8)]
+pub enum FilteredSearchOrder {
+    OldestReviewedFirst = 0,
+    Random = 1,
+    IntervalsAscending = 2,
+    IntervalsDescending = 3,
+    Lapses = 4,
+    Added = 5,
+    Due = 6,
+    ReverseAdded = 7,
+    DuePriority = 8,
+}
+
+/// One search of a filtered deck, stored as `[search, limit, order]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(
+    from = "(String, usize, FilteredSearchOrder)",
+    into = "(String, usize, FilteredSearchOrder)"
+)]
+pub struct FilteredSearchTerm {
+    pub search: String,
+    pub limit: usize,
+    pub order: FilteredSearchOrder,
+}
+
+impl FilteredSearchTerm {
+    pub fn new(
+        search: &str,
+        limit: usize,
+        order: FilteredSearchOrder,
+    ) -> Self {
+        Self {
+            search: search.to_string(),
+            limit,
+            order,
+        }
+    }
+}
+
+impl From<(String, usize, FilteredSearchOrder)>
+    for FilteredSearchTerm
+{
+    fn from(
+        (search, limit, order): (String, usize, FilteredSearchOrder),
+    ) -> Self {
+        Self {
+            search,
+            limit,
+            order,
+        }
+    }
+}
+
+impl From<FilteredSearchTerm>
+    for (String, usize, FilteredSearchOrder)
+{
+    fn from(term: FilteredSearchTerm) -> Self {
+        (term.search, term.limit, term.order)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FilteredDeckOptions {
+    // Answers change the scheduling of the cards, otherwise the deck
+    // only previews them
+    #[serde(rename = "resched")]
+    pub reschedule: bool,
+    // Anki allows one or two searches
+    pub terms: Vec<FilteredSearchTerm>,
+    // Unused, older clients expect it
+    #[serde(default = "FilteredDeckOptions::default_separate")]
+    pub separate: bool,
+    // Preview delays in minutes of the v1 scheduler
+    #[serde(default)]
+    pub delays: Option<Vec<f32>>,
+    // Preview delay in minutes of the v2 scheduler
+    #[serde(default, rename = "previewDelay")]
+    pub preview_delay: u32,
+    // Preview delays of the v3 scheduler
+    #[serde(default, rename = "previewAgainSecs")]
+    pub preview_again_secs: u32,
+    #[serde(default, rename = "previewHardSecs")]
+    pub preview_hard_secs: u32,
+    #[serde(default, rename = "previewGoodSecs")]
+    pub preview_good_secs: u32,
+}
+
+impl FilteredDeckOptions {
+    /// Options with Anki's defaults for a new filtered deck.
+    pub fn new(terms: Vec<FilteredSearchTerm>) -> Self {
+        Self {
+            reschedule: true,
+            terms,
+            separate: true,
+            delays: None,
+            preview_delay: 10,
+            preview_again_secs: 60,
+            preview_hard_secs: 600,
+            preview_good_secs: 0,
+        }
+    }
+
+    fn default_separate() -> bool {
+        true
+    }
+}
+
+#[derive(
+    Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
+#[repr(u8)]
+pub enum LeechAction {
+    Suspend = 0,
+    TagOnly = 1,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct LapseConfig {
-    pub delays: Vec<usize>,
+    // Relearning steps in minutes
+    pub delays: Vec<f32>,
     #[serde(rename = "leechAction")]
-    pub leech_action: usize,
+    pub leech_action: LeechAction,
     #[serde(rename = "leechFails")]
     pub leech_fails: usize,
     #[serde(rename = "minInt")]
     pub min_interval: usize,
+    // Share of the interval kept after a lapse
     #[serde(rename = "mult")]
-    pub interval_increase: usize,
+    pub interval_increase: f32,
 }
 
 impl LapseConfig {
+    /// Anki's default lapse options.
     pub fn new() -> Self {
         Self {
-            delays: vec![0],
-            leech_action: 0,
-            leech_fails: 0,
-            min_interval: 0,
-            interval_increase: 0,
+            delays: vec![10.0],
+            leech_action: LeechAction::TagOnly,
+            leech_fails: 8,
+            min_interval: 1,
+            interval_increase: 0.0,
         }
     }
 }
@@ -94,7 +242,9 @@ impl Default for LapseConfig {
     }
 }
 
-#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, PartialEq)]
+#[derive(
+    Serialize_repr, Deserialize_repr, Debug, Clone, PartialEq,
+)]
 #[repr(u8)]
 pub enum NewCardOrder {
     Random = 0,
@@ -104,9 +254,12 @@ pub enum NewCardOrder {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct NewConfig {
     pub bury: bool,
-    pub delays: Vec<usize>,
+    // Learning steps in minutes
+    pub delays: Vec<f32>,
+    // Starting ease in permille
     #[serde(rename = "initialFactor")]
     pub initial_factor: usize,
+    // Graduating and easy intervals in days
     #[serde(rename = "ints")]
     pub intervals: Vec<usize>,
     pub order: NewCardOrder,
@@ -116,15 +269,16 @@ pub struct NewConfig {
 }
 
 impl NewConfig {
+    /// Anki's default new card options.
     pub fn new() -> Self {
         Self {
             bury: false,
-            delays: vec![0],
-            initial_factor: 0,
-            intervals: vec![0],
-            order: NewCardOrder::Random,
-            per_day: 0,
-            separate: false,
+            delays: vec![1.0, 10.0],
+            initial_factor: 2500,
+            intervals: vec![1, 4],
+            order: NewCardOrder::Due,
+            per_day: 20,
+            separate: true,
         }
     }
 }
@@ -135,14 +289,23 @@ impl Default for NewConfig {
     }
 }
 
+fn default_hard_factor() -> f32 {
+    1.2
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ReviewConfig {
     pub bury: bool,
+    // Easy bonus
     #[serde(rename = "ease4")]
-    pub ease_factor: usize,
-    pub fuzz: usize,
+    pub ease_factor: f32,
+    pub fuzz: f32,
+    // Hard interval, missing before Anki 2.1.11
+    #[serde(rename = "hardFactor", default = "default_hard_factor")]
+    pub hard_factor: f32,
+    // Interval modifier
     #[serde(rename = "ivlFct")]
-    pub interval_factor: usize,
+    pub interval_factor: f32,
     #[serde(rename = "maxIvl")]
     pub max_interval: usize,
     // Unused
@@ -153,15 +316,17 @@ pub struct ReviewConfig {
 }
 
 impl ReviewConfig {
+    /// Anki's default review options.
     pub fn new() -> Self {
         Self {
             bury: false,
-            ease_factor: 0,
-            fuzz: 0,
-            interval_factor: 0,
-            max_interval: 0,
-            min_space: 0,
-            cards_daily: 0,
+            ease_factor: 1.3,
+            fuzz: 0.05,
+            hard_factor: default_hard_factor(),
+            interval_factor: 1.0,
+            max_interval: 36500,
+            min_space: 1,
+            cards_daily: 200,
         }
     }
 }
@@ -172,9 +337,24 @@ impl Default for ReviewConfig {
     }
 }
 
+// Only schema 18 has a decks table, schema 11 keeps the decks as JSON
+// in col.decks
 impl Table for Deck {
-    fn table_name(&self) -> &'static str {
-        "decks"
+    const TABLE_NAME: &'static str = "decks";
+    const COLUMNS: &'static [&'static str] =
+        &["id", "name", "mtime_secs", "usn", "common", "kind"];
+    const GRAVE_TYPE: Option<GraveType> = Some(GraveType::Deck);
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn to_row(&self) -> Result<Vec<SqlValue>, String> {
+        Ok(schema18::deck_to_row(self))
+    }
+
+    fn from_row(row: &Row) -> Result<Self, String> {
+        schema18::deck_from_row(row)
     }
 }
 
@@ -203,17 +383,18 @@ pub struct DeckConfig {
 }
 
 impl DeckConfig {
+    /// Options with Anki's defaults, as in its Default preset.
     pub fn new(name: String) -> Self {
         Self {
-            autoplay: false,
+            autoplay: true,
             filtered: None,
             id: None,
             lapse_config: LapseConfig::new(),
-            max_taken: 0,
+            max_taken: 60,
             modified: 0,
             name,
             new_config: NewConfig::new(),
-            replay_question: false,
+            replay_question: true,
             review_config: ReviewConfig::new(),
             timer: false,
             update_seq_number: 0,
@@ -0,0 +1,369 @@
+use std::fmt;
+
+use crate::db_model::deck::{DeckConfig, LeechAction, NewCardOrder};
+
+/// Longest interval Anki allows, in days.
+pub const MAX_INTERVAL_LIMIT: usize = 36500;
+
+/// An option of a deck config outside the range Anki accepts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeckConfigError {
+    /// Learning steps must be positive minutes.
+    LearningStep(f32),
+    RelearningStep(f32),
+    /// Starting ease in permille, from 1310 to 5000.
+    StartingEase(usize),
+    /// From 1 to 5.
+    EasyBonus(f32),
+    /// From 0.5 to 1.3.
+    HardInterval(f32),
+    /// From 0.5 to 2.
+    IntervalModifier(f32),
+    /// Share of the interval kept after a lapse, from 0 to 1.
+    NewInterval(f32),
+    /// From 0 to 1.
+    Fuzz(f32),
+    /// From 1 to 36500 days.
+    MaximumInterval(usize),
+    /// At least a day and at most the maximum interval.
+    MinimumInterval {
+        minimum: usize,
+        maximum: usize,
+    },
+    /// The graduating interval is at least a day, the easy interval
+    /// at least the graduating one, both at most the maximum
+    /// interval.
+    GraduatingIntervals {
+        good: usize,
+        easy: usize,
+    },
+    LeechThreshold(usize),
+}
+
+impl fmt::Display for DeckConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::LearningStep(step) => {
+                write!(f, "Invalid learning step {}", step)
+            }
+            Self::RelearningStep(step) => {
+                write!(f, "Invalid relearning step {}", step)
+            }
+            Self::StartingEase(ease) => write!(
+                f,
+                "Starting ease {} is not between 1.31 and 5",
+                *ease as f32 / 1000.0
+            ),
+            Self::EasyBonus(bonus) => write!(
+                f,
+                "Easy bonus {} is not between 1 and 5",
+                bonus
+            ),
+            Self::HardInterval(factor) => write!(
+                f,
+                "Hard interval {} is not between 0.5 and 1.3",
+                factor
+            ),
+            Self::IntervalModifier(factor) => write!(
+                f,
+                "Interval modifier {} is not between 0.5 and 2",
+                factor
+            ),
+            Self::NewInterval(factor) => write!(
+                f,
+                "New interval {} is not between 0 and 1",
+                factor
+            ),
+            Self::Fuzz(fuzz) => {
+                write!(f, "Fuzz {} is not between 0 and 1", fuzz)
+            }
+            Self::MaximumInterval(days) => write!(
+                f,
+                "Maximum interval {} is not between 1 and {} days",
+                days, MAX_INTERVAL_LIMIT
+            ),
+            Self::MinimumInterval { minimum, maximum } => write!(
+                f,
+                "Minimum interval {} is not between 1 and {} days",
+                minimum, maximum
+            ),
+            Self::GraduatingIntervals { good, easy } => write!(
+                f,
+                "Invalid graduating interval {} and easy interval {}",
+                good, easy
+            ),
+            Self::LeechThreshold(lapses) => {
+                write!(f, "Invalid leech threshold {}", lapses)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeckConfigError {}
+
+impl From<DeckConfigError> for String {
+    fn from(err: DeckConfigError) -> Self {
+        err.to_string()
+    }
+}
+
+fn check_range(
+    value: f32,
+    min: f32,
+    max: f32,
+    err: fn(f32) -> DeckConfigError,
+) -> Result<(), DeckConfigError> {
+    // Also rejects NaN
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(err(value))
+    }
+}
+
+fn check_steps(
+    steps: &[f32],
+    err: fn(f32) -> DeckConfigError,
+) -> Result<(), DeckConfigError> {
+    match steps
+        .iter()
+        .find(|step| !(step.is_finite() && **step > 0.0))
+    {
+        Some(step) => Err(err(*step)),
+        None => Ok(()),
+    }
+}
+
+impl DeckConfig {
+    /// Options for an exam in the coming days: many new cards with
+    /// short steps, and intervals capped at a week.
+    pub fn cramming(name: String) -> Self {
+        let mut config = Self::new(name);
+        let new = &mut config.new_config;
+        new.delays = vec![1.0, 10.0, 60.0];
+        new.intervals = vec![1, 2];
+        new.per_day = 100;
+        let review = &mut config.review_config;
+        review.interval_factor = 0.5;
+        review.max_interval = 7;
+        review.cards_daily = 9999;
+        let lapse = &mut config.lapse_config;
+        lapse.delays = vec![1.0, 10.0];
+        config
+    }
+
+    /// Options for vocabulary with recognition and recall cards:
+    /// siblings are buried, lapses relearn the next day and keep some
+    /// of their interval, and leeches are suspended early.
+    pub fn language_learning(name: String) -> Self {
+        let mut config = Self::new(name);
+        let new = &mut config.new_config;
+        new.bury = true;
+        new.delays = vec![1.0, 10.0, 60.0];
+        new.intervals = vec![1, 3];
+        new.per_day = 15;
+        let review = &mut config.review_config;
+        review.bury = true;
+        review.max_interval = 3650;
+        review.cards_daily = 300;
+        let lapse = &mut config.lapse_config;
+        lapse.delays = vec![10.0, 1440.0];
+        lapse.interval_increase = 0.2;
+        lapse.leech_action = LeechAction::Suspend;
+        lapse.leech_fails = 6;
+        config
+    }
+
+    pub fn builder(name: String) -> DeckConfigBuilder {
+        DeckConfigBuilder::new(name)
+    }
+
+    /// Checks that the options are within the ranges of Anki's deck
+    /// options screen.
+    pub fn validate(&self) -> Result<(), DeckConfigError> {
+        let new = &self.new_config;
+        let review = &self.review_config;
+        let lapse = &self.lapse_config;
+
+        check_steps(&new.delays, DeckConfigError::LearningStep)?;
+        check_steps(&lapse.delays, DeckConfigError::RelearningStep)?;
+        if !(1310..=5000).contains(&new.initial_factor) {
+            return Err(DeckConfigError::StartingEase(
+                new.initial_factor,
+            ));
+        }
+        check_range(
+            review.ease_factor,
+            1.0,
+            5.0,
+            DeckConfigError::EasyBonus,
+        )?;
+        check_range(
+            review.hard_factor,
+            0.5,
+            1.3,
+            DeckConfigError::HardInterval,
+        )?;
+        check_range(
+            review.interval_factor,
+            0.5,
+            2.0,
+            DeckConfigError::IntervalModifier,
+        )?;
+        check_range(
+            lapse.interval_increase,
+            0.0,
+            1.0,
+            DeckConfigError::NewInterval,
+        )?;
+        check_range(review.fuzz, 0.0, 1.0, DeckConfigError::Fuzz)?;
+
+        let maximum = review.max_interval;
+        if !(1..=MAX_INTERVAL_LIMIT).contains(&maximum) {
+            return Err(DeckConfigError::MaximumInterval(maximum));
+        }
+        if !(1..=maximum).contains(&lapse.min_interval) {
+            return Err(DeckConfigError::MinimumInterval {
+                minimum: lapse.min_interval,
+                maximum,
+            });
+        }
+        let good = new.intervals.first().copied().unwrap_or_default();
+        let easy = new.intervals.get(1).copied().unwrap_or_default();
+        if good < 1 || easy < good || easy > maximum {
+            return Err(DeckConfigError::GraduatingIntervals {
+                good,
+                easy,
+            });
+        }
+        if lapse.leech_fails < 1 {
+            return Err(DeckConfigError::LeechThreshold(
+                lapse.leech_fails,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Builds a validated deck config, starting from Anki's defaults or
+/// from a preset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeckConfigBuilder {
+    config: DeckConfig,
+}
+
+impl From<DeckConfig> for DeckConfigBuilder {
+    fn from(config: DeckConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl DeckConfigBuilder {
+    pub fn new(name: String) -> Self {
+        Self::from(DeckConfig::new(name))
+    }
+
+    pub fn id(mut self, id: usize) -> Self {
+        self.config.id = Some(id);
+        self
+    }
+
+    pub fn new_per_day(mut self, cards: usize) -> Self {
+        self.config.new_config.per_day = cards;
+        self
+    }
+
+    pub fn reviews_per_day(mut self, cards: usize) -> Self {
+        self.config.review_config.cards_daily = cards;
+        self
+    }
+
+    /// Steps in minutes.
+    pub fn learning_steps(mut self, steps: &[f32]) -> Self {
+        self.config.new_config.delays = steps.to_vec();
+        self
+    }
+
+    /// Steps in minutes.
+    pub fn relearning_steps(mut self, steps: &[f32]) -> Self {
+        self.config.lapse_config.delays = steps.to_vec();
+        self
+    }
+
+    /// Intervals in days after answering good and easy on the last
+    /// learning step.
+    pub fn graduating_intervals(
+        mut self,
+        good: usize,
+        easy: usize,
+    ) -> Self {
+        self.config.new_config.intervals = vec![good, easy];
+        self
+    }
+
+    pub fn new_card_order(mut self, order: NewCardOrder) -> Self {
+        self.config.new_config.order = order;
+        self
+    }
+
+    /// Ease as a factor, 2.5 being Anki's default.
+    pub fn starting_ease(mut self, ease: f32) -> Self {
+        self.config.new_config.initial_factor =
+            (ease * 1000.0).round() as usize;
+        self
+    }
+
+    pub fn easy_bonus(mut self, factor: f32) -> Self {
+        self.config.review_config.ease_factor = factor;
+        self
+    }
+
+    pub fn hard_interval(mut self, factor: f32) -> Self {
+        self.config.review_config.hard_factor = factor;
+        self
+    }
+
+    pub fn interval_modifier(mut self, factor: f32) -> Self {
+        self.config.review_config.interval_factor = factor;
+        self
+    }
+
+    /// Longest interval in days.
+    pub fn maximum_interval(mut self, days: usize) -> Self {
+        self.config.review_config.max_interval = days;
+        self
+    }
+
+    /// Share of the interval a lapsed card keeps.
+    pub fn new_interval(mut self, factor: f32) -> Self {
+        self.config.lapse_config.interval_increase = factor;
+        self
+    }
+
+    /// Shortest interval in days after a lapse.
+    pub fn minimum_interval(mut self, days: usize) -> Self {
+        self.config.lapse_config.min_interval = days;
+        self
+    }
+
+    pub fn leech(
+        mut self,
+        threshold: usize,
+        action: LeechAction,
+    ) -> Self {
+        self.config.lapse_config.leech_fails = threshold;
+        self.config.lapse_config.leech_action = action;
+        self
+    }
+
+    pub fn bury_siblings(mut self, new: bool, reviews: bool) -> Self {
+        self.config.new_config.bury = new;
+        self.config.review_config.bury = reviews;
+        self
+    }
+
+    pub fn build(self) -> Result<DeckConfig, DeckConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
@@ -0,0 +1,343 @@
+use crate::db_model::card::Card;
+use crate::db_model::collection::Collection;
+use crate::db_model::database::DEFAULT_DECK_ID;
+use crate::db_model::deck::Deck;
+use crate::db_model::grave::{Grave, GraveType};
+use crate::db_model::id::{now_millis, IdAllocator};
+use crate::db_model::note::Note;
+
+pub const DECK_SEPARATOR: &str = "::";
+
+/// Components of a deck name, an error for empty ones such as in
+/// `Spanish::::Verbs`.
+pub fn deck_components(name: &str) -> Result<Vec<&str>, String> {
+    let components = name
+        .split(DECK_SEPARATOR)
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    if components.iter().any(|component| component.is_empty()) {
+        return Err(format!("Invalid deck name '{}'", name));
+    }
+    Ok(components)
+}
+
+/// Name of the parent deck, None for top level decks.
+pub fn parent_name(name: &str) -> Option<&str> {
+    name.rsplit_once(DECK_SEPARATOR).map(|(parent, _)| parent)
+}
+
+fn is_descendant_name(name: &str, ancestor: &str) -> bool {
+    name.get(..ancestor.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(ancestor))
+        && name[ancestor.len()..].starts_with(DECK_SEPARATOR)
+        && name.len() > ancestor.len() + DECK_SEPARATOR.len()
+}
+
+/// A deck with its child decks, as shown in Anki's deck list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeckTreeNode {
+    pub id: usize,
+    // last component of the deck name
+    pub name: String,
+    pub children: Vec<DeckTreeNode>,
+}
+
+/// What happens to the cards of a removed deck.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CardRemoval {
+    /// Delete the cards, and the notes left without cards.
+    Delete,
+    /// Move the cards to the deck with the id.
+    MoveTo(usize),
+}
+
+impl Collection {
+    pub fn deck(&self, id: usize) -> Option<&Deck> {
+        self.decks
+            .iter()
+            .find(|(deck_id, _)| *deck_id == id)
+            .map(|(_, deck)| deck)
+    }
+
+    /// Id of the deck with the name, ignoring case as Anki does.
+    pub fn deck_id(&self, name: &str) -> Option<usize> {
+        self.decks
+            .iter()
+            .find(|(_, deck)| deck.name.eq_ignore_ascii_case(name))
+            .map(|(id, _)| *id)
+    }
+
+    pub fn parent_deck(&self, id: usize) -> Option<usize> {
+        self.deck_id(parent_name(&self.deck(id)?.name)?)
+    }
+
+    /// Direct children of the deck, sorted by name.
+    pub fn child_decks(&self, id: usize) -> Vec<usize> {
+        let Some(deck) = self.deck(id) else {
+            return vec![];
+        };
+        let mut children = self
+            .decks
+            .iter()
+            .filter(|(_, child)| {
+                parent_name(&child.name).is_some_and(|parent| {
+                    parent.eq_ignore_ascii_case(&deck.name)
+                })
+            })
+            .map(|(id, child)| (child.name.to_lowercase(), *id))
+            .collect::<Vec<_>>();
+        children.sort();
+        children.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Every deck below the deck, at any depth.
+    pub fn descendant_decks(&self, id: usize) -> Vec<usize> {
+        let Some(deck) = self.deck(id) else {
+            return vec![];
+        };
+        self.decks
+            .iter()
+            .filter(|(_, other)| {
+                is_descendant_name(&other.name, &deck.name)
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Top level decks with their children, sorted by name.
+    pub fn deck_tree(&self) -> Vec<DeckTreeNode> {
+        let mut roots = self
+            .decks
+            .iter()
+            .filter(|(_, deck)| parent_name(&deck.name).is_none())
+            .map(|(id, deck)| (deck.name.to_lowercase(), *id))
+            .collect::<Vec<_>>();
+        roots.sort();
+        roots
+            .into_iter()
+            .map(|(_, id)| self.deck_tree_node(id))
+            .collect()
+    }
+
+    fn deck_tree_node(&self, id: usize) -> DeckTreeNode {
+        let name = self
+            .deck(id)
+            .map(|deck| deck.name.as_str())
+            .unwrap_or_default();
+        DeckTreeNode {
+            id,
+            name: name
+                .rsplit(DECK_SEPARATOR)
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            children: self
+                .child_decks(id)
+                .into_iter()
+                .map(|child| self.deck_tree_node(child))
+                .collect(),
+        }
+    }
+
+    /// Id of the deck with the name, adding it and any missing parent
+    /// decks, so adding `Spanish::Verbs::Irregular` also adds `Spanish`
+    /// and `Spanish::Verbs`.
+    pub fn add_deck(
+        &mut self,
+        name: &str,
+        ids: &mut IdAllocator,
+    ) -> Result<usize, String> {
+        let components = deck_components(name)?;
+        let mut id = None;
+        for depth in 1..=components.len() {
+            let path = components[..depth].join(DECK_SEPARATOR);
+            id = Some(match self.deck_id(&path) {
+                Some(id)
+                    if depth < components.len()
+                        && self
+                            .deck(id)
+                            .is_some_and(Deck::is_filtered) =>
+                {
+                    return Err(format!(
+                        "Filtered deck '{}' cannot have subdecks",
+                        path
+                    ))
+                }
+                Some(id) => id,
+                None => {
+                    let id = ids.next_id();
+                    let mut deck = Deck::new(path);
+                    deck.id = id;
+                    deck.config_id = Some(1);
+                    deck.update_seq_number = -1;
+                    deck.modified = now_millis() / 1000;
+                    self.decks.push((id as usize, deck));
+                    id as usize
+                }
+            });
+        }
+        id.ok_or_else(|| format!("Invalid deck name '{}'", name))
+    }
+
+    // Adds the missing parents of a new deck name, an error if its
+    // parent is a filtered deck
+    pub(crate) fn add_parent_decks(
+        &mut self,
+        name: &str,
+        ids: &mut IdAllocator,
+    ) -> Result<(), String> {
+        let Some(parent) = parent_name(name) else {
+            return Ok(());
+        };
+        let parent_id = self.add_deck(parent, ids)?;
+        if self.deck(parent_id).is_some_and(Deck::is_filtered) {
+            return Err(format!(
+                "Filtered deck '{}' cannot have subdecks",
+                parent
+            ));
+        }
+        Ok(())
+    }
+
+    /// Renames the deck and moves its subtree along, e.g. renaming
+    /// `Spanish::Verbs` to `Grammar::Verbs` renames
+    /// `Spanish::Verbs::Irregular` to `Grammar::Verbs::Irregular`.
+    /// Missing parents of the new name are added.
+    pub fn rename_deck(
+        &mut self,
+        id: usize,
+        new_name: &str,
+        ids: &mut IdAllocator,
+    ) -> Result<(), String> {
+        let new_name =
+            deck_components(new_name)?.join(DECK_SEPARATOR);
+        let old_name = self
+            .deck(id)
+            .ok_or_else(|| format!("No deck {}", id))?
+            .name
+            .clone();
+        if is_descendant_name(&new_name, &old_name) {
+            return Err(format!(
+                "Cannot move '{}' into its own subdeck",
+                old_name
+            ));
+        }
+        if self.deck_id(&new_name).is_some_and(|other| other != id) {
+            return Err(format!(
+                "Deck '{}' already exists",
+                new_name
+            ));
+        }
+
+        self.add_parent_decks(&new_name, ids)?;
+        let modified = now_millis() / 1000;
+        for (deck_id, deck) in self.decks.iter_mut() {
+            let renamed = if *deck_id == id {
+                new_name.clone()
+            } else if is_descendant_name(&deck.name, &old_name) {
+                format!(
+                    "{}{}",
+                    new_name,
+                    &deck.name[old_name.len()..]
+                )
+            } else {
+                continue;
+            };
+            deck.name = renamed;
+            deck.modified = modified;
+            deck.update_seq_number = -1;
+        }
+        Ok(())
+    }
+
+    /// Removes the deck and its subdecks. Filtered decks among them
+    /// are emptied first, returning their cards home, then the cards
+    /// are deleted or moved to another deck. Returns the graves of
+    /// everything deleted.
+    pub fn remove_deck(
+        &mut self,
+        id: usize,
+        notes: &mut Vec<Note>,
+        cards: &mut Vec<Card>,
+        removal: CardRemoval,
+        update_seq_number: i64,
+    ) -> Result<Vec<Grave>, String> {
+        if id as i64 == DEFAULT_DECK_ID {
+            return Err(
+                "The default deck cannot be deleted".to_string()
+            );
+        }
+        if self.deck(id).is_none() {
+            return Err(format!("No deck {}", id));
+        }
+        let mut removed = self.descendant_decks(id);
+        removed.push(id);
+        if let CardRemoval::MoveTo(target) = removal {
+            if self.deck(target).is_none()
+                || removed.contains(&target)
+            {
+                return Err(format!(
+                    "Cannot move cards to deck {}",
+                    target
+                ));
+            }
+        }
+
+        for deck_id in &removed {
+            if self.deck(*deck_id).is_some_and(Deck::is_filtered) {
+                self.empty_filtered_deck(*deck_id, cards)?;
+            }
+        }
+
+        let grave = |id: i64, grave_type| {
+            Grave::new(id, grave_type, update_seq_number)
+        };
+        let mut graves = vec![];
+        match removal {
+            CardRemoval::MoveTo(target) => {
+                for card in cards.iter_mut() {
+                    if removed.contains(&card.deck_id) {
+                        card.deck_id = target;
+                        card.update_seq_number = -1;
+                    }
+                    if removed.contains(&card.original_deck_id) {
+                        card.original_deck_id = target;
+                        card.update_seq_number = -1;
+                    }
+                }
+            }
+            CardRemoval::Delete => {
+                let mut touched_notes = vec![];
+                cards.retain(|card| {
+                    let keep = !removed.contains(&card.deck_id)
+                        && !removed.contains(&card.original_deck_id);
+                    if !keep {
+                        graves.push(grave(card.id, GraveType::Card));
+                        touched_notes.push(card.note_id as i64);
+                    }
+                    keep
+                });
+                // Notes whose last cards were deleted go with them
+                notes.retain(|note| {
+                    let keep = !touched_notes.contains(&note.id)
+                        || cards.iter().any(|card| {
+                            card.note_id as i64 == note.id
+                        });
+                    if !keep {
+                        graves.push(grave(note.id, GraveType::Note));
+                    }
+                    keep
+                });
+            }
+        }
+
+        self.decks.retain(|(deck_id, _)| {
+            let keep = !removed.contains(deck_id);
+            if !keep {
+                graves.push(grave(*deck_id as i64, GraveType::Deck));
+            }
+            keep
+        });
+        Ok(graves)
+    }
+}
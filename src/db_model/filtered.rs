@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use crate::db_model::card::{Card, CardQueue, CardType};
+use crate::db_model::collection::Collection;
+use crate::db_model::deck::{
+    Deck, FilteredDeckOptions, FilteredSearchOrder,
+};
+use crate::db_model::deck_tree::{deck_components, DECK_SEPARATOR};
+use crate::db_model::id::{now_millis, IdAllocator};
+use crate::db_model::model::Model;
+use crate::db_model::note::Note;
+
+// Filtered decks put their cards before the others, Anki numbers them
+// from here
+const FILTERED_POSITION_BASE: i64 = -100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+enum SearchTerm {
+    Deck(String),
+    Tag(String),
+    Is(String),
+    Note(String),
+    Field(String, String),
+    Text(String),
+}
+
+/// Terms of an Anki search, each negated or not, all of which must
+/// match. Grouping and `or` are not supported.
+fn parse_search(
+    search: &str,
+) -> Result<Vec<(bool, SearchTerm)>, String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    for character in search.chars() {
+        match character {
+            '"' => quoted = !quoted,
+            character if character.is_whitespace() && !quoted => {
+                tokens.push(std::mem::take(&mut token))
+            }
+            character => token.push(character),
+        }
+    }
+    tokens.push(token);
+
+    tokens
+        .into_iter()
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            if token.eq_ignore_ascii_case("or")
+                || token.starts_with('(')
+                || token.ends_with(')')
+            {
+                return Err(format!(
+                    "Unsupported search '{}', only terms that all \
+                     match are supported",
+                    search
+                ));
+            }
+            let (negated, token) = match token.strip_prefix('-') {
+                Some(token) => (true, token.to_string()),
+                None => (false, token),
+            };
+            let term = match token.split_once(':') {
+                Some((key, value)) => {
+                    match key.to_lowercase().as_str() {
+                        "deck" => SearchTerm::Deck(value.to_string()),
+                        "tag" => SearchTerm::Tag(value.to_string()),
+                        "is" => SearchTerm::Is(value.to_lowercase()),
+                        "note" => SearchTerm::Note(value.to_string()),
+                        _ => SearchTerm::Field(
+                            key.to_string(),
+                            value.to_string(),
+                        ),
+                    }
+                }
+                None => SearchTerm::Text(token),
+            };
+            Ok((negated, term))
+        })
+        .collect()
+}
+
+/// Case insensitive match of the text with a pattern where `*` is any
+/// run of characters.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, start)) = backtrack {
+            p = star + 1;
+            t = start + 1;
+            backtrack = Some((star, start + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|character| *character == '*')
+}
+
+// A name matches itself and its children, as for decks and tags
+fn hierarchy_match(
+    pattern: &str,
+    name: &str,
+    separator: &str,
+) -> bool {
+    wildcard_match(pattern, name)
+        || wildcard_match(&format!("{}{}*", pattern, separator), name)
+}
+
+fn queue_is_due(card: &Card, today: i64) -> bool {
+    match card.queue {
+        CardQueue::Review | CardQueue::InLearning => {
+            card.due <= today
+        }
+        CardQueue::Learning => card.due <= now_millis() / 1000,
+        _ => false,
+    }
+}
+
+impl Collection {
+    /// Adds an empty filtered deck and any missing parent decks,
+    /// returning its id.
+    pub fn add_filtered_deck(
+        &mut self,
+        name: &str,
+        options: FilteredDeckOptions,
+        ids: &mut IdAllocator,
+    ) -> Result<usize, String> {
+        if options.terms.is_empty() {
+            return Err(format!(
+                "Filtered deck '{}' has no search",
+                name
+            ));
+        }
+        let name = deck_components(name)?.join(DECK_SEPARATOR);
+        if self.deck_id(&name).is_some() {
+            return Err(format!("Deck '{}' already exists", name));
+        }
+        self.add_parent_decks(&name, ids)?;
+        let id = ids.next_id();
+        let mut deck = Deck::new_filtered(name, options);
+        deck.id = id;
+        deck.update_seq_number = -1;
+        deck.modified = now_millis() / 1000;
+        self.decks.push((id as usize, deck));
+        Ok(id as usize)
+    }
+
+    fn filtered_options(
+        &self,
+        id: usize,
+    ) -> Result<&FilteredDeckOptions, String> {
+        let deck =
+            self.deck(id).ok_or_else(|| format!("No deck {}", id))?;
+        deck.filtered_options
+            .as_ref()
+            .filter(|_| deck.is_filtered())
+            .ok_or_else(|| {
+                format!("Deck '{}' is not filtered", deck.name)
+            })
+    }
+
+    /// Fills the filtered deck from its searches, after returning the
+    /// cards it already has. Cards keep their home deck and due in
+    /// `original_deck_id` and `original_due`. `today` is the day number
+    /// of review due dates, days since the collection was created.
+    /// Returns the number of cards moved in.
+    pub fn build_filtered_deck(
+        &self,
+        id: usize,
+        notes: &[Note],
+        cards: &mut [Card],
+        today: i64,
+    ) -> Result<usize, String> {
+        let options = self.filtered_options(id)?;
+        let searches = options
+            .terms
+            .iter()
+            .map(|term| parse_search(&term.search))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.empty_filtered_deck(id, cards)?;
+        // Reversed so the first of the notes sharing an id is kept
+        let notes_by_id = notes
+            .iter()
+            .rev()
+            .map(|note| (note.id, note))
+            .collect::<HashMap<_, _>>();
+        let models_by_id = self
+            .models
+            .iter()
+            .rev()
+            .map(|(id, model)| (*id, model))
+            .collect::<HashMap<_, _>>();
+
+        let mut taken = vec![false; cards.len()];
+        let mut position = FILTERED_POSITION_BASE;
+        let modified = now_millis() / 1000;
+        for (term, search) in options.terms.iter().zip(searches) {
+            let mut matching = (0..cards.len())
+                .filter(|index| {
+                    let card = &cards[*index];
+                    if taken[*index] || !self.is_eligible(card) {
+                        return false;
+                    }
+                    let note = notes_by_id
+                        .get(&(card.note_id as i64))
+                        .copied();
+                    let model = note.and_then(|note| {
+                        models_by_id.get(&note.model_id).copied()
+                    });
+                    search.iter().all(|(negated, search_term)| {
+                        self.card_matches(
+                            card,
+                            note,
+                            model,
+                            search_term,
+                            today,
+                        ) != *negated
+                    })
+                })
+                .collect::<Vec<_>>();
+            sort_cards(&mut matching, cards, term.order, today);
+            for index in matching.into_iter().take(term.limit) {
+                taken[index] = true;
+                let card = &mut cards[index];
+                card.original_deck_id = card.deck_id;
+                card.original_due = card.due.max(0) as usize;
+                card.deck_id = id;
+                card.due = position;
+                position += 1;
+                if !options.reschedule {
+                    card.queue = CardQueue::Review;
+                }
+                card.modified = modified;
+                card.update_seq_number = -1;
+            }
+        }
+        Ok((position - FILTERED_POSITION_BASE) as usize)
+    }
+
+    /// Returns the cards of the filtered deck to their home decks with
+    /// their original due, returning how many there were.
+    pub fn empty_filtered_deck(
+        &self,
+        id: usize,
+        cards: &mut [Card],
+    ) -> Result<usize, String> {
+        self.filtered_options(id)?;
+        let modified = now_millis() / 1000;
+        let mut count = 0;
+        for card in cards.iter_mut().filter(|card| card.deck_id == id)
+        {
+            card.deck_id = card.original_deck_id;
+            card.due = card.original_due as i64;
+            card.original_deck_id = 0;
+            card.original_due = 0;
+            if !card.is_suspended_or_buried() {
+                card.queue = card.queue_for_type();
+            }
+            card.modified = modified;
+            card.update_seq_number = -1;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // Cards in filtered decks, suspended or buried are never gathered
+    fn is_eligible(&self, card: &Card) -> bool {
+        card.original_deck_id == 0
+            && !card.is_suspended_or_buried()
+            && self
+                .deck(card.deck_id)
+                .is_some_and(|deck| !deck.is_filtered())
+    }
+
+    fn card_matches(
+        &self,
+        card: &Card,
+        note: Option<&Note>,
+        model: Option<&Model>,
+        term: &SearchTerm,
+        today: i64,
+    ) -> bool {
+        match term {
+            SearchTerm::Deck(pattern) => {
+                self.deck(card.deck_id).is_some_and(|deck| {
+                    hierarchy_match(
+                        pattern,
+                        &deck.name,
+                        DECK_SEPARATOR,
+                    )
+                })
+            }
+            SearchTerm::Tag(pattern) => note.is_some_and(|note| {
+                note.tags.iter().any(|tag| {
+                    hierarchy_match(pattern, &tag.name, "::")
+                })
+            }),
+            SearchTerm::Is(state) => match state.as_str() {
+                "new" => card.card_type == CardType::New,
+                "learn" => matches!(
+                    card.queue,
+                    CardQueue::Learning | CardQueue::InLearning
+                ),
+                "review" => matches!(
+                    card.card_type,
+                    CardType::Review | CardType::Relearning
+                ),
+                "due" => queue_is_due(card, today),
+                "suspended" => card.queue == CardQueue::Suspended,
+                // -2 is the queue of cards buried by the scheduler,
+                // -3 of the ones buried by the user
+                "buried" => matches!(
+                    card.queue,
+                    CardQueue::Buried | CardQueue::UserSuspended
+                ),
+                _ => false,
+            },
+            SearchTerm::Note(pattern) => model.is_some_and(|model| {
+                wildcard_match(pattern, &model.name)
+            }),
+            SearchTerm::Field(name, pattern) => match (note, model) {
+                (Some(note), Some(model)) => model
+                    .fields
+                    .iter()
+                    .filter(|field| {
+                        field.name.eq_ignore_ascii_case(name)
+                    })
+                    .filter_map(|field| {
+                        note.fields.get(field.ordinal)
+                    })
+                    .any(|value| wildcard_match(pattern, value)),
+                _ => false,
+            },
+            SearchTerm::Text(text) => note.is_some_and(|note| {
+                note.fields.iter().any(|value| {
+                    wildcard_match(&format!("*{}*", text), value)
+                })
+            }),
+        }
+    }
+}
+
+fn sort_cards(
+    indices: &mut [usize],
+    cards: &[Card],
+    order: FilteredSearchOrder,
+    today: i64,
+) {
+    match order {
+        // Anki orders by the last review log entry, the modification
+        // time follows it closely without reading the log
+        FilteredSearchOrder::OldestReviewedFirst => {
+            indices.sort_by_key(|index| cards[*index].modified)
+        }
+        FilteredSearchOrder::Random => fastrand::shuffle(indices),
+        FilteredSearchOrder::IntervalsAscending => {
+            indices.sort_by_key(|index| cards[*index].interval)
+        }
+        FilteredSearchOrder::IntervalsDescending => {
+            indices.sort_by_key(|index| -cards[*index].interval)
+        }
+        FilteredSearchOrder::Lapses => {
+            indices.sort_by_key(|index| -cards[*index].lapses)
+        }
+        FilteredSearchOrder::Added => indices.sort_by_key(|index| {
+            (cards[*index].note_id, cards[*index].ordinal)
+        }),
+        FilteredSearchOrder::ReverseAdded => {
+            indices.sort_by_key(|index| {
+                (
+                    std::cmp::Reverse(cards[*index].note_id),
+                    cards[*index].ordinal,
+                )
+            })
+        }
+        FilteredSearchOrder::Due => indices.sort_by_key(|index| {
+            (cards[*index].card_type.clone() as u8, cards[*index].due)
+        }),
+        // Most overdue relative to the interval first, then the cards
+        // that are not due reviews
+        FilteredSearchOrder::DuePriority => {
+            let overdue = |card: &Card| {
+                (card.queue == CardQueue::Review && card.due <= today)
+                    .then(|| {
+                        (today - card.due) as f64
+                            / card.interval.max(1) as f64
+                    })
+            };
+            indices.sort_by(|a, b| {
+                match (overdue(&cards[*a]), overdue(&cards[*b])) {
+                    (Some(a), Some(b)) => b.total_cmp(&a),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => std::cmp::Ordering::Equal,
+                }
+            })
+        }
+    }
+}
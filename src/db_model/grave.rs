@@ -0,0 +1,84 @@
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::db_model::database::sql_error;
+use crate::db_model::table::Table;
+
+#[derive(
+    Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq,
+)]
+#[repr(u8)]
+pub enum GraveType {
+    Card = 0,
+    Note = 1,
+    Deck = 2,
+}
+
+/// Record of a deleted card, note or deck, a row of the graves table.
+/// Syncing clients delete their copy of the object when they see it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Grave {
+    #[serde(rename = "usn")]
+    pub update_seq_number: i64,
+    #[serde(rename = "oid")]
+    pub object_id: i64, // id of the deleted object
+    #[serde(rename = "type")]
+    pub grave_type: GraveType,
+}
+
+impl Grave {
+    pub fn new(
+        object_id: i64,
+        grave_type: GraveType,
+        update_seq_number: i64,
+    ) -> Self {
+        Self {
+            update_seq_number,
+            object_id,
+            grave_type,
+        }
+    }
+}
+
+// The object id is only unique together with the type, so graves are
+// updated by both and can't be selected or deleted by the id alone
+impl Table for Grave {
+    const TABLE_NAME: &'static str = "graves";
+    const COLUMNS: &'static [&'static str] = &["oid", "type", "usn"];
+
+    fn id(&self) -> i64 {
+        self.object_id
+    }
+
+    fn update(&self, connection: &Connection) -> Result<(), String> {
+        let changed = connection
+            .execute(
+                "UPDATE graves SET usn = ?3 WHERE oid = ?1 AND type = ?2",
+                params_from_iter(self.to_row()?),
+            )
+            .map_err(sql_error)?;
+        if changed == 0 {
+            return Err(format!(
+                "No grave {} of type {:?}",
+                self.object_id, self.grave_type
+            ));
+        }
+        Ok(())
+    }
+
+    fn select_by_id(
+        _connection: &Connection,
+        id: i64,
+    ) -> Result<Option<Self>, String> {
+        Err(format!("Grave {} needs its type to be selected", id))
+    }
+
+    fn delete_by_id(
+        _connection: &Connection,
+        id: i64,
+        _update_seq_number: i64,
+    ) -> Result<(), String> {
+        Err(format!("Grave {} needs its type to be deleted", id))
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::db_model::card::Card;
+use crate::db_model::collection::Collection;
+use crate::db_model::note::Note;
+
+/// Milliseconds since the epoch, the unit of Anki IDs.
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Hands out IDs for notes, cards, decks and models. Anki IDs are
+/// creation times in milliseconds, unique per table; the allocator
+/// starts at the clock and counts up from there, so generating
+/// thousands of notes within the same millisecond never collides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdAllocator {
+    next: i64,
+}
+
+impl IdAllocator {
+    /// Allocator starting at the current time.
+    pub fn new() -> Self {
+        Self::starting_at(now_millis())
+    }
+
+    pub fn starting_at(id: i64) -> Self {
+        Self { next: id }
+    }
+
+    /// Allocator starting after every ID in the collection, its
+    /// models, decks, deck configs, notes and cards, or at the current
+    /// time if that is later.
+    pub fn for_collection(
+        collection: &Collection,
+        notes: &[Note],
+        cards: &[Card],
+    ) -> Self {
+        let max_id = collection
+            .models
+            .iter()
+            .map(|(id, model)| {
+                (*id as i64).max(model.model_id as i64)
+            })
+            .chain(
+                collection
+                    .decks
+                    .iter()
+                    .map(|(id, deck)| (*id as i64).max(deck.id)),
+            )
+            .chain(
+                collection
+                    .deck_configs
+                    .iter()
+                    .map(|(id, _)| *id as i64),
+            )
+            .chain(notes.iter().map(|note| note.id))
+            .chain(cards.iter().map(|card| card.id))
+            .max()
+            .unwrap_or_default();
+        Self::starting_at(now_millis().max(max_id + 1))
+    }
+
+    /// The next ID, greater than every ID handed out before.
+    pub fn next_id(&mut self) -> i64 {
+        let id = self.next;
+        self.next += 1;
+        id
+    }
+}
+
+impl Default for IdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for IdAllocator {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        Some(self.next_id())
+    }
+}
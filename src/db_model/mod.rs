@@ -1,6 +1,17 @@
 pub mod card;
+pub mod check;
 pub mod collection;
+pub mod database;
 pub mod deck;
+pub mod deck_config;
+pub mod deck_tree;
+pub mod filtered;
+pub mod grave;
+pub mod id;
 pub mod model;
+pub mod model_change;
 pub mod note;
+pub mod revlog;
+pub mod scheduler;
+pub mod schema18;
 pub mod table;
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
+use crate::template::{card_requirements, FRONT_SIDE};
+
 #[derive(Debug, Clone, Deserialize_repr, Serialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum ModelType {
@@ -151,6 +153,8 @@ impl Model {
         vec![]
     }
 
+    /// Model with Anki's defaults, `req` is left empty until the
+    /// model is written, see `try_new`.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         css: Option<String>,
@@ -181,4 +185,192 @@ impl Model {
             version: Self::default_version(),
         }
     }
+
+    /// Same as `new`, with `req` computed from the templates, which
+    /// fails when a template doesn't parse.
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_new(
+        css: Option<String>,
+        fields: Vec<ModelField>,
+        model_id: u64,
+        latex_post: Option<String>,
+        latex_pre: Option<String>,
+        name: String,
+        sort_field_index: usize,
+        templates: Vec<ModelTemplate>,
+        model_type: ModelType,
+    ) -> Result<Self, String> {
+        let mut model = Self::new(
+            css,
+            fields,
+            model_id,
+            latex_post,
+            latex_pre,
+            name,
+            sort_field_index,
+            templates,
+            model_type,
+        );
+        model.update_requirements()?;
+        Ok(model)
+    }
+
+    /// Recomputes `req` from the templates, old clients rely on it to
+    /// pick the cards of a note.
+    pub fn update_requirements(&mut self) -> Result<(), String> {
+        self.req = card_requirements(self)?;
+        Ok(())
+    }
+}
+
+const STOCK_ANSWER_SEPARATOR: &str = "\n\n<hr id=answer>\n\n";
+
+const CLOZE_CSS: &str = r#"
+.cloze {
+    font-weight: bold;
+    color: blue;
+}
+.nightMode .cloze {
+    color: lightblue;
+}"#;
+
+/// Anki's stock notetypes, with the fields, templates and CSS Anki
+/// ships.
+impl Model {
+    fn stock(
+        model_id: u64,
+        name: &str,
+        fields: &[&str],
+        templates: &[(&str, String, String)],
+        model_type: ModelType,
+    ) -> Self {
+        let mut model = Self::new(
+            None,
+            fields
+                .iter()
+                .enumerate()
+                .map(|(ordinal, field)| {
+                    ModelField::new(field.to_string(), ordinal)
+                })
+                .collect(),
+            model_id,
+            None,
+            None,
+            name.to_string(),
+            0,
+            templates
+                .iter()
+                .map(|(name, question, answer)| {
+                    ModelTemplate::new(
+                        name.to_string(),
+                        question.clone(),
+                        answer.clone(),
+                    )
+                })
+                .collect(),
+            model_type,
+        );
+        // The stock templates always parse
+        model.req = card_requirements(&model).unwrap_or_default();
+        model
+    }
+
+    fn front_to_back(front: &str, back: &str) -> (String, String) {
+        (
+            format!("{{{{{}}}}}", front),
+            format!(
+                "{{{{{}}}}}{}{{{{{}}}}}",
+                FRONT_SIDE, STOCK_ANSWER_SEPARATOR, back
+            ),
+        )
+    }
+
+    /// "Basic": a Front and Back card.
+    pub fn basic(model_id: u64) -> Self {
+        let (question, answer) = Self::front_to_back("Front", "Back");
+        Self::stock(
+            model_id,
+            "Basic",
+            &["Front", "Back"],
+            &[("Card 1", question, answer)],
+            ModelType::FrontBack,
+        )
+    }
+
+    /// "Basic (and reversed card)": Front to Back and Back to Front.
+    pub fn basic_and_reversed(model_id: u64) -> Self {
+        let (question, answer) = Self::front_to_back("Front", "Back");
+        let (reverse_question, reverse_answer) =
+            Self::front_to_back("Back", "Front");
+        Self::stock(
+            model_id,
+            "Basic (and reversed card)",
+            &["Front", "Back"],
+            &[
+                ("Card 1", question, answer),
+                ("Card 2", reverse_question, reverse_answer),
+            ],
+            ModelType::FrontBack,
+        )
+    }
+
+    /// "Basic (optional reversed card)": the reversed card is only
+    /// made when the "Add Reverse" field is filled.
+    pub fn basic_optional_reversed(model_id: u64) -> Self {
+        let (question, answer) = Self::front_to_back("Front", "Back");
+        let (_, reverse_answer) =
+            Self::front_to_back("Back", "Front");
+        Self::stock(
+            model_id,
+            "Basic (optional reversed card)",
+            &["Front", "Back", "Add Reverse"],
+            &[
+                ("Card 1", question, answer),
+                (
+                    "Card 2",
+                    "{{#Add Reverse}}{{Back}}{{/Add Reverse}}"
+                        .to_string(),
+                    reverse_answer,
+                ),
+            ],
+            ModelType::FrontBack,
+        )
+    }
+
+    /// "Basic (type in the answer)": the Back is typed in and
+    /// compared.
+    pub fn basic_type_answer(model_id: u64) -> Self {
+        Self::stock(
+            model_id,
+            "Basic (type in the answer)",
+            &["Front", "Back"],
+            &[(
+                "Card 1",
+                "{{Front}}\n\n{{type:Back}}".to_string(),
+                format!(
+                    "{{{{Front}}}}{}{{{{type:Back}}}}",
+                    STOCK_ANSWER_SEPARATOR
+                ),
+            )],
+            ModelType::FrontBack,
+        )
+    }
+
+    /// "Cloze": a card per cloze deletion of the Text, with the Back
+    /// Extra shown on the answer.
+    pub fn cloze(model_id: u64) -> Self {
+        let mut model = Self::stock(
+            model_id,
+            "Cloze",
+            &["Text", "Back Extra"],
+            &[(
+                "Cloze",
+                "{{cloze:Text}}".to_string(),
+                "{{cloze:Text}}<br>\n{{Back Extra}}".to_string(),
+            )],
+            ModelType::Cloze,
+        );
+        model.css.push_str(CLOZE_CSS);
+        model
+    }
 }
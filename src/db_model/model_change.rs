@@ -0,0 +1,422 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::db_model::card::{Card, CardFlag, CardQueue, CardType};
+use crate::db_model::collection::Collection;
+use crate::db_model::grave::{Grave, GraveType};
+use crate::db_model::id::{now_millis, IdAllocator};
+use crate::db_model::model::{
+    Model, ModelField, ModelTemplate, ModelType,
+};
+use crate::db_model::note::Note;
+use crate::template::{card_ordinals, rename_and_remove_fields};
+
+/// A change to the fields or templates of a model, fields and
+/// templates are named by their current name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelChange {
+    /// Adds an empty field after the others.
+    AddField(String),
+    RemoveField(String),
+    RenameField {
+        from: String,
+        to: String,
+    },
+    /// Moves the field to the position, counted from 0.
+    MoveField {
+        name: String,
+        position: usize,
+    },
+    /// Adds a template after the others.
+    AddTemplate(ModelTemplate),
+    RemoveTemplate(String),
+    RenameTemplate {
+        from: String,
+        to: String,
+    },
+    MoveTemplate {
+        name: String,
+        position: usize,
+    },
+}
+
+// Fields or templates being changed, with their ordinal before the
+// changes, None for the added ones
+type Changing<T> = Vec<(Option<usize>, T)>;
+
+trait Named {
+    const KIND: &'static str;
+
+    fn name(&self) -> &str;
+}
+
+impl Named for ModelField {
+    const KIND: &'static str = "field";
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Named for ModelTemplate {
+    const KIND: &'static str = "template";
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn position_of<T: Named>(
+    items: &Changing<T>,
+    name: &str,
+) -> Result<usize, String> {
+    items
+        .iter()
+        .position(|(_, item)| item.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("No {} '{}'", T::KIND, name))
+}
+
+fn check_unused<T: Named>(
+    items: &Changing<T>,
+    name: &str,
+) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err(format!("The {} name cannot be empty", T::KIND));
+    }
+    if items
+        .iter()
+        .any(|(_, item)| item.name().eq_ignore_ascii_case(name))
+    {
+        return Err(format!(
+            "There is already a {} '{}'",
+            T::KIND,
+            name
+        ));
+    }
+    Ok(())
+}
+
+// Templates refer to fields by name, so Anki keeps the characters of
+// the template syntax out of field names
+fn check_field_name(name: &str) -> Result<(), String> {
+    if name.contains([':', '{', '}', '"'])
+        || name.trim_start().starts_with(['#', '^', '/'])
+    {
+        return Err(format!("Invalid field name '{}'", name));
+    }
+    Ok(())
+}
+
+fn move_item<T>(
+    items: &mut Changing<T>,
+    from: usize,
+    position: usize,
+) -> Result<(), String> {
+    if position >= items.len() {
+        return Err(format!("No position {}", position));
+    }
+    let item = items.remove(from);
+    items.insert(position, item);
+    Ok(())
+}
+
+// New ordinal of each old ordinal, None for the removed ones
+fn ordinal_map<T>(
+    items: &Changing<T>,
+    old_count: usize,
+) -> Vec<Option<usize>> {
+    let mut map = vec![None; old_count];
+    for (new, (old, _)) in items.iter().enumerate() {
+        if let Some(slot) = old.and_then(|old| map.get_mut(old)) {
+            *slot = Some(new);
+        }
+    }
+    map
+}
+
+fn apply_changes(
+    model: &Model,
+    changes: &[ModelChange],
+) -> Result<(Changing<ModelField>, Changing<ModelTemplate>), String> {
+    let mut fields = model.fields.clone();
+    fields.sort_by_key(|field| field.ordinal);
+    let mut fields = fields
+        .into_iter()
+        .map(|field| (Some(field.ordinal), field))
+        .collect::<Changing<_>>();
+    let mut templates = model
+        .templates
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(ordinal, template)| (Some(ordinal), template))
+        .collect::<Changing<_>>();
+
+    for change in changes {
+        let is_template_change = matches!(
+            change,
+            ModelChange::AddTemplate(_)
+                | ModelChange::RemoveTemplate(_)
+                | ModelChange::RenameTemplate { .. }
+                | ModelChange::MoveTemplate { .. }
+        );
+        // Their cards are numbered by cloze, not by template
+        if is_template_change && model.model_type == ModelType::Cloze
+        {
+            return Err(format!(
+                "The templates of cloze model {} cannot change",
+                model.name
+            ));
+        }
+
+        match change {
+            ModelChange::AddField(name) => {
+                check_field_name(name)?;
+                check_unused(&fields, name)?;
+                fields.push((None, ModelField::new(name.clone(), 0)));
+            }
+            ModelChange::RemoveField(name) => {
+                let index = position_of(&fields, name)?;
+                if fields.len() == 1 {
+                    return Err(format!(
+                        "Model {} needs at least one field",
+                        model.name
+                    ));
+                }
+                fields.remove(index);
+            }
+            ModelChange::RenameField { from, to } => {
+                let index = position_of(&fields, from)?;
+                check_field_name(to)?;
+                if !from.eq_ignore_ascii_case(to) {
+                    check_unused(&fields, to)?;
+                }
+                fields[index].1.name = to.clone();
+            }
+            ModelChange::MoveField { name, position } => {
+                let index = position_of(&fields, name)?;
+                move_item(&mut fields, index, *position)?;
+            }
+            ModelChange::AddTemplate(template) => {
+                check_unused(&templates, &template.name)?;
+                templates.push((None, template.clone()));
+            }
+            ModelChange::RemoveTemplate(name) => {
+                let index = position_of(&templates, name)?;
+                if templates.len() == 1 {
+                    return Err(format!(
+                        "Model {} needs at least one template",
+                        model.name
+                    ));
+                }
+                templates.remove(index);
+            }
+            ModelChange::RenameTemplate { from, to } => {
+                let index = position_of(&templates, from)?;
+                if !from.eq_ignore_ascii_case(to) {
+                    check_unused(&templates, to)?;
+                }
+                templates[index].1.name = to.clone();
+            }
+            ModelChange::MoveTemplate { name, position } => {
+                let index = position_of(&templates, name)?;
+                move_item(&mut templates, index, *position)?;
+            }
+        }
+    }
+    Ok((fields, templates))
+}
+
+impl Collection {
+    /// Changes the fields and templates of the model and migrates its
+    /// notes and cards: note fields follow their field, cards follow
+    /// their template and keep their review history, the cards of
+    /// removed templates are deleted and added templates get cards for
+    /// the notes they render for. Templates are updated for renamed and
+    /// removed fields. Changing the schema requires a full sync, so the
+    /// collection's schema modification time is bumped.
+    ///
+    /// Returns the graves of the deleted cards.
+    pub fn change_model(
+        &mut self,
+        model_id: usize,
+        changes: &[ModelChange],
+        notes: &mut [Note],
+        cards: &mut Vec<Card>,
+        ids: &mut IdAllocator,
+        update_seq_number: i64,
+    ) -> Result<Vec<Grave>, String> {
+        let model = self
+            .models
+            .iter()
+            .find(|(id, _)| *id == model_id)
+            .map(|(_, model)| model)
+            .ok_or_else(|| format!("No model {}", model_id))?;
+        if changes.is_empty() {
+            return Ok(vec![]);
+        }
+        let (fields, templates) = apply_changes(model, changes)?;
+
+        let field_map = ordinal_map(&fields, model.fields.len());
+        let template_map =
+            ordinal_map(&templates, model.templates.len());
+        let mut renames = HashMap::new();
+        for field in &model.fields {
+            match field_map.get(field.ordinal).copied().flatten() {
+                Some(new) if fields[new].1.name != field.name => {
+                    renames.insert(
+                        field.name.clone(),
+                        Some(fields[new].1.name.clone()),
+                    );
+                }
+                Some(_) => {}
+                None => {
+                    renames.insert(field.name.clone(), None);
+                }
+            }
+        }
+
+        let mut changed = model.clone();
+        changed.fields = fields
+            .iter()
+            .enumerate()
+            .map(|(ordinal, (_, field))| ModelField {
+                ordinal,
+                ..field.clone()
+            })
+            .collect();
+        changed.templates = templates
+            .iter()
+            .map(|(_, template)| {
+                // Writing a template back normalizes its tags, so
+                // templates are only rewritten when a field changes
+                let rewrite = |format: &str| {
+                    if renames.is_empty() {
+                        Ok(format.to_string())
+                    } else {
+                        rename_and_remove_fields(format, &renames)
+                    }
+                };
+                Ok(ModelTemplate {
+                    question_format: rewrite(
+                        &template.question_format,
+                    )?,
+                    answer_template: rewrite(
+                        &template.answer_template,
+                    )?,
+                    browser_question_format: rewrite(
+                        &template.browser_question_format,
+                    )?,
+                    browser_answer_template: rewrite(
+                        &template.browser_answer_template,
+                    )?,
+                    ..template.clone()
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        changed.sort_field_index = field_map
+            .get(model.sort_field_index)
+            .copied()
+            .flatten()
+            .unwrap_or_default();
+        changed.update_requirements()?;
+        let now = now_millis();
+        changed.modification = now / 1000;
+        changed.update_seq_number = -1;
+
+        let mut changed_notes = HashSet::new();
+        for note in
+            notes.iter_mut().filter(|note| note.model_id == model_id)
+        {
+            note.fields = fields
+                .iter()
+                .map(|(old, _)| {
+                    old.and_then(|old| note.fields.get(old).cloned())
+                        .unwrap_or_default()
+                })
+                .collect();
+            note.update_sort_field(&changed)?;
+            note.modified = now / 1000;
+            note.update_seq_number = -1;
+            changed_notes.insert(note.id);
+        }
+
+        let mut graves = vec![];
+        if changed.model_type == ModelType::FrontBack {
+            cards.retain_mut(|card| {
+                if !changed_notes.contains(&(card.note_id as i64)) {
+                    return true;
+                }
+                match template_map.get(card.ordinal as usize) {
+                    Some(Some(new)) => {
+                        if card.ordinal != *new as u64 {
+                            card.ordinal = *new as u64;
+                            card.modified = now / 1000;
+                            card.update_seq_number = -1;
+                        }
+                        true
+                    }
+                    Some(None) => {
+                        graves.push(Grave::new(
+                            card.id,
+                            GraveType::Card,
+                            update_seq_number,
+                        ));
+                        false
+                    }
+                    // Already without a template, left to the check
+                    None => true,
+                }
+            });
+
+            let added = templates
+                .iter()
+                .enumerate()
+                .filter(|(_, (old, _))| old.is_none())
+                .map(|(ordinal, _)| ordinal as u64)
+                .collect::<Vec<_>>();
+            for note in
+                notes.iter().filter(|note| note.model_id == model_id)
+            {
+                let siblings = cards
+                    .iter()
+                    .find(|card| card.note_id as i64 == note.id);
+                let deck_id = siblings
+                    .map(|card| card.deck_id)
+                    .unwrap_or(changed.default_deck_id as usize);
+                for ordinal in card_ordinals(&changed, note)? {
+                    if !added.contains(&ordinal) {
+                        continue;
+                    }
+                    cards.push(Card::new(
+                        ids.next_id(),
+                        note.id as usize,
+                        deck_id,
+                        ordinal,
+                        now / 1000,
+                        -1,
+                        CardType::New,
+                        CardQueue::New,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        CardFlag::Null,
+                    ));
+                }
+            }
+        }
+
+        if let Some((_, model)) =
+            self.models.iter_mut().find(|(id, _)| *id == model_id)
+        {
+            *model = changed;
+        }
+        self.scheme_mod_time = now;
+        self.modified = now;
+        Ok(graves)
+    }
+}
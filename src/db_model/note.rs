@@ -1,6 +1,18 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Row;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use crate::db_model::database::{
+    join_fields, join_tags, json_error, row_to_json, split_fields,
+    split_tags,
+};
+use crate::db_model::grave::GraveType;
+use crate::db_model::model::Model;
+use crate::db_model::table::{columns_to_row, Table};
+use crate::util::{field_checksum, strip_html};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct NoteTag {
     pub name: String,
 }
@@ -14,13 +26,27 @@ impl NoteTag {
         } else if name.contains(' ') {
             return Err("Tag name cannot contain spaces".to_string());
         } else if name.len() > 255 {
-            return Err("Tag name cannot be longer than 255 characters".to_string());
+            return Err(
+                "Tag name cannot be longer than 255 characters"
+                    .to_string(),
+            );
         }
 
         Ok(NoteTag {
             name: name.to_string(),
         })
     }
+
+    // Like new, but replaces spaces and repeated underscores with a
+    // single underscore instead of rejecting the name
+    pub fn repair(name: &str) -> Result<Self, String> {
+        let mut repaired =
+            name.split_whitespace().collect::<Vec<_>>().join("_");
+        while repaired.contains("__") {
+            repaired = repaired.replace("__", "_");
+        }
+        Self::new(&repaired)
+    }
 }
 
 impl Default for NoteTag {
@@ -31,7 +57,7 @@ impl Default for NoteTag {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Note {
     pub id: i64, // time in milliseconds when the note was created
     #[serde(rename = "guid")]
@@ -47,10 +73,10 @@ pub struct Note {
     #[serde(rename = "flds")]
     pub fields: Vec<String>,
     #[serde(rename = "sfld")]
-    pub sort_filed: usize, // index of the field used for sorting
+    pub sort_field: String, // text of the sort field without HTML
     #[serde(rename = "csum")]
-    pub checksum: i64, // checksum of the note, the first 8 digits of sha1 hash of the first field
-    pub flags: i64,   // flags associated with the note, unused
+    pub checksum: i64, // checksum of the note, the first 8 digits of sha1 hash of the first field without HTML
+    pub flags: i64, // flags associated with the note, unused
     pub data: String, // additional data associated with the note, unused
 }
 
@@ -64,7 +90,7 @@ impl Note {
         update_seq_number: i64,
         tags: Vec<NoteTag>,
         fields: Vec<String>,
-        sort_filed: usize,
+        sort_field: String,
         checksum: i64,
     ) -> Self {
         Note {
@@ -75,10 +101,141 @@ impl Note {
             update_seq_number,
             tags,
             fields,
-            sort_filed,
+            sort_field,
             checksum,
             flags: 0,
             data: "".to_string(),
         }
     }
+
+    /// New note of the model, with the sort field and the checksum
+    /// computed from the fields as Anki does. The fields are in the
+    /// order of the model's field ordinals.
+    pub fn from_model(
+        model: &Model,
+        global_id: String,
+        fields: Vec<String>,
+    ) -> Result<Self, String> {
+        if fields.len() != model.fields.len() {
+            return Err(format!(
+                "Model {} has {} fields, got {}",
+                model.name,
+                model.fields.len(),
+                fields.len()
+            ));
+        }
+        let (sort_field, checksum) =
+            sort_field_and_checksum(model, &fields)?;
+
+        Ok(Note::new(
+            0,
+            global_id,
+            model.model_id as usize,
+            0,
+            -1,
+            vec![],
+            fields,
+            sort_field,
+            checksum,
+        ))
+    }
+
+    /// Recomputes the sort field and the checksum after the fields
+    /// changed.
+    pub fn update_sort_field(
+        &mut self,
+        model: &Model,
+    ) -> Result<(), String> {
+        (self.sort_field, self.checksum) =
+            sort_field_and_checksum(model, &self.fields)?;
+        Ok(())
+    }
+}
+
+fn sort_field_and_checksum(
+    model: &Model,
+    fields: &[String],
+) -> Result<(String, i64), String> {
+    let sort_field = fields
+        .get(model.sort_field_index)
+        .map(|field| strip_html(field))
+        .ok_or_else(|| {
+            format!(
+                "Model {} has no sort field {}",
+                model.name, model.sort_field_index
+            )
+        })?;
+    let checksum = field_checksum(&strip_html(
+        fields.first().map(String::as_str).unwrap_or_default(),
+    ));
+    Ok((sort_field, checksum))
+}
+
+fn text_column<'a>(
+    map: &'a Map<String, Value>,
+    name: &str,
+) -> Result<&'a str, String> {
+    map.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Note row has no text {}", name))
+}
+
+// sfld has integer affinity, so "007" or "1.50" come back as numbers;
+// the original text is the field that holds the same number
+fn numeric_sort_field(value: &Value, fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| strip_html(field))
+        .find(|text| {
+            text.trim().parse::<f64>().ok() == value.as_f64()
+        })
+        .unwrap_or_else(|| value.to_string())
+}
+
+// notes.tags and notes.flds are joined strings
+impl Table for Note {
+    const TABLE_NAME: &'static str = "notes";
+    const COLUMNS: &'static [&'static str] = &[
+        "id", "guid", "mid", "mod", "usn", "tags", "flds", "sfld",
+        "csum", "flags", "data",
+    ];
+    const GRAVE_TYPE: Option<GraveType> = Some(GraveType::Note);
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn to_row(&self) -> Result<Vec<SqlValue>, String> {
+        let mut map = match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map,
+            Ok(_) => return Err("Note is not an object".to_string()),
+            Err(err) => return Err(json_error(err)),
+        };
+        map.insert("tags".to_string(), json!(join_tags(self)));
+        map.insert("flds".to_string(), json!(join_fields(self)));
+        columns_to_row::<Self>(&map)
+    }
+
+    fn from_row(row: &Row) -> Result<Self, String> {
+        let mut map = row_to_json(row)?;
+        let tags = split_tags(text_column(&map, "tags")?);
+        let fields = split_fields(text_column(&map, "flds")?);
+        let sort_field = match map.get("sfld") {
+            Some(Value::String(text)) => text.clone(),
+            Some(Value::Null) | None => {
+                return Err("Note row has no sfld".to_string())
+            }
+            Some(value) => numeric_sort_field(value, &fields),
+        };
+
+        map.insert(
+            "tags".to_string(),
+            tags.into_iter()
+                .map(|name| json!({ "name": name }))
+                .collect(),
+        );
+        map.insert("flds".to_string(), json!(fields));
+        map.insert("sfld".to_string(), json!(sort_field));
+        serde_json::from_value(Value::Object(map)).map_err(json_error)
+    }
 }
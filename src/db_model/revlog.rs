@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::db_model::table::Table;
+
+/// Answer button pressed for a review.
+#[derive(
+    Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq,
+)]
+#[repr(u8)]
+pub enum ReviewEase {
+    // logged when a card is rescheduled by hand
+    Manual = 0,
+    Again = 1,
+    Hard = 2,
+    Good = 3,
+    Easy = 4,
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq,
+)]
+#[repr(u8)]
+pub enum ReviewType {
+    Learning = 0,
+    Review = 1,
+    Relearning = 2,
+    Filtered = 3, // review in a filtered deck without rescheduling
+    Manual = 4,
+    Rescheduled = 5,
+}
+
+/// One review of a card, a row of the revlog table.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReviewLog {
+    pub id: i64, // time in milliseconds of the review
+    #[serde(rename = "cid")]
+    pub card_id: i64,
+    #[serde(rename = "usn")]
+    pub update_seq_number: i64,
+    pub ease: ReviewEase,
+    #[serde(rename = "ivl")]
+    pub interval: i64, // negative in seconds, positive in days
+    #[serde(rename = "lastIvl")]
+    pub last_interval: i64, // same unit as interval
+    pub factor: i64, // ease factor in permille, 2500 is 250%
+    pub time: i64,   // milliseconds spent on the review
+    #[serde(rename = "type")]
+    pub review_type: ReviewType,
+}
+
+impl ReviewLog {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: i64,
+        card_id: i64,
+        update_seq_number: i64,
+        ease: ReviewEase,
+        interval: i64,
+        last_interval: i64,
+        factor: i64,
+        time: i64,
+        review_type: ReviewType,
+    ) -> Self {
+        Self {
+            id,
+            card_id,
+            update_seq_number,
+            ease,
+            interval,
+            last_interval,
+            factor,
+            time,
+            review_type,
+        }
+    }
+}
+
+impl Table for ReviewLog {
+    const TABLE_NAME: &'static str = "revlog";
+    const COLUMNS: &'static [&'static str] = &[
+        "id", "cid", "usn", "ease", "ivl", "lastIvl", "factor",
+        "time", "type",
+    ];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+}
@@ -0,0 +1,446 @@
+use crate::db_model::card::{Card, CardQueue, CardType};
+use crate::db_model::deck::{DeckConfig, LeechAction};
+use crate::db_model::deck_config::DeckConfigError;
+use crate::db_model::revlog::{ReviewEase, ReviewLog, ReviewType};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+// Ease factors are in permille
+const MINIMUM_FACTOR: i64 = 1300;
+const AGAIN_FACTOR_CHANGE: i64 = -200;
+const HARD_FACTOR_CHANGE: i64 = -150;
+const EASY_FACTOR_CHANGE: i64 = 150;
+
+/// Outcome of answering a card.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answer {
+    pub review_log: ReviewLog,
+    /// The card lapsed often enough to be a leech. Anki tags its note
+    /// `leech`, and the card is suspended if the config says so.
+    pub leech: bool,
+}
+
+/// Anki's SM-2 answer logic, as in its v3 scheduler, for the cards
+/// of decks using a config.
+///
+/// Intervals are fuzzed like Anki does, with a random number seeded
+/// from the card id and its number of reviews, so answering the same
+/// card in the same state always gives the same interval.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scheduler<'a> {
+    config: &'a DeckConfig,
+    today: i64,
+    now: i64,
+    fuzz_seed: Option<u64>,
+}
+
+fn step_secs(minutes: f32) -> i64 {
+    ((minutes * 60.0).round() as i64).max(1)
+}
+
+fn step_delay(steps: &[f32], index: usize) -> Option<i64> {
+    steps.get(index).map(|step| step_secs(*step))
+}
+
+// Hard repeats the step, on the first step it waits between the first
+// and second steps
+fn hard_delay(steps: &[f32], index: usize) -> Option<i64> {
+    match (index, steps) {
+        (0, [only]) => {
+            let secs = step_secs(*only);
+            Some((secs * 3 / 2).min(secs + SECONDS_PER_DAY))
+        }
+        (0, [first, second, ..]) => {
+            Some((step_secs(*first) + step_secs(*second)) / 2)
+        }
+        _ => step_delay(steps, index),
+    }
+}
+
+// Anki keeps the number of steps left below 1000, older versions put
+// the steps that can be done today above it
+fn step_index(card: &Card, steps: &[f32]) -> usize {
+    if card.card_type == CardType::New {
+        return 0;
+    }
+    let remaining = (card.left % 1000).max(0) as usize;
+    steps.len().saturating_sub(remaining)
+}
+
+// Range of days an interval is fuzzed within, wider for longer
+// intervals
+fn fuzz_bounds(
+    interval: f64,
+    minimum: i64,
+    maximum: i64,
+) -> (i64, i64) {
+    if interval < 2.5 {
+        let interval =
+            (interval.round() as i64).clamp(minimum, maximum);
+        return (interval, interval);
+    }
+    let delta = 1.0
+        + 0.15 * (interval.min(7.0) - 2.5).max(0.0)
+        + 0.1 * (interval.min(20.0) - 7.0).max(0.0)
+        + 0.05 * (interval - 20.0).max(0.0);
+    let lower =
+        ((interval - delta).round() as i64).clamp(minimum, maximum);
+    let mut upper =
+        ((interval + delta).round() as i64).clamp(minimum, maximum);
+    if upper == lower && upper > 2 && upper < maximum {
+        upper = lower + 1;
+    }
+    (lower, upper)
+}
+
+impl<'a> Scheduler<'a> {
+    /// Scheduler answering at `now`, in milliseconds since the epoch,
+    /// on day `today`, in days since the collection was created. The
+    /// config is checked first, Anki's zero filled configs would
+    /// schedule nonsense.
+    pub fn new(
+        config: &'a DeckConfig,
+        today: i64,
+        now: i64,
+    ) -> Result<Self, DeckConfigError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            today,
+            now,
+            fuzz_seed: Some(0),
+        })
+    }
+
+    /// Changes the seed mixed with the card id and reviews.
+    pub fn with_fuzz_seed(mut self, seed: u64) -> Self {
+        self.fuzz_seed = Some(seed);
+        self
+    }
+
+    /// Schedules with the exact intervals.
+    pub fn without_fuzz(mut self) -> Self {
+        self.fuzz_seed = None;
+        self
+    }
+
+    /// Answers the card, updating its type, queue, due, interval,
+    /// factor, remaining steps and lapses, and returns the review log
+    /// entry. `time_taken` is in milliseconds, capped by the config.
+    ///
+    /// Cards in filtered decks are not supported, the deck should be
+    /// emptied first.
+    pub fn answer(
+        &self,
+        card: &mut Card,
+        ease: ReviewEase,
+        time_taken: i64,
+    ) -> Result<Answer, String> {
+        if ease == ReviewEase::Manual {
+            return Err(
+                "A card cannot be answered manually".to_string()
+            );
+        }
+        if card.is_suspended_or_buried() {
+            return Err(format!(
+                "Card {} is suspended or buried",
+                card.id
+            ));
+        }
+        if card.original_deck_id != 0 {
+            return Err(format!(
+                "Card {} is in a filtered deck",
+                card.id
+            ));
+        }
+
+        let review_type = match card.card_type {
+            CardType::New | CardType::Learning => {
+                ReviewType::Learning
+            }
+            CardType::Review => ReviewType::Review,
+            CardType::Relearning => ReviewType::Relearning,
+        };
+        let last_interval = self.interval_before(card);
+        let mut leech = false;
+        match card.card_type {
+            CardType::New | CardType::Learning => {
+                self.answer_learning(card, ease)
+            }
+            CardType::Relearning => {
+                self.answer_relearning(card, ease)
+            }
+            CardType::Review => {
+                leech = self.answer_review(card, ease)
+            }
+        }
+        card.reviews += 1;
+        card.modified = self.now / 1000;
+        card.update_seq_number = -1;
+
+        let max_taken = self.config.max_taken as i64 * 1000;
+        let time_taken = match max_taken {
+            0 => time_taken.max(0),
+            _ => time_taken.clamp(0, max_taken),
+        };
+        let interval = self.interval_after(card);
+        if leech
+            && self.config.lapse_config.leech_action
+                == LeechAction::Suspend
+        {
+            card.queue = CardQueue::Suspended;
+        }
+        let review_log = ReviewLog::new(
+            self.now,
+            card.id,
+            -1,
+            ease,
+            interval,
+            last_interval,
+            card.factor,
+            time_taken,
+            review_type,
+        );
+        Ok(Answer { review_log, leech })
+    }
+
+    // Logged intervals are negative seconds for learning steps and
+    // days otherwise
+    fn interval_before(&self, card: &Card) -> i64 {
+        let steps = match card.card_type {
+            CardType::New => return 0,
+            CardType::Review => return card.interval,
+            CardType::Learning => &self.config.new_config.delays,
+            CardType::Relearning => &self.config.lapse_config.delays,
+        };
+        match step_delay(steps, step_index(card, steps)) {
+            Some(secs) if secs < SECONDS_PER_DAY => -secs,
+            Some(secs) => secs / SECONDS_PER_DAY,
+            None => 0,
+        }
+    }
+
+    fn interval_after(&self, card: &Card) -> i64 {
+        match card.queue {
+            CardQueue::Learning => -(card.due - self.now / 1000),
+            CardQueue::InLearning => card.due - self.today,
+            _ => card.interval,
+        }
+    }
+
+    fn answer_learning(&self, card: &mut Card, ease: ReviewEase) {
+        let steps = &self.config.new_config.delays;
+        let index = step_index(card, steps);
+        let remaining = steps.len() - index;
+        let delay = match ease {
+            ReviewEase::Again => {
+                step_delay(steps, 0).map(|delay| (delay, steps.len()))
+            }
+            ReviewEase::Hard => hard_delay(steps, index)
+                .map(|delay| (delay, remaining)),
+            ReviewEase::Good => step_delay(steps, index + 1)
+                .map(|delay| (delay, remaining - 1)),
+            _ => None,
+        };
+        if let Some((delay, remaining)) = delay {
+            self.schedule_step(
+                card,
+                CardType::Learning,
+                delay,
+                remaining,
+            );
+            return;
+        }
+
+        let intervals = &self.config.new_config.intervals;
+        let good = intervals.first().copied().unwrap_or(1) as f64;
+        let interval = match ease {
+            ReviewEase::Easy => {
+                intervals.get(1).copied().unwrap_or(4) as f64
+            }
+            _ => good,
+        };
+        card.factor = self.config.new_config.initial_factor as i64;
+        let interval = self.fuzzed(
+            card,
+            interval,
+            1,
+            self.config.review_config.max_interval as i64,
+        );
+        self.schedule_review(card, interval);
+    }
+
+    fn answer_relearning(&self, card: &mut Card, ease: ReviewEase) {
+        let steps = &self.config.lapse_config.delays;
+        let index = step_index(card, steps);
+        let remaining = steps.len() - index;
+        let delay = match ease {
+            ReviewEase::Again => {
+                step_delay(steps, 0).map(|delay| (delay, steps.len()))
+            }
+            ReviewEase::Hard => hard_delay(steps, index)
+                .map(|delay| (delay, remaining)),
+            ReviewEase::Good => step_delay(steps, index + 1)
+                .map(|delay| (delay, remaining - 1)),
+            _ => None,
+        };
+        match delay {
+            Some((delay, remaining)) => self.schedule_step(
+                card,
+                CardType::Relearning,
+                delay,
+                remaining,
+            ),
+            None => {
+                let interval = match ease {
+                    ReviewEase::Easy => card.interval + 1,
+                    _ => card.interval,
+                };
+                let maximum =
+                    self.config.review_config.max_interval as i64;
+                self.schedule_review(card, interval.clamp(1, maximum))
+            }
+        }
+    }
+
+    // Returns whether the card became a leech
+    fn answer_review(
+        &self,
+        card: &mut Card,
+        ease: ReviewEase,
+    ) -> bool {
+        let review = &self.config.review_config;
+        let lapse = &self.config.lapse_config;
+        let maximum = review.max_interval as i64;
+        let current = card.interval.max(1);
+
+        if ease == ReviewEase::Again {
+            card.lapses += 1;
+            card.factor = (card.factor + AGAIN_FACTOR_CHANGE)
+                .max(MINIMUM_FACTOR);
+            card.interval = ((current as f32
+                * lapse.interval_increase)
+                .round() as i64)
+                .max(lapse.min_interval as i64)
+                .clamp(1, maximum);
+            match step_delay(&lapse.delays, 0) {
+                Some(delay) => self.schedule_step(
+                    card,
+                    CardType::Relearning,
+                    delay,
+                    lapse.delays.len(),
+                ),
+                None => self.schedule_review(card, card.interval),
+            }
+
+            let threshold = lapse.leech_fails as i64;
+            let leech = card.lapses >= threshold
+                && (card.lapses - threshold) % (threshold / 2).max(1)
+                    == 0;
+            return leech;
+        }
+
+        let days_late = (self.today - card.due).max(0) as f64;
+        let factor = card.factor.max(MINIMUM_FACTOR) as f64 / 1000.0;
+        let hard_factor = review.hard_factor as f64;
+        let hard_minimum = match hard_factor <= 1.0 {
+            true => 0,
+            false => current + 1,
+        };
+        let hard = self.passing_interval(
+            card,
+            current as f64 * hard_factor,
+            hard_minimum,
+        );
+        let good_minimum = match hard_factor <= 1.0 {
+            true => current + 1,
+            false => hard + 1,
+        };
+        let good = self.passing_interval(
+            card,
+            (current as f64 + days_late / 2.0) * factor,
+            good_minimum,
+        );
+        let (interval, factor_change) = match ease {
+            ReviewEase::Hard => (hard, HARD_FACTOR_CHANGE),
+            ReviewEase::Good => (good, 0),
+            _ => (
+                self.passing_interval(
+                    card,
+                    (current as f64 + days_late)
+                        * factor
+                        * review.ease_factor as f64,
+                    good + 1,
+                ),
+                EASY_FACTOR_CHANGE,
+            ),
+        };
+        card.factor =
+            (card.factor + factor_change).max(MINIMUM_FACTOR);
+        self.schedule_review(card, interval);
+        false
+    }
+
+    fn passing_interval(
+        &self,
+        card: &Card,
+        interval: f64,
+        minimum: i64,
+    ) -> i64 {
+        let interval = interval
+            * self.config.review_config.interval_factor as f64;
+        self.fuzzed(
+            card,
+            interval,
+            minimum,
+            self.config.review_config.max_interval as i64,
+        )
+    }
+
+    fn fuzzed(
+        &self,
+        card: &Card,
+        interval: f64,
+        minimum: i64,
+        maximum: i64,
+    ) -> i64 {
+        let minimum = minimum.clamp(1, maximum);
+        let Some(seed) = self.fuzz_seed else {
+            return (interval.round() as i64).clamp(minimum, maximum);
+        };
+        let (lower, upper) = fuzz_bounds(interval, minimum, maximum);
+        let mut rng = fastrand::Rng::with_seed(
+            seed.wrapping_add(card.id as u64)
+                .wrapping_add(card.reviews as u64),
+        );
+        lower + (rng.f64() * (upper - lower + 1) as f64) as i64
+    }
+
+    // Steps of a day or more are counted in days, from today
+    fn schedule_step(
+        &self,
+        card: &mut Card,
+        card_type: CardType,
+        delay: i64,
+        remaining: usize,
+    ) {
+        card.card_type = card_type;
+        card.left = remaining as i64;
+        if delay >= SECONDS_PER_DAY {
+            let days =
+                (delay as f64 / SECONDS_PER_DAY as f64).round();
+            card.queue = CardQueue::InLearning;
+            card.due = self.today + (days as i64).max(1);
+        } else {
+            card.queue = CardQueue::Learning;
+            card.due = self.now / 1000 + delay;
+        }
+    }
+
+    fn schedule_review(&self, card: &mut Card, interval: i64) {
+        card.card_type = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = interval;
+        card.due = self.today + interval;
+        card.left = 0;
+    }
+}
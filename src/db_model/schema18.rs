@@ -0,0 +1,1074 @@
+use std::collections::BTreeMap;
+
+use prost::Message;
+use rusqlite::types::FromSql;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection, Params, Row};
+use serde_json::{Map, Value};
+
+use crate::db_model::collection::{Collection, CollectionConfig};
+use crate::db_model::database::{
+    json_error, select_json, sql_error, written_models,
+};
+use crate::db_model::deck::{
+    Deck, DeckConfig, FilteredDeckOptions, FilteredSearchTerm,
+    LeechAction, NewCardOrder,
+};
+use crate::db_model::model::{
+    Model, ModelField, ModelTemplate, ModelType,
+};
+use crate::db_model::table::Table;
+
+// Modern collections keep the notetypes, decks, deck configs and
+// config in their own tables, with protobuf encoded configs, instead
+// of the JSON columns of col.
+pub const SCHEMA: &str = r#"
+CREATE TABLE col (
+    id              integer primary key,
+    crt             integer not null,
+    mod             integer not null,
+    scm             integer not null,
+    ver             integer not null,
+    dty             integer not null,
+    usn             integer not null,
+    ls              integer not null,
+    conf            text not null,
+    models          text not null,
+    decks           text not null,
+    dconf           text not null,
+    tags            text not null
+);
+CREATE TABLE notes (
+    id              integer primary key,
+    guid            text not null,
+    mid             integer not null,
+    mod             integer not null,
+    usn             integer not null,
+    tags            text not null,
+    flds            text not null,
+    sfld            integer not null,
+    csum            integer not null,
+    flags           integer not null,
+    data            text not null
+);
+CREATE TABLE cards (
+    id              integer primary key,
+    nid             integer not null,
+    did             integer not null,
+    ord             integer not null,
+    mod             integer not null,
+    usn             integer not null,
+    type            integer not null,
+    queue           integer not null,
+    due             integer not null,
+    ivl             integer not null,
+    factor          integer not null,
+    reps            integer not null,
+    lapses          integer not null,
+    left            integer not null,
+    odue            integer not null,
+    odid            integer not null,
+    flags           integer not null,
+    data            text not null
+);
+CREATE TABLE revlog (
+    id              integer primary key,
+    cid             integer not null,
+    usn             integer not null,
+    ease            integer not null,
+    ivl             integer not null,
+    lastIvl         integer not null,
+    factor          integer not null,
+    time            integer not null,
+    type            integer not null
+);
+CREATE TABLE graves (
+    oid             integer not null,
+    type            integer not null,
+    usn             integer not null,
+    PRIMARY KEY (oid, type)
+) WITHOUT ROWID;
+CREATE TABLE deck_config (
+    id              integer primary key not null,
+    name            text not null COLLATE unicase,
+    mtime_secs      integer not null,
+    usn             integer not null,
+    config          blob not null
+);
+CREATE TABLE config (
+    KEY             text not null primary key,
+    usn             integer not null,
+    mtime_secs      integer not null,
+    val             blob not null
+) WITHOUT ROWID;
+CREATE TABLE fields (
+    ntid            integer not null,
+    ord             integer not null,
+    name            text not null COLLATE unicase,
+    config          blob not null,
+    PRIMARY KEY (ntid, ord)
+) WITHOUT ROWID;
+CREATE TABLE templates (
+    ntid            integer not null,
+    ord             integer not null,
+    name            text not null COLLATE unicase,
+    mtime_secs      integer not null,
+    usn             integer not null,
+    config          blob not null,
+    PRIMARY KEY (ntid, ord)
+) WITHOUT ROWID;
+CREATE TABLE notetypes (
+    id              integer not null primary key,
+    name            text not null COLLATE unicase,
+    mtime_secs      integer not null,
+    usn             integer not null,
+    config          blob not null
+);
+CREATE TABLE decks (
+    id              integer primary key not null,
+    name            text not null COLLATE unicase,
+    mtime_secs      integer not null,
+    usn             integer not null,
+    common          blob not null,
+    kind            blob not null
+);
+CREATE TABLE tags (
+    tag             text not null primary key COLLATE unicase,
+    usn             integer not null,
+    collapsed       boolean not null,
+    config          blob null
+) WITHOUT ROWID;
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+CREATE UNIQUE INDEX idx_fields_name_ntid ON fields (name, ntid);
+CREATE UNIQUE INDEX idx_templates_name_ntid ON templates (name, ntid);
+CREATE INDEX idx_templates_usn ON templates (usn);
+CREATE UNIQUE INDEX idx_notetypes_name ON notetypes (name);
+CREATE INDEX idx_notetypes_usn ON notetypes (usn);
+CREATE UNIQUE INDEX idx_decks_name ON decks (name);
+CREATE INDEX idx_notes_mid ON notes (mid);
+CREATE INDEX idx_cards_odid ON cards (odid) WHERE odid != 0;
+"#;
+
+// Separator between the components of deck names in the decks table,
+// "::" in the legacy JSON
+pub const DECK_NAME_SEPARATOR: char = '\x1f';
+
+/// Notetype.Config of Anki's notetypes.proto
+#[derive(Clone, PartialEq, Message)]
+pub struct NotetypeConfig {
+    #[prost(int32, tag = "1")]
+    pub kind: i32,
+    #[prost(uint32, tag = "2")]
+    pub sort_field_idx: u32,
+    #[prost(string, tag = "3")]
+    pub css: String,
+    // Model.default_deck_id, kept for legacy clients
+    #[prost(int64, tag = "4")]
+    pub target_deck_id_unused: i64,
+    #[prost(string, tag = "5")]
+    pub latex_pre: String,
+    #[prost(string, tag = "6")]
+    pub latex_post: String,
+    #[prost(message, repeated, tag = "8")]
+    pub reqs: Vec<CardRequirement>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CardRequirement {
+    #[prost(uint32, tag = "1")]
+    pub card_ord: u32,
+    // 0 none, 1 any, 2 all
+    #[prost(int32, tag = "2")]
+    pub kind: i32,
+    #[prost(uint32, repeated, tag = "3")]
+    pub field_ords: Vec<u32>,
+}
+
+/// Notetype.Field.Config
+#[derive(Clone, PartialEq, Message)]
+pub struct FieldConfig {
+    #[prost(bool, tag = "1")]
+    pub sticky: bool,
+    #[prost(bool, tag = "2")]
+    pub rtl: bool,
+    #[prost(string, tag = "3")]
+    pub font_name: String,
+    #[prost(uint32, tag = "4")]
+    pub font_size: u32,
+}
+
+/// Notetype.Template.Config
+#[derive(Clone, PartialEq, Message)]
+pub struct TemplateConfig {
+    #[prost(string, tag = "1")]
+    pub q_format: String,
+    #[prost(string, tag = "2")]
+    pub a_format: String,
+    #[prost(string, tag = "3")]
+    pub q_format_browser: String,
+    #[prost(string, tag = "4")]
+    pub a_format_browser: String,
+    #[prost(int64, tag = "5")]
+    pub target_deck_id: i64,
+}
+
+/// Deck.Common of decks.proto
+#[derive(Clone, PartialEq, Message)]
+pub struct DeckCommon {
+    #[prost(bool, tag = "1")]
+    pub study_collapsed: bool,
+    #[prost(bool, tag = "2")]
+    pub browser_collapsed: bool,
+    #[prost(uint32, tag = "3")]
+    pub last_day_studied: u32,
+    #[prost(int32, tag = "4")]
+    pub new_studied: i32,
+    #[prost(int32, tag = "5")]
+    pub review_studied: i32,
+    #[prost(int32, tag = "6")]
+    pub learning_studied: i32,
+    #[prost(int32, tag = "7")]
+    pub milliseconds_studied: i32,
+}
+
+/// Deck.Normal
+#[derive(Clone, PartialEq, Message)]
+pub struct NormalDeck {
+    #[prost(int64, tag = "1")]
+    pub config_id: i64,
+    #[prost(uint32, tag = "2")]
+    pub extend_new: u32,
+    #[prost(uint32, tag = "3")]
+    pub extend_review: u32,
+    #[prost(string, tag = "4")]
+    pub description: String,
+}
+
+/// Deck.Filtered.SearchTerm
+#[derive(Clone, PartialEq, Message)]
+pub struct FilteredSearch {
+    #[prost(string, tag = "1")]
+    pub search: String,
+    #[prost(uint32, tag = "2")]
+    pub limit: u32,
+    // FilteredSearchOrder
+    #[prost(int32, tag = "3")]
+    pub order: i32,
+}
+
+/// Deck.Filtered
+#[derive(Clone, PartialEq, Message)]
+pub struct FilteredDeck {
+    #[prost(bool, tag = "1")]
+    pub reschedule: bool,
+    #[prost(message, repeated, tag = "2")]
+    pub search_terms: Vec<FilteredSearch>,
+    #[prost(float, repeated, tag = "3")]
+    pub delays: Vec<f32>,
+    #[prost(uint32, tag = "4")]
+    pub preview_delay: u32,
+    #[prost(uint32, tag = "5")]
+    pub preview_good_secs: u32,
+    #[prost(uint32, tag = "7")]
+    pub preview_again_secs: u32,
+    #[prost(uint32, tag = "8")]
+    pub preview_hard_secs: u32,
+}
+
+impl From<&FilteredDeckOptions> for FilteredDeck {
+    fn from(options: &FilteredDeckOptions) -> Self {
+        Self {
+            reschedule: options.reschedule,
+            search_terms: options
+                .terms
+                .iter()
+                .map(|term| FilteredSearch {
+                    search: term.search.clone(),
+                    limit: term.limit as u32,
+                    order: term.order as i32,
+                })
+                .collect(),
+            delays: options.delays.clone().unwrap_or_default(),
+            preview_delay: options.preview_delay,
+            preview_good_secs: options.preview_good_secs,
+            preview_again_secs: options.preview_again_secs,
+            preview_hard_secs: options.preview_hard_secs,
+        }
+    }
+}
+
+impl FilteredDeck {
+    pub fn to_options(&self) -> Result<FilteredDeckOptions, String> {
+        let terms = self
+            .search_terms
+            .iter()
+            .map(|term| {
+                let order = serde_json::from_value(term.order.into())
+                    .map_err(|_| {
+                        format!("Invalid search order {}", term.order)
+                    })?;
+                Ok(FilteredSearchTerm::new(
+                    &term.search,
+                    term.limit as usize,
+                    order,
+                ))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(FilteredDeckOptions {
+            reschedule: self.reschedule,
+            terms,
+            separate: true,
+            delays: (!self.delays.is_empty())
+                .then(|| self.delays.clone()),
+            preview_delay: self.preview_delay,
+            preview_again_secs: self.preview_again_secs,
+            preview_hard_secs: self.preview_hard_secs,
+            preview_good_secs: self.preview_good_secs,
+        })
+    }
+}
+
+/// Deck.KindContainer, stored in decks.kind
+#[derive(Clone, PartialEq, Message)]
+pub struct DeckKindContainer {
+    #[prost(oneof = "DeckKind", tags = "1, 2")]
+    pub kind: Option<DeckKind>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum DeckKind {
+    #[prost(message, tag = "1")]
+    Normal(NormalDeck),
+    #[prost(message, tag = "2")]
+    Filtered(FilteredDeck),
+}
+
+/// DeckConfig.Config of deck_config.proto
+#[derive(Clone, PartialEq, Message)]
+pub struct DeckConfigConfig {
+    #[prost(float, repeated, tag = "1")]
+    pub learn_steps: Vec<f32>,
+    #[prost(float, repeated, tag = "2")]
+    pub relearn_steps: Vec<f32>,
+    #[prost(uint32, tag = "9")]
+    pub new_per_day: u32,
+    #[prost(uint32, tag = "10")]
+    pub reviews_per_day: u32,
+    #[prost(float, tag = "11")]
+    pub initial_ease: f32,
+    #[prost(float, tag = "12")]
+    pub easy_multiplier: f32,
+    #[prost(float, tag = "13")]
+    pub hard_multiplier: f32,
+    #[prost(float, tag = "14")]
+    pub lapse_multiplier: f32,
+    #[prost(float, tag = "15")]
+    pub interval_multiplier: f32,
+    #[prost(uint32, tag = "16")]
+    pub maximum_review_interval: u32,
+    #[prost(uint32, tag = "17")]
+    pub minimum_lapse_interval: u32,
+    #[prost(uint32, tag = "18")]
+    pub graduating_interval_good: u32,
+    #[prost(uint32, tag = "19")]
+    pub graduating_interval_easy: u32,
+    // 0 due, 1 random
+    #[prost(int32, tag = "20")]
+    pub new_card_insert_order: i32,
+    #[prost(int32, tag = "21")]
+    pub leech_action: i32,
+    #[prost(uint32, tag = "22")]
+    pub leech_threshold: u32,
+    #[prost(bool, tag = "23")]
+    pub disable_autoplay: bool,
+    #[prost(uint32, tag = "24")]
+    pub cap_answer_time_to_secs: u32,
+    #[prost(bool, tag = "25")]
+    pub show_timer: bool,
+    #[prost(bool, tag = "26")]
+    pub skip_question_when_replaying_answer: bool,
+    #[prost(bool, tag = "27")]
+    pub bury_new: bool,
+    #[prost(bool, tag = "28")]
+    pub bury_reviews: bool,
+}
+
+const REQUIREMENT_KINDS: [&str; 3] = ["none", "any", "all"];
+
+fn decode_error(err: prost::DecodeError) -> String {
+    err.to_string()
+}
+
+impl From<&Model> for NotetypeConfig {
+    fn from(model: &Model) -> Self {
+        Self {
+            kind: model.model_type.clone() as i32,
+            sort_field_idx: model.sort_field_index as u32,
+            css: model.css.clone(),
+            target_deck_id_unused: model.default_deck_id as i64,
+            latex_pre: model.latex_pre.clone(),
+            latex_post: model.latex_post.clone(),
+            reqs: model
+                .req
+                .iter()
+                .map(|(ordinal, kind, fields)| CardRequirement {
+                    card_ord: *ordinal as u32,
+                    kind: REQUIREMENT_KINDS
+                        .iter()
+                        .position(|name| name == kind)
+                        .unwrap_or_default()
+                        as i32,
+                    field_ords: fields
+                        .iter()
+                        .map(|field| *field as u32)
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&ModelField> for FieldConfig {
+    fn from(field: &ModelField) -> Self {
+        Self {
+            sticky: field.sticky,
+            rtl: field.right_to_left,
+            font_name: field.font.clone(),
+            font_size: field.font_size as u32,
+        }
+    }
+}
+
+impl From<&ModelTemplate> for TemplateConfig {
+    fn from(template: &ModelTemplate) -> Self {
+        Self {
+            q_format: template.question_format.clone(),
+            a_format: template.answer_template.clone(),
+            q_format_browser: template
+                .browser_question_format
+                .clone(),
+            a_format_browser: template
+                .browser_answer_template
+                .clone(),
+            target_deck_id: template
+                .default_deck_id
+                .unwrap_or_default()
+                as i64,
+        }
+    }
+}
+
+impl From<&DeckConfig> for DeckConfigConfig {
+    fn from(config: &DeckConfig) -> Self {
+        let new = &config.new_config;
+        let review = &config.review_config;
+        let lapse = &config.lapse_config;
+        Self {
+            learn_steps: new.delays.clone(),
+            relearn_steps: lapse.delays.clone(),
+            new_per_day: new.per_day as u32,
+            reviews_per_day: review.cards_daily as u32,
+            initial_ease: new.initial_factor as f32 / 1000.0,
+            easy_multiplier: review.ease_factor,
+            hard_multiplier: review.hard_factor,
+            lapse_multiplier: lapse.interval_increase,
+            interval_multiplier: review.interval_factor,
+            maximum_review_interval: review.max_interval as u32,
+            minimum_lapse_interval: lapse.min_interval as u32,
+            graduating_interval_good: new
+                .intervals
+                .first()
+                .copied()
+                .unwrap_or_default()
+                as u32,
+            graduating_interval_easy: new
+                .intervals
+                .get(1)
+                .copied()
+                .unwrap_or_default()
+                as u32,
+            new_card_insert_order: match new.order {
+                NewCardOrder::Due => 0,
+                NewCardOrder::Random => 1,
+            },
+            leech_action: lapse.leech_action as i32,
+            leech_threshold: lapse.leech_fails as u32,
+            disable_autoplay: !config.autoplay,
+            cap_answer_time_to_secs: config.max_taken as u32,
+            show_timer: config.timer,
+            skip_question_when_replaying_answer: !config
+                .replay_question,
+            bury_new: new.bury,
+            bury_reviews: review.bury,
+        }
+    }
+}
+
+impl DeckConfigConfig {
+    pub fn to_deck_config(
+        &self,
+        id: usize,
+        name: &str,
+        modified: usize,
+        update_seq_number: usize,
+    ) -> DeckConfig {
+        let mut config = DeckConfig::new(name.to_string());
+        config.id = Some(id);
+        config.modified = modified;
+        config.update_seq_number = update_seq_number;
+        config.autoplay = !self.disable_autoplay;
+        config.max_taken = self.cap_answer_time_to_secs as usize;
+        config.timer = self.show_timer;
+        config.replay_question =
+            !self.skip_question_when_replaying_answer;
+
+        let new = &mut config.new_config;
+        new.delays = self.learn_steps.clone();
+        new.per_day = self.new_per_day as usize;
+        new.initial_factor =
+            (self.initial_ease * 1000.0).round() as usize;
+        new.intervals = vec![
+            self.graduating_interval_good as usize,
+            self.graduating_interval_easy as usize,
+        ];
+        new.order = match self.new_card_insert_order {
+            0 => NewCardOrder::Due,
+            _ => NewCardOrder::Random,
+        };
+        new.bury = self.bury_new;
+
+        let review = &mut config.review_config;
+        review.cards_daily = self.reviews_per_day as usize;
+        review.ease_factor = self.easy_multiplier;
+        review.hard_factor = self.hard_multiplier;
+        review.interval_factor = self.interval_multiplier;
+        review.max_interval = self.maximum_review_interval as usize;
+        review.bury = self.bury_reviews;
+
+        let lapse = &mut config.lapse_config;
+        lapse.delays = self.relearn_steps.clone();
+        lapse.interval_increase = self.lapse_multiplier;
+        lapse.min_interval = self.minimum_lapse_interval as usize;
+        lapse.leech_action = match self.leech_action {
+            0 => LeechAction::Suspend,
+            _ => LeechAction::TagOnly,
+        };
+        lapse.leech_fails = self.leech_threshold as usize;
+        config
+    }
+}
+
+fn deck_common(deck: &Deck) -> DeckCommon {
+    DeckCommon {
+        study_collapsed: deck.collapsed,
+        browser_collapsed: deck.browser_collapsed,
+        last_day_studied: deck.new_today[0] as u32,
+        new_studied: deck.new_today[1] as i32,
+        review_studied: deck.review_today[1] as i32,
+        learning_studied: deck.learn_today[1] as i32,
+        milliseconds_studied: deck.time_today[1] as i32,
+    }
+}
+
+fn deck_kind(deck: &Deck) -> DeckKindContainer {
+    let kind = if deck.is_filtered() {
+        DeckKind::Filtered(
+            deck.filtered_options
+                .as_ref()
+                .map(FilteredDeck::from)
+                .unwrap_or_else(|| FilteredDeck {
+                    reschedule: true,
+                    ..Default::default()
+                }),
+        )
+    } else {
+        DeckKind::Normal(NormalDeck {
+            config_id: deck.config_id.unwrap_or(1) as i64,
+            extend_new: deck.extended_new_limit.unwrap_or_default()
+                as u32,
+            extend_review: deck
+                .extended_review_limit
+                .unwrap_or_default()
+                as u32,
+            description: deck.description.clone(),
+        })
+    };
+    DeckKindContainer { kind: Some(kind) }
+}
+
+pub fn create_schema(connection: &Connection) -> Result<(), String> {
+    register_collations(connection)?;
+    connection.execute_batch(SCHEMA).map_err(sql_error)
+}
+
+/// Anki declares the name columns with its own `unicase` collation,
+/// which has to exist on a connection using those tables.
+pub fn register_collations(
+    connection: &Connection,
+) -> Result<(), String> {
+    connection
+        .create_collation("unicase", |a, b| {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        })
+        .map_err(sql_error)
+}
+
+pub fn insert_collection(
+    connection: &Connection,
+    collection: &Collection,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "INSERT INTO col VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, \
+             '', '', '', '', '')",
+            params![
+                collection.id,
+                collection.created,
+                collection.modified,
+                collection.scheme_mod_time,
+                18,
+                collection.dirty,
+                collection.update_seq_number,
+                collection.last_sync_time,
+            ],
+        )
+        .map_err(sql_error)?;
+
+    let mtime_secs = collection.modified / 1000;
+    let config = serde_json::to_value(&collection.config)
+        .map_err(json_error)?;
+    for (key, value) in config.as_object().into_iter().flatten() {
+        connection
+            .execute(
+                "INSERT INTO config VALUES (?1, 0, ?2, ?3)",
+                params![
+                    key,
+                    mtime_secs,
+                    value.to_string().into_bytes()
+                ],
+            )
+            .map_err(sql_error)?;
+    }
+
+    for (_, model) in written_models(collection)? {
+        insert_model(connection, &model)?;
+    }
+    for (id, deck) in &collection.decks {
+        let deck = Deck {
+            id: *id as i64,
+            ..deck.clone()
+        };
+        deck.insert(connection)?;
+    }
+    for (id, deck_config) in &collection.deck_configs {
+        connection
+            .execute(
+                "INSERT INTO deck_config VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    *id as i64,
+                    deck_config.name,
+                    deck_config.modified as i64,
+                    deck_config.update_seq_number as i64,
+                    DeckConfigConfig::from(deck_config)
+                        .encode_to_vec(),
+                ],
+            )
+            .map_err(sql_error)?;
+    }
+
+    if !collection.tags.is_empty() {
+        let tags: BTreeMap<String, i64> =
+            serde_json::from_str(&collection.tags)
+                .map_err(json_error)?;
+        for (tag, usn) in tags {
+            connection
+                .execute(
+                    "INSERT INTO tags VALUES (?1, ?2, 0, NULL)",
+                    params![tag, usn],
+                )
+                .map_err(sql_error)?;
+        }
+    }
+    Ok(())
+}
+
+fn insert_model(
+    connection: &Connection,
+    model: &Model,
+) -> Result<(), String> {
+    let id = model.model_id as i64;
+    connection
+        .execute(
+            "INSERT INTO notetypes VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                model.name,
+                model.modification,
+                model.update_seq_number,
+                NotetypeConfig::from(model).encode_to_vec(),
+            ],
+        )
+        .map_err(sql_error)?;
+    for field in &model.fields {
+        connection
+            .execute(
+                "INSERT INTO fields VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    id,
+                    field.ordinal as i64,
+                    field.name,
+                    FieldConfig::from(field).encode_to_vec(),
+                ],
+            )
+            .map_err(sql_error)?;
+    }
+    for (ordinal, template) in model.templates.iter().enumerate() {
+        connection
+            .execute(
+                "INSERT INTO templates VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    id,
+                    ordinal as i64,
+                    template.name,
+                    model.modification,
+                    model.update_seq_number,
+                    TemplateConfig::from(template).encode_to_vec(),
+                ],
+            )
+            .map_err(sql_error)?;
+    }
+    Ok(())
+}
+
+/// decks row of the deck, with "::" in the name replaced by the
+/// separator of the decks table.
+pub(crate) fn deck_to_row(deck: &Deck) -> Vec<SqlValue> {
+    vec![
+        SqlValue::Integer(deck.id),
+        SqlValue::Text(
+            deck.name.replace("::", &DECK_NAME_SEPARATOR.to_string()),
+        ),
+        SqlValue::Integer(deck.modified),
+        SqlValue::Integer(deck.update_seq_number),
+        SqlValue::Blob(deck_common(deck).encode_to_vec()),
+        SqlValue::Blob(deck_kind(deck).encode_to_vec()),
+    ]
+}
+
+pub(crate) fn deck_from_row(row: &Row) -> Result<Deck, String> {
+    let common = DeckCommon::decode(
+        column::<Vec<u8>>(row, "common")?.as_slice(),
+    )
+    .map_err(decode_error)?;
+    let kind = DeckKindContainer::decode(
+        column::<Vec<u8>>(row, "kind")?.as_slice(),
+    )
+    .map_err(decode_error)?;
+
+    let mut deck = Deck::new(
+        row.get::<_, String>("name")
+            .map_err(sql_error)?
+            .replace(DECK_NAME_SEPARATOR, "::"),
+    );
+    deck.id = row.get("id").map_err(sql_error)?;
+    deck.modified = row.get("mtime_secs").map_err(sql_error)?;
+    deck.update_seq_number = row.get("usn").map_err(sql_error)?;
+    deck.collapsed = common.study_collapsed;
+    deck.browser_collapsed = common.browser_collapsed;
+    let day = common.last_day_studied as usize;
+    deck.new_today = [day, common.new_studied as usize];
+    deck.review_today = [day, common.review_studied as usize];
+    deck.learn_today = [day, common.learning_studied as usize];
+    deck.time_today = [day, common.milliseconds_studied as usize];
+    match kind.kind {
+        Some(DeckKind::Normal(normal)) => {
+            deck.config_id = Some(normal.config_id as usize);
+            deck.extended_new_limit =
+                Some(normal.extend_new as usize);
+            deck.extended_review_limit =
+                Some(normal.extend_review as usize);
+            deck.description = normal.description;
+        }
+        Some(DeckKind::Filtered(filtered)) => {
+            deck.filtered = 1;
+            deck.filtered_options = Some(filtered.to_options()?);
+        }
+        None => return Err(format!("Deck {} has no kind", deck.id)),
+    }
+    Ok(deck)
+}
+
+fn integer(
+    row: &Map<String, Value>,
+    column: &str,
+) -> Result<i64, String> {
+    row.get(column)
+        .and_then(Value::as_i64)
+        .ok_or_else(|| format!("{} is not an integer", column))
+}
+
+fn text(
+    row: &Map<String, Value>,
+    column: &str,
+) -> Result<String, String> {
+    row.get(column)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| format!("{} is not text", column))
+}
+
+fn column<T: FromSql>(row: &Row, name: &str) -> Result<T, String> {
+    row.get(name).map_err(sql_error)
+}
+
+// Rows mapped as they are read, unlike select_json this keeps blobs
+fn select_rows<T>(
+    connection: &Connection,
+    sql: &str,
+    params: impl Params,
+    mut map: impl FnMut(&Row) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    let mut statement = connection.prepare(sql).map_err(sql_error)?;
+    let mut rows = statement.query(params).map_err(sql_error)?;
+    let mut result = vec![];
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        result.push(map(row)?);
+    }
+    Ok(result)
+}
+
+fn select_fields(
+    connection: &Connection,
+    model_id: i64,
+) -> Result<Vec<ModelField>, String> {
+    select_rows(
+        connection,
+        "SELECT ord, name, config FROM fields WHERE ntid = ? \
+         ORDER BY ord",
+        [model_id],
+        |row| {
+            let config = FieldConfig::decode(
+                column::<Vec<u8>>(row, "config")?.as_slice(),
+            )
+            .map_err(decode_error)?;
+            let mut field = ModelField::new(
+                column(row, "name")?,
+                column::<i64>(row, "ord")? as usize,
+            );
+            field.sticky = config.sticky;
+            field.right_to_left = config.rtl;
+            field.font = config.font_name;
+            field.font_size = config.font_size as usize;
+            Ok(field)
+        },
+    )
+}
+
+fn select_templates(
+    connection: &Connection,
+    model_id: i64,
+) -> Result<Vec<ModelTemplate>, String> {
+    select_rows(
+        connection,
+        "SELECT name, config FROM templates WHERE ntid = ? \
+         ORDER BY ord",
+        [model_id],
+        |row| {
+            let config = TemplateConfig::decode(
+                column::<Vec<u8>>(row, "config")?.as_slice(),
+            )
+            .map_err(decode_error)?;
+            let mut template = ModelTemplate::new(
+                column(row, "name")?,
+                config.q_format,
+                config.a_format,
+            );
+            template.browser_question_format =
+                config.q_format_browser;
+            template.browser_answer_template =
+                config.a_format_browser;
+            template.default_deck_id = Some(config.target_deck_id)
+                .filter(|id| *id != 0)
+                .map(|id| id as u64);
+            Ok(template)
+        },
+    )
+}
+
+fn select_models(
+    connection: &Connection,
+) -> Result<Vec<Model>, String> {
+    let rows = select_rows(
+        connection,
+        "SELECT id, name, mtime_secs, usn, config FROM notetypes \
+         ORDER BY id",
+        [],
+        |row| {
+            let config = NotetypeConfig::decode(
+                column::<Vec<u8>>(row, "config")?.as_slice(),
+            )
+            .map_err(decode_error)?;
+            Ok((
+                column::<i64>(row, "id")?,
+                column::<String>(row, "name")?,
+                column::<i64>(row, "mtime_secs")?,
+                column::<i64>(row, "usn")?,
+                config,
+            ))
+        },
+    )?;
+
+    let mut models = vec![];
+    for (id, name, modified, update_seq_number, config) in rows {
+        let model_type = match config.kind {
+            1 => ModelType::Cloze,
+            _ => ModelType::FrontBack,
+        };
+        let mut model = Model::new(
+            Some(config.css),
+            select_fields(connection, id)?,
+            id as u64,
+            Some(config.latex_post),
+            Some(config.latex_pre),
+            name,
+            config.sort_field_idx as usize,
+            select_templates(connection, id)?,
+            model_type,
+        );
+        model.default_deck_id = config.target_deck_id_unused as u64;
+        model.modification = modified;
+        model.update_seq_number = update_seq_number;
+        model.req = config
+            .reqs
+            .iter()
+            .map(|req| {
+                (
+                    req.card_ord as usize,
+                    REQUIREMENT_KINDS
+                        .get(req.kind as usize)
+                        .unwrap_or(&"none")
+                        .to_string(),
+                    req.field_ords
+                        .iter()
+                        .map(|ord| *ord as usize)
+                        .collect(),
+                )
+            })
+            .collect();
+        models.push(model);
+    }
+    Ok(models)
+}
+
+fn select_deck_configs(
+    connection: &Connection,
+) -> Result<Vec<(usize, DeckConfig)>, String> {
+    select_rows(
+        connection,
+        "SELECT id, name, mtime_secs, usn, config FROM deck_config \
+         ORDER BY id",
+        [],
+        |row| {
+            let id = column::<i64>(row, "id")? as usize;
+            let config = DeckConfigConfig::decode(
+                column::<Vec<u8>>(row, "config")?.as_slice(),
+            )
+            .map_err(decode_error)?;
+            Ok((
+                id,
+                config.to_deck_config(
+                    id,
+                    &column::<String>(row, "name")?,
+                    column::<i64>(row, "mtime_secs")? as usize,
+                    column::<i64>(row, "usn")? as usize,
+                ),
+            ))
+        },
+    )
+}
+
+// Config table values over the defaults, so keys added by newer Anki
+// versions are ignored and missing ones keep their default
+fn select_config(
+    connection: &Connection,
+) -> Result<CollectionConfig, String> {
+    let mut config = serde_json::to_value(CollectionConfig::new())
+        .map_err(json_error)?;
+    let defaults = config
+        .as_object_mut()
+        .ok_or("Collection config is not an object")?;
+    let rows = select_rows(
+        connection,
+        "SELECT KEY AS key, val FROM config",
+        [],
+        |row| {
+            Ok((
+                column::<String>(row, "key")?,
+                column::<Vec<u8>>(row, "val")?,
+            ))
+        },
+    )?;
+    for (key, value) in rows {
+        if !defaults.contains_key(&key) {
+            continue;
+        }
+        let value: Value =
+            serde_json::from_slice(&value).map_err(json_error)?;
+        defaults.insert(key, value);
+    }
+    serde_json::from_value(config).map_err(json_error)
+}
+
+fn select_tags(connection: &Connection) -> Result<String, String> {
+    let tags = select_json(connection, "SELECT tag, usn FROM tags")?
+        .iter()
+        .map(|row| Ok((text(row, "tag")?, integer(row, "usn")?)))
+        .collect::<Result<BTreeMap<_, _>, String>>()?;
+    serde_json::to_string(&tags).map_err(json_error)
+}
+
+/// Collection of a schema 18 database, rebuilt from the notetypes,
+/// decks, deck_config, config and tags tables.
+pub fn select_collection(
+    connection: &Connection,
+) -> Result<Collection, String> {
+    register_collations(connection)?;
+    let rows = select_json(
+        connection,
+        "SELECT id, crt, mod, scm, ver, dty, usn, ls FROM col",
+    )?;
+    let [row] = rows.as_slice() else {
+        return Err(format!(
+            "Expected one col row, found {}",
+            rows.len()
+        ));
+    };
+
+    Ok(Collection {
+        id: integer(row, "id")?,
+        created: integer(row, "crt")?,
+        modified: integer(row, "mod")?,
+        scheme_mod_time: integer(row, "scm")?,
+        version: integer(row, "ver")? as usize,
+        dirty: integer(row, "dty")?,
+        update_seq_number: integer(row, "usn")?,
+        last_sync_time: integer(row, "ls")?,
+        config: select_config(connection)?,
+        models: select_models(connection)?
+            .into_iter()
+            .map(|model| (model.model_id as usize, model))
+            .collect(),
+        decks: Deck::select_where(connection, "1", [])?
+            .into_iter()
+            .map(|deck| (deck.id as usize, deck))
+            .collect(),
+        deck_configs: select_deck_configs(connection)?,
+        tags: select_tags(connection)?,
+    })
+}
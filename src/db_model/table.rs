@@ -1,3 +1,206 @@
-pub trait Table {
-    fn table_name(&self) -> &'static str;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{
+    params_from_iter, Connection, OptionalExtension, Params, Row,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::db_model::database::{json_error, row_to_json, sql_error};
+use crate::db_model::grave::{Grave, GraveType};
+
+/// A db_model type stored as one row of a table. The rows are mapped
+/// through serde, so the serde renames of the type name the columns;
+/// types whose columns don't match their serialized form override
+/// `to_row` and `from_row`.
+pub trait Table: Serialize + DeserializeOwned {
+    const TABLE_NAME: &'static str;
+    // The first column is the id
+    const COLUMNS: &'static [&'static str];
+    // Grave recorded when a row is deleted, for the tables whose
+    // deletions are synced
+    const GRAVE_TYPE: Option<GraveType> = None;
+
+    fn table_name(&self) -> &'static str {
+        Self::TABLE_NAME
+    }
+
+    fn id(&self) -> i64;
+
+    /// Values of `COLUMNS`, in order.
+    fn to_row(&self) -> Result<Vec<SqlValue>, String> {
+        let map =
+            match serde_json::to_value(self).map_err(json_error)? {
+                Value::Object(map) => map,
+                _ => {
+                    return Err(format!(
+                        "{} row is not an object",
+                        Self::TABLE_NAME
+                    ))
+                }
+            };
+        columns_to_row::<Self>(&map)
+    }
+
+    fn from_row(row: &Row) -> Result<Self, String> {
+        serde_json::from_value(Value::Object(row_to_json(row)?))
+            .map_err(json_error)
+    }
+
+    fn insert(&self, connection: &Connection) -> Result<(), String> {
+        let placeholders = (1..=Self::COLUMNS.len())
+            .map(|index| format!("?{}", index))
+            .collect::<Vec<_>>()
+            .join(", ");
+        connection
+            .execute(
+                &format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    Self::TABLE_NAME,
+                    Self::COLUMNS.join(", "),
+                    placeholders
+                ),
+                params_from_iter(self.to_row()?),
+            )
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    /// Updates the row with the same id, an error if there is none.
+    fn update(&self, connection: &Connection) -> Result<(), String> {
+        let assignments = Self::COLUMNS[1..]
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                format!("{} = ?{}", column, index + 2)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let changed = connection
+            .execute(
+                &format!(
+                    "UPDATE {} SET {} WHERE {} = ?1",
+                    Self::TABLE_NAME,
+                    assignments,
+                    Self::COLUMNS[0]
+                ),
+                params_from_iter(self.to_row()?),
+            )
+            .map_err(sql_error)?;
+        if changed == 0 {
+            return Err(format!(
+                "No row {} in {}",
+                self.id(),
+                Self::TABLE_NAME
+            ));
+        }
+        Ok(())
+    }
+
+    fn select_by_id(
+        connection: &Connection,
+        id: i64,
+    ) -> Result<Option<Self>, String> {
+        connection
+            .query_row(
+                &format!(
+                    "SELECT {} FROM {} WHERE {} = ?1",
+                    Self::COLUMNS.join(", "),
+                    Self::TABLE_NAME,
+                    Self::COLUMNS[0]
+                ),
+                [id],
+                |row| Ok(Self::from_row(row)),
+            )
+            .optional()
+            .map_err(sql_error)?
+            .transpose()
+    }
+
+    /// Deletes the row with the id, recording a grave with the update
+    /// sequence number for tables that have them. An error if there is
+    /// no such row.
+    fn delete_by_id(
+        connection: &Connection,
+        id: i64,
+        update_seq_number: i64,
+    ) -> Result<(), String> {
+        let deleted = connection
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE {} = ?1",
+                    Self::TABLE_NAME,
+                    Self::COLUMNS[0]
+                ),
+                [id],
+            )
+            .map_err(sql_error)?;
+        if deleted == 0 {
+            return Err(format!(
+                "No row {} in {}",
+                id,
+                Self::TABLE_NAME
+            ));
+        }
+        match Self::GRAVE_TYPE {
+            Some(grave_type) => {
+                Grave::new(id, grave_type, update_seq_number)
+                    .insert(connection)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Rows matching the SQL condition, ordered by id, e.g.
+    /// `Card::select_where(&connection, "nid = ?1", [note_id])`.
+    fn select_where<P: Params>(
+        connection: &Connection,
+        condition: &str,
+        params: P,
+    ) -> Result<Vec<Self>, String> {
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM {} WHERE {} ORDER BY {}",
+                Self::COLUMNS.join(", "),
+                Self::TABLE_NAME,
+                condition,
+                Self::COLUMNS[0]
+            ))
+            .map_err(sql_error)?;
+        let mut rows = statement.query(params).map_err(sql_error)?;
+        let mut result = vec![];
+        while let Some(row) = rows.next().map_err(sql_error)? {
+            result.push(Self::from_row(row)?);
+        }
+        Ok(result)
+    }
+}
+
+/// Values of the table's columns in a serialized row, nested values
+/// are stored as JSON text.
+pub fn columns_to_row<T: Table>(
+    map: &Map<String, Value>,
+) -> Result<Vec<SqlValue>, String> {
+    T::COLUMNS
+        .iter()
+        .map(|column| {
+            let value = map.get(*column).ok_or_else(|| {
+                format!("{} has no column {}", T::TABLE_NAME, column)
+            })?;
+            Ok(match value {
+                Value::Null => SqlValue::Null,
+                Value::Bool(value) => {
+                    SqlValue::Integer(*value as i64)
+                }
+                Value::Number(number) => match number.as_i64() {
+                    Some(integer) => SqlValue::Integer(integer),
+                    None => SqlValue::Real(
+                        number.as_f64().unwrap_or_default(),
+                    ),
+                },
+                Value::String(text) => SqlValue::Text(text.clone()),
+                value => SqlValue::Text(value.to_string()),
+            })
+        })
+        .collect()
 }
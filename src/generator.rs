@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use crate::db_model::model::Model;
+use crate::information::{InformationContent, IntoCards, NoteCards};
+use crate::markdown::util::front_matter;
+
+/// Front matter key selecting the card generator of a document.
+pub const GENERATOR_KEY: &str = "generator";
+pub const DEFAULT_GENERATOR: &str = "basic";
+
+type GeneratorFactory = Box<dyn Fn() -> Box<dyn IntoCards>>;
+
+/// One card showing the front and asking for the back.
+#[derive(Debug, Clone, Default)]
+pub struct BasicCards;
+
+impl IntoCards for BasicCards {
+    fn into_cards(
+        &self,
+        information_content: &InformationContent,
+        model: &Model,
+    ) -> Result<Vec<NoteCards>, String> {
+        Ok(vec![NoteCards::new(information_content, model, &[0])?])
+    }
+}
+
+/// A front to back card and a back to front card.
+#[derive(Debug, Clone, Default)]
+pub struct ReversedCards;
+
+impl IntoCards for ReversedCards {
+    fn into_cards(
+        &self,
+        information_content: &InformationContent,
+        model: &Model,
+    ) -> Result<Vec<NoteCards>, String> {
+        Ok(vec![NoteCards::new(information_content, model, &[0, 1])?])
+    }
+}
+
+/// One card per cloze number found in the clozed fields, ordinals
+/// start at 0 for `c1` as in Anki.
+#[derive(Debug, Clone, Default)]
+pub struct ClozeCards;
+
+impl IntoCards for ClozeCards {
+    fn into_cards(
+        &self,
+        information_content: &InformationContent,
+        model: &Model,
+    ) -> Result<Vec<NoteCards>, String> {
+        let bundle =
+            NoteCards::from_templates(information_content, model)?;
+        if bundle.cards.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![bundle])
+    }
+}
+
+/// Runs the wrapped generator once per sense of a polysemous entry,
+/// giving every sense its own note.
+pub struct PerSense(pub Box<dyn IntoCards>);
+
+impl IntoCards for PerSense {
+    fn into_cards(
+        &self,
+        information_content: &InformationContent,
+        model: &Model,
+    ) -> Result<Vec<NoteCards>, String> {
+        if information_content.senses.len() <= 1 {
+            return self.0.into_cards(information_content, model);
+        }
+
+        let key = information_content.note_guid_key(model);
+        let mut notes = vec![];
+        for (index, mut content) in (0..information_content
+            .senses
+            .len())
+            .filter_map(|index| information_content.for_sense(index))
+            .enumerate()
+        {
+            content.guid_key = Some(key.sense(index + 1));
+            notes.extend(self.0.into_cards(&content, model)?);
+        }
+        Ok(notes)
+    }
+}
+
+/// Card generators by name, documents pick one with a `generator` key
+/// in their front matter.
+pub struct GeneratorRegistry {
+    generators: HashMap<String, GeneratorFactory>,
+}
+
+impl GeneratorRegistry {
+    pub fn new() -> Self {
+        Self {
+            generators: HashMap::new(),
+        }
+    }
+
+    /// Registry with the built-in "basic", "reversed", "cloze" and
+    /// "senses" (basic, one note per sense) generators.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.generators.insert(
+            "basic".to_string(),
+            Box::new(|| Box::new(BasicCards)),
+        );
+        registry.generators.insert(
+            "reversed".to_string(),
+            Box::new(|| Box::new(ReversedCards)),
+        );
+        registry.generators.insert(
+            "cloze".to_string(),
+            Box::new(|| Box::new(ClozeCards)),
+        );
+        registry.generators.insert(
+            "senses".to_string(),
+            Box::new(|| Box::new(PerSense(Box::new(BasicCards)))),
+        );
+        registry
+    }
+
+    pub fn register<F>(
+        &mut self,
+        name: &str,
+        factory: F,
+    ) -> Result<(), String>
+    where
+        F: Fn() -> Box<dyn IntoCards> + 'static,
+    {
+        if name.trim().is_empty() {
+            return Err("Generator name cannot be empty".to_string());
+        }
+        if self.generators.contains_key(name) {
+            return Err(format!(
+                "Generator '{}' is already registered",
+                name
+            ));
+        }
+        self.generators.insert(name.to_string(), Box::new(factory));
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.generators.contains_key(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self
+            .generators
+            .keys()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn get(
+        &self,
+        name: &str,
+    ) -> Result<Box<dyn IntoCards>, String> {
+        self.generators
+            .get(name)
+            .map(|factory| factory())
+            .ok_or_else(|| format!("Unknown generator '{}'", name))
+    }
+
+    /// Generator named in the document front matter, "basic" when the
+    /// document does not name one.
+    pub fn for_document(
+        &self,
+        markdown: &str,
+    ) -> Result<Box<dyn IntoCards>, String> {
+        let name = front_matter(markdown)
+            .into_iter()
+            .find(|(key, _)| key == GENERATOR_KEY)
+            .map(|(_, value)| value)
+            .unwrap_or_else(|| DEFAULT_GENERATOR.to_string());
+        self.get(&name)
+    }
+}
+
+impl Default for GeneratorRegistry {
+    fn default() -> Self {
+        Self::with_defaults()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db_model::model::{
+        ModelField, ModelTemplate, ModelType,
+    };
+    use crate::information::Sense;
+
+    struct ThreeCards;
+
+    impl IntoCards for ThreeCards {
+        fn into_cards(
+            &self,
+            information_content: &InformationContent,
+            model: &Model,
+        ) -> Result<Vec<NoteCards>, String> {
+            Ok(vec![NoteCards::new(
+                information_content,
+                model,
+                &[0, 1, 2],
+            )?])
+        }
+    }
+
+    fn text_model() -> Model {
+        Model::new(
+            None,
+            vec![ModelField::new("Text".to_string(), 0)],
+            1,
+            None,
+            None,
+            "Text".to_string(),
+            0,
+            vec![ModelTemplate::new(
+                "Cloze".to_string(),
+                "{{cloze:Text}}".to_string(),
+                "{{cloze:Text}}".to_string(),
+            )],
+            ModelType::Cloze,
+        )
+    }
+
+    #[test]
+    fn test_default_generators() {
+        let registry = GeneratorRegistry::default();
+        assert_eq!(
+            registry.names(),
+            vec!["basic", "cloze", "reversed", "senses"]
+        );
+
+        let content = InformationContent {
+            fields: vec![(
+                "Text".to_string(),
+                "{{c1::hola}} {{c2::mundo}}".to_string(),
+            )],
+            ..Default::default()
+        };
+        let ordinals = |name: &str| {
+            registry
+                .get(name)
+                .unwrap()
+                .into_cards(&content, &text_model())
+                .unwrap()
+                .iter()
+                .flat_map(|note| note.cards.iter())
+                .map(|card| card.ordinal)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ordinals("basic"), vec![0]);
+        assert_eq!(ordinals("reversed"), vec![0, 1]);
+        assert_eq!(ordinals("cloze"), vec![0, 1]);
+        assert!(registry
+            .get("cloze")
+            .unwrap()
+            .into_cards(&InformationContent::default(), &text_model())
+            .unwrap()
+            .is_empty());
+        assert!(registry.get("missing").is_err());
+    }
+
+    #[test]
+    fn test_register_and_select() {
+        let mut registry = GeneratorRegistry::with_defaults();
+        registry.register("three", || Box::new(ThreeCards)).unwrap();
+        assert!(registry
+            .register("three", || Box::new(ThreeCards))
+            .is_err());
+
+        let content = InformationContent {
+            fields: vec![(
+                "Text".to_string(),
+                "{{c1::uno}} {{c2::dos}} {{c3::tres}}".to_string(),
+            )],
+            ..Default::default()
+        };
+        let document = "---\ngenerator: three\n---\n# hola\n";
+        let generator = registry.for_document(document).unwrap();
+        let notes =
+            generator.into_cards(&content, &text_model()).unwrap();
+        assert_eq!(notes[0].cards.len(), 3);
+
+        let generator = registry.for_document("# hola\n").unwrap();
+        let notes =
+            generator.into_cards(&content, &text_model()).unwrap();
+        assert_eq!(notes[0].cards.len(), 1);
+    }
+
+    #[test]
+    fn test_per_sense() {
+        let model = Model::new(
+            None,
+            vec![
+                ModelField::new("Front".to_string(), 0),
+                ModelField::new("Back".to_string(), 1),
+            ],
+            1,
+            None,
+            None,
+            "Basic".to_string(),
+            0,
+            vec![ModelTemplate::new(
+                "Card 1".to_string(),
+                "{{Front}}".to_string(),
+                "{{Back}}".to_string(),
+            )],
+            ModelType::FrontBack,
+        );
+        let content = InformationContent {
+            word: "bank".to_string(),
+            senses: vec![
+                Sense::new("a place that keeps money"),
+                Sense::new("the side of a river"),
+            ],
+            sense_field: Some("Back".to_string()),
+            fields: vec![
+                ("Front".to_string(), "bank".to_string()),
+                ("Back".to_string(), "both senses".to_string()),
+            ],
+            tags: vec![],
+            guid_key: None,
+        };
+        let registry = GeneratorRegistry::default();
+
+        let notes = registry
+            .get("senses")
+            .unwrap()
+            .into_cards(&content, &model)
+            .unwrap();
+
+        assert_eq!(notes.len(), 2);
+        assert_eq!(
+            notes[0].note.fields[1],
+            "a place that keeps money"
+        );
+        assert_eq!(notes[1].note.fields[1], "the side of a river");
+        assert_ne!(notes[0].note.global_id, notes[1].note.global_id);
+
+        let single = content.for_sense(0).unwrap();
+        let notes = registry
+            .get("senses")
+            .unwrap()
+            .into_cards(&single, &model)
+            .unwrap();
+        assert_eq!(notes.len(), 1);
+    }
+}
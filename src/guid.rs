@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+// Anki's base91 table, letters, digits and the printable ASCII
+// punctuation except quotes and backslash
+const BASE91_TABLE: &[u8] = b"abcdefghijklmnopqrstuvwxyz\
+ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!#$%&()*+,-./:;<=>?@[]^_`{|}~";
+
+/// Encodes the number with Anki's base91 table, most significant
+/// digit first.
+pub fn base91(mut number: u64) -> String {
+    let mut digits = vec![];
+    loop {
+        digits.push(BASE91_TABLE[(number % 91) as usize]);
+        number /= 91;
+        if number == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap_or_default()
+}
+
+/// A random 64-bit GUID, as Anki creates for new notes.
+pub fn random_guid() -> String {
+    base91(fastrand::u64(..))
+}
+
+/// Stable identity of a note, so rebuilding a deck from the same
+/// documents gives its notes the same GUIDs and Anki updates them
+/// instead of adding duplicates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuidKey {
+    /// An entry of a document, by the document path and the headings
+    /// down to the entry.
+    Heading { file: String, headings: Vec<String> },
+    /// An ID given by the author, independent of where the entry is.
+    Explicit(String),
+}
+
+impl GuidKey {
+    /// Key of the entry under the headings of the file. The path is
+    /// used as given, pass it relative to the deck root so moving the
+    /// documents keeps the GUIDs.
+    pub fn heading(file: &Path, headings: &[String]) -> Self {
+        GuidKey::Heading {
+            file: file.to_string_lossy().replace('\\', "/"),
+            headings: headings.to_vec(),
+        }
+    }
+
+    pub fn explicit(id: &str) -> Self {
+        GuidKey::Explicit(id.to_string())
+    }
+
+    /// The same key in another file, explicit keys don't depend on the
+    /// file.
+    pub fn in_file(self, file: &Path) -> Self {
+        match self {
+            GuidKey::Heading { headings, .. } => {
+                Self::heading(file, &headings)
+            }
+            explicit => explicit,
+        }
+    }
+
+    /// Key of the numbered sense of the entry.
+    pub fn sense(&self, number: usize) -> Self {
+        match self {
+            GuidKey::Heading { file, headings } => {
+                let mut headings = headings.clone();
+                headings.push(format!("sense {}", number));
+                GuidKey::Heading {
+                    file: file.clone(),
+                    headings,
+                }
+            }
+            GuidKey::Explicit(id) => {
+                GuidKey::Explicit(format!("{}#{}", id, number))
+            }
+        }
+    }
+
+    /// 64-bit GUID hashed from the key, in Anki's base91.
+    pub fn guid(&self) -> String {
+        let key = match self {
+            GuidKey::Heading { file, headings } => {
+                let mut parts = vec!["heading", file.as_str()];
+                parts.extend(headings.iter().map(String::as_str));
+                parts.join("\x1f")
+            }
+            GuidKey::Explicit(id) => format!("id\x1f{}", id),
+        };
+        let hash = Sha256::digest(key.as_bytes());
+        base91(
+            hash[..8]
+                .iter()
+                .fold(0, |number, byte| (number << 8) + *byte as u64),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base91() {
+        assert_eq!(base91(0), "a");
+        assert_eq!(base91(90), "~");
+        assert_eq!(base91(91), "ba");
+        assert!(base91(u64::MAX).len() <= 10);
+    }
+
+    #[test]
+    fn test_random_guid() {
+        let guid = random_guid();
+        assert!(guid
+            .bytes()
+            .all(|byte| BASE91_TABLE.contains(&byte)));
+        assert_ne!(random_guid(), random_guid());
+    }
+
+    #[test]
+    fn test_guid_key() {
+        let headings =
+            vec!["Verbs".to_string(), "hablar".to_string()];
+        let key = GuidKey::heading(
+            Path::new("spanish/verbs.md"),
+            &headings,
+        );
+
+        assert_eq!(key.guid(), key.clone().guid());
+        assert_ne!(
+            key.guid(),
+            GuidKey::heading(
+                Path::new("spanish/nouns.md"),
+                &headings
+            )
+            .guid()
+        );
+        assert_ne!(key.guid(), key.sense(1).guid());
+        assert_eq!(
+            GuidKey::heading(
+                Path::new("spanish\\verbs.md"),
+                &headings
+            ),
+            key
+        );
+        assert_eq!(
+            GuidKey::explicit("hablar").in_file(Path::new("a.md")),
+            GuidKey::explicit("hablar")
+        );
+        assert_ne!(GuidKey::explicit("hablar").guid(), key.guid());
+    }
+}
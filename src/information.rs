@@ -1,20 +1,625 @@
-use crate::db_model::card::Card;
-use crate::markdown::ast::Node;
+use std::path::Path;
 
-type Example = Vec<String>;
+use crate::db_model::card::{Card, CardFlag, CardQueue, CardType};
+use crate::db_model::id::IdAllocator;
+use crate::db_model::model::Model;
+use crate::db_model::note::{Note, NoteTag};
+use crate::guid::GuidKey;
+use crate::markdown::ast::{Node, NodeType, SharedNode};
+use crate::tags::{heading_path_tag, push_tag, strip_hashtags};
+use crate::template::card_ordinals;
+
+/// One meaning of a word with the examples illustrating it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sense {
+    pub definition: String,
+    pub examples: Vec<String>,
+    // register or domain, written as a leading "(label)"
+    pub label: Option<String>,
+}
+
+impl Sense {
+    pub fn new(definition: &str) -> Self {
+        Self {
+            definition: definition.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_example(mut self, example: &str) -> Self {
+        self.examples.push(example.to_string());
+        self
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = match &self.label {
+            Some(label) => {
+                format!("<i>{}</i> {}", label, self.definition)
+            }
+            None => self.definition.clone(),
+        };
+        if !self.examples.is_empty() {
+            html.push_str("<ul>");
+            for example in &self.examples {
+                html.push_str(&format!("<li>{}</li>", example));
+            }
+            html.push_str("</ul>");
+        }
+        html
+    }
+
+    // Splits a leading "(label)" off the definition
+    fn from_definition(definition: &str) -> Result<Self, String> {
+        let definition = definition.trim();
+        let labeled = definition
+            .strip_prefix('(')
+            .and_then(|rest| rest.split_once(')'))
+            .map(|(label, rest)| (label.trim(), rest.trim()))
+            .filter(|(label, _)| !label.is_empty());
+
+        let sense = match labeled {
+            Some((label, rest)) => Self::new(rest).with_label(label),
+            None => Self::new(definition),
+        };
+        if sense.definition.is_empty() {
+            return Err("Sense has an empty definition".to_string());
+        }
+        Ok(sense)
+    }
+
+    // An ordered list item: its text is the definition and a nested
+    // unordered list holds the examples
+    fn from_item(item: &Node) -> Result<Self, String> {
+        let mut definition = String::new();
+        let mut examples = vec![];
+        for subnode in item.subnodes() {
+            let subnode = subnode.borrow();
+            match subnode.node_type() {
+                NodeType::List { ordered: false }
+                    if examples.is_empty() =>
+                {
+                    examples = subnode
+                        .subnodes()
+                        .iter()
+                        .map(|example| {
+                            example
+                                .borrow()
+                                .subnodes_html()
+                                .trim()
+                                .to_string()
+                        })
+                        .collect();
+                }
+                NodeType::List { .. } => {
+                    return Err(
+                        "Examples must be a single unordered list"
+                            .to_string(),
+                    )
+                }
+                _ => definition.push_str(&subnode.html_text()),
+            }
+        }
+
+        let mut sense = Self::from_definition(&definition)?;
+        if examples.iter().any(String::is_empty) {
+            return Err(format!(
+                "Empty example for sense '{}'",
+                sense.definition
+            ));
+        }
+        sense.examples = examples;
+        Ok(sense)
+    }
+
+    /// Senses of a section: the items of an ordered list, or a single
+    /// sense per paragraph.
+    pub fn from_section(section: &Node) -> Result<Vec<Self>, String> {
+        let subnodes = section.subnodes();
+        let lists = subnodes
+            .iter()
+            .filter(|node| {
+                matches!(
+                    node.borrow().node_type(),
+                    NodeType::List { .. }
+                )
+            })
+            .count();
+
+        let senses = match (lists, subnodes.len()) {
+            (0, _) => subnodes
+                .iter()
+                .map(|paragraph| {
+                    Self::from_definition(
+                        &paragraph.borrow().html_text(),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            (1, 1) => {
+                let list = subnodes[0].borrow();
+                if list.node_type()
+                    != &(NodeType::List { ordered: true })
+                {
+                    return Err(
+                        "Senses must be an ordered list".to_string()
+                    );
+                }
+                list.subnodes()
+                    .iter()
+                    .map(|item| Self::from_item(&item.borrow()))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            _ => {
+                return Err("Senses must be a single ordered list"
+                    .to_string())
+            }
+        };
+
+        if senses.is_empty() {
+            return Err("Entry has no senses".to_string());
+        }
+        Ok(senses)
+    }
+}
+
+/// HTML of a list of senses, numbered when there is more than one.
+pub fn senses_html(senses: &[Sense]) -> String {
+    match senses {
+        [sense] => sense.to_html(),
+        _ => format!(
+            "<ol>{}</ol>",
+            senses
+                .iter()
+                .map(|sense| format!("<li>{}</li>", sense.to_html()))
+                .collect::<String>()
+        ),
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct InformationContent {
     pub word: String,
-    pub definitions: Vec<String>,
-    pub examples: Vec<Option<Example>>,
+    pub senses: Vec<Sense>,
+    // model field holding the rendered senses
+    pub sense_field: Option<String>,
+    // (model field name, HTML value) pairs extracted through a FieldMapping
+    pub fields: Vec<(String, String)>,
+    pub tags: Vec<NoteTag>,
+    // identity of the entry the note GUID is derived from
+    pub guid_key: Option<GuidKey>,
+}
+
+impl InformationContent {
+    /// Identity of the note made of the content with the model.
+    /// Content built by hand has no key, the model and the word
+    /// identify it.
+    pub fn note_guid_key(&self, model: &Model) -> GuidKey {
+        self.guid_key.clone().unwrap_or_else(|| {
+            GuidKey::explicit(&format!(
+                "{}\x1f{}",
+                model.name, self.word
+            ))
+        })
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Values of the model fields in ordinal order, fields missing
+    /// from the content are left empty.
+    pub fn field_values(&self, model: &Model) -> Vec<String> {
+        let mut fields = model.fields.clone();
+        fields.sort_by_key(|field| field.ordinal);
+        fields
+            .iter()
+            .map(|field| {
+                self.field(&field.name)
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    // Moves the #hashtags of the word, fields and senses into the tags
+    fn take_hashtags(&mut self) -> Result<(), String> {
+        let mut hashtags = vec![];
+        let mut strip = |text: &mut String| {
+            let (stripped, found) = strip_hashtags(text);
+            *text = stripped;
+            hashtags.extend(found);
+        };
+
+        strip(&mut self.word);
+        for (_, value) in self.fields.iter_mut() {
+            strip(value);
+        }
+        for sense in self.senses.iter_mut() {
+            strip(&mut sense.definition);
+            sense.examples.iter_mut().for_each(&mut strip);
+        }
+
+        for hashtag in hashtags {
+            push_tag(&mut self.tags, NoteTag::repair(&hashtag)?);
+        }
+        Ok(())
+    }
+
+    /// Content restricted to a single sense, with the sense field
+    /// rendered for that sense only.
+    pub fn for_sense(&self, index: usize) -> Option<Self> {
+        let sense = self.senses.get(index)?.clone();
+        let mut content = self.clone();
+        if let Some(sense_field) = &self.sense_field {
+            for (name, value) in content.fields.iter_mut() {
+                if name == sense_field {
+                    *value = sense.to_html();
+                }
+            }
+        }
+        content.senses = vec![sense];
+        Some(content)
+    }
+}
+
+/// Declares which section headings of an entry fill which fields of
+/// the target `Model`. The entry heading itself goes to `word_field`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMapping {
+    pub word_field: String,
+    // (section heading, model field name) pairs
+    pub sections: Vec<(String, String)>,
+    // heading of the section parsed into senses
+    pub senses: Option<String>,
+}
+
+impl FieldMapping {
+    pub fn new(word_field: &str) -> Self {
+        Self {
+            word_field: word_field.to_string(),
+            sections: vec![],
+            senses: None,
+        }
+    }
+
+    /// Maps a section whose content is parsed into senses, see
+    /// `Sense::from_section`.
+    pub fn with_senses(mut self, heading: &str, field: &str) -> Self {
+        self.senses = Some(heading.trim().to_string());
+        self.with_section(heading, field)
+    }
+
+    fn is_senses(&self, heading: &str) -> bool {
+        self.senses.as_ref().is_some_and(|senses| {
+            senses.eq_ignore_ascii_case(heading.trim())
+        })
+    }
+
+    pub fn with_section(
+        mut self,
+        heading: &str,
+        field: &str,
+    ) -> Self {
+        self.sections
+            .push((heading.trim().to_string(), field.to_string()));
+        self
+    }
+
+    /// Headings are matched ignoring surrounding whitespace and case.
+    pub fn field_for(&self, heading: &str) -> Option<&str> {
+        let heading = heading.trim();
+        self.sections
+            .iter()
+            .find(|(section, _)| {
+                section.eq_ignore_ascii_case(heading)
+            })
+            .map(|(_, field)| field.as_str())
+    }
+
+    pub fn validate(&self, model: &Model) -> Result<(), String> {
+        let targets = std::iter::once(&self.word_field)
+            .chain(self.sections.iter().map(|(_, field)| field));
+        for target in targets {
+            if !model.fields.iter().any(|field| &field.name == target)
+            {
+                return Err(format!(
+                    "Model '{}' has no field '{}'",
+                    model.name, target
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Extracts an entry from a heading node: the heading text becomes
+    /// the word and every sub-heading is looked up in the mapping.
+    pub fn extract(
+        &self,
+        node: &Node,
+    ) -> Result<InformationContent, String> {
+        self.extract_entry(node, None, &[])
+    }
+
+    /// Extracts every entry of a document. Headings without mapped
+    /// sections group entries, their names become the tag path of the
+    /// entries below them. Entries have no GUID key, see `extract_file`.
+    pub fn extract_document(
+        &self,
+        document: &Node,
+    ) -> Result<Vec<InformationContent>, String> {
+        self.extract_entries(document, None)
+    }
+
+    /// Like `extract_document`, with the GUID keys of the entries in
+    /// `file`, so entries with the same headings in different files
+    /// stay different notes.
+    pub fn extract_file(
+        &self,
+        document: &Node,
+        file: &Path,
+    ) -> Result<Vec<InformationContent>, String> {
+        self.extract_entries(document, Some(file))
+    }
+
+    // Entries only get a GUID key when their file is known
+    fn extract_entries(
+        &self,
+        document: &Node,
+        file: Option<&Path>,
+    ) -> Result<Vec<InformationContent>, String> {
+        let mut entries = vec![];
+        self.collect_entries(
+            document.subnodes(),
+            file,
+            &mut vec![],
+            &mut entries,
+        )?;
+        Ok(entries)
+    }
+
+    fn is_entry(&self, heading: &Node) -> bool {
+        let mut sections = heading
+            .subnodes()
+            .iter()
+            .filter(|node| {
+                matches!(
+                    node.borrow().node_type(),
+                    NodeType::Heading { .. }
+                )
+            })
+            .peekable();
+        sections.peek().is_none()
+            || sections.any(|section| {
+                self.field_for(&section.borrow().plain_text())
+                    .is_some()
+            })
+    }
+
+    fn collect_entries(
+        &self,
+        nodes: &[SharedNode],
+        file: Option<&Path>,
+        path: &mut Vec<String>,
+        entries: &mut Vec<InformationContent>,
+    ) -> Result<(), String> {
+        for node in nodes {
+            let node = node.borrow();
+            if !matches!(node.node_type(), NodeType::Heading { .. }) {
+                continue;
+            }
+            if self.is_entry(&node) {
+                entries.push(self.extract_entry(&node, file, path)?);
+            } else {
+                path.push(node.plain_text().trim().to_string());
+                self.collect_entries(
+                    node.subnodes(),
+                    file,
+                    path,
+                    entries,
+                )?;
+                path.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn extract_entry(
+        &self,
+        node: &Node,
+        file: Option<&Path>,
+        path: &[String],
+    ) -> Result<InformationContent, String> {
+        if !matches!(node.node_type(), NodeType::Heading { .. }) {
+            return Err("Entry must start with a heading".to_string());
+        }
+
+        let word = node.plain_text().trim().to_string();
+        let mut fields =
+            vec![(self.word_field.clone(), node.html_text())];
+        let mut senses = vec![];
+        let mut sense_field = None;
+
+        for subnode in node.subnodes() {
+            let section = subnode.borrow();
+            if !matches!(
+                section.node_type(),
+                NodeType::Heading { .. }
+            ) {
+                continue;
+            }
+            let heading = section.plain_text();
+            let field =
+                self.field_for(&heading).ok_or_else(|| {
+                    format!(
+                        "Unmapped section '{}' in '{}'",
+                        heading.trim(),
+                        word
+                    )
+                })?;
+            let value = if self.is_senses(&heading) {
+                if !senses.is_empty() {
+                    return Err(format!(
+                        "Duplicate senses section in '{}'",
+                        word
+                    ));
+                }
+                senses =
+                    Sense::from_section(&section).map_err(|err| {
+                        format!("{} in '{}'", err, word)
+                    })?;
+                sense_field = Some(field.to_string());
+                senses_html(&senses)
+            } else {
+                section
+                    .subnodes()
+                    .iter()
+                    .map(|paragraph| paragraph.borrow().html_text())
+                    .collect::<Vec<_>>()
+                    .join("<br>")
+            };
+
+            match fields.iter_mut().find(|(name, _)| name == field) {
+                Some((_, existing)) => {
+                    existing.push_str("<br>");
+                    existing.push_str(&value);
+                }
+                None => fields.push((field.to_string(), value)),
+            }
+        }
+
+        let mut content = InformationContent {
+            word,
+            senses,
+            sense_field,
+            fields,
+            tags: vec![],
+            guid_key: None,
+        };
+        if let Some(tag) = heading_path_tag(path)? {
+            content.tags.push(tag);
+        }
+        content.take_hashtags()?;
+        // After the hashtags are gone, so retagging keeps the GUID
+        let mut headings = path.to_vec();
+        headings.push(content.word.clone());
+        content.guid_key =
+            file.map(|file| GuidKey::heading(file, &headings));
+        Ok(content)
+    }
+
+    /// Field values ordered by the ordinals of the model fields, fields
+    /// without a mapped section are left empty.
+    pub fn field_values(
+        &self,
+        content: &InformationContent,
+        model: &Model,
+    ) -> Result<Vec<String>, String> {
+        self.validate(model)?;
+        Ok(content.field_values(model))
+    }
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self::new("Front").with_senses("Meaning", "Back")
+    }
+}
+
+/// A note ready to be inserted into a collection with its cards.
+#[derive(Debug, Clone)]
+pub struct NoteCards {
+    pub note: Note,
+    pub cards: Vec<Card>,
+}
+
+impl NoteCards {
+    /// Note of `model` filled from the content, with one new card in
+    /// the model's default deck for each template ordinal. Ordinals
+    /// whose card would be empty are left out, and it is an error
+    /// when none is left.
+    pub fn new(
+        content: &InformationContent,
+        model: &Model,
+        ordinals: &[u64],
+    ) -> Result<Self, String> {
+        let mut bundle = Self::from_templates(content, model)?;
+        bundle.cards.retain(|card| ordinals.contains(&card.ordinal));
+        if bundle.cards.is_empty() {
+            return Err(format!(
+                "'{}' makes no card with model {}",
+                content.word, model.name
+            ));
+        }
+        Ok(bundle)
+    }
+
+    /// Note of `model` filled from the content, with a new card for
+    /// each card the model's templates make of it. The note has no
+    /// cards when they would all be empty.
+    pub fn from_templates(
+        content: &InformationContent,
+        model: &Model,
+    ) -> Result<Self, String> {
+        let mut note = Note::from_model(
+            model,
+            content.note_guid_key(model).guid(),
+            content.field_values(model),
+        )?;
+        note.tags = content.tags.clone();
+
+        let cards = card_ordinals(model, &note)?
+            .into_iter()
+            .map(|ordinal| {
+                Card::new(
+                    0,
+                    note.id as usize,
+                    model.default_deck_id as usize,
+                    ordinal,
+                    0,
+                    -1,
+                    CardType::New,
+                    CardQueue::New,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    CardFlag::Null,
+                )
+            })
+            .collect();
+
+        Ok(Self { note, cards })
+    }
+
+    /// Gives the note and its cards new IDs, pointing the cards at
+    /// the note.
+    pub fn assign_ids(&mut self, ids: &mut IdAllocator) {
+        self.note.id = ids.next_id();
+        for card in self.cards.iter_mut() {
+            card.id = ids.next_id();
+            card.note_id = self.note.id as usize;
+        }
+    }
 }
 
 pub trait IntoCards {
+    #[allow(clippy::wrong_self_convention)]
     fn into_cards(
         &self,
         information_content: &InformationContent,
-    ) -> Vec<Card>;
+        model: &Model,
+    ) -> Result<Vec<NoteCards>, String>;
 }
 
 pub struct Information {
@@ -23,12 +628,22 @@ pub struct Information {
 }
 
 impl Information {
-    pub fn from_ast(node: &Node) -> Self {
-        todo!()
+    pub fn from_ast(
+        node: &Node,
+        mapping: &FieldMapping,
+        information_type: Box<dyn IntoCards>,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            information_type,
+            content: mapping.extract(node)?,
+        })
     }
 
-    pub fn get_cards(&self) -> Vec<Card> {
-        self.information_type.into_cards(&self.content)
+    pub fn get_cards(
+        &self,
+        model: &Model,
+    ) -> Result<Vec<NoteCards>, String> {
+        self.information_type.into_cards(&self.content, model)
     }
 }
 
@@ -36,15 +651,297 @@ impl Information {
 mod tests {
     use super::*;
 
+    use crate::db_model::model::{
+        ModelField, ModelTemplate, ModelType,
+    };
+
+    fn parse_entry(markdown: &str) -> Node {
+        let nodes = Node::parse_nodes(
+            &mut pulldown_cmark::Parser::new(markdown),
+        )
+        .unwrap();
+        let entry = nodes[0].borrow().clone();
+        entry
+    }
+
+    fn vocabulary_model() -> Model {
+        Model::new(
+            None,
+            vec![
+                ModelField::new("Word".to_string(), 0),
+                ModelField::new("Meaning".to_string(), 1),
+                ModelField::new("Pronunciation".to_string(), 2),
+                ModelField::new("Etymology".to_string(), 3),
+            ],
+            1,
+            None,
+            None,
+            "Vocabulary".to_string(),
+            0,
+            vec![
+                ModelTemplate::new(
+                    "Recognition".to_string(),
+                    "{{Word}}".to_string(),
+                    "{{Meaning}}".to_string(),
+                ),
+                ModelTemplate::new(
+                    "Recall".to_string(),
+                    "{{Meaning}}".to_string(),
+                    "{{Word}}".to_string(),
+                ),
+                ModelTemplate::new(
+                    "Origin".to_string(),
+                    "{{#Etymology}}{{Word}}{{/Etymology}}"
+                        .to_string(),
+                    "{{Etymology}}".to_string(),
+                ),
+            ],
+            ModelType::FrontBack,
+        )
+    }
+
     #[test]
     fn test_information() {
         let simple_info = InformationContent {
             word: "hello".to_string(),
-            definitions: vec!["a greeting".to_string()],
-            examples: vec![None],
+            senses: vec![Sense::new("a greeting")],
+            ..Default::default()
         };
 
         assert_eq!(simple_info.word, "hello");
-        assert_eq!(simple_info.definitions, vec!["a greeting"])
+        assert_eq!(simple_info.senses[0].definition, "a greeting");
+        assert!(simple_info.senses[0].examples.is_empty())
+    }
+
+    #[test]
+    fn test_field_mapping_extract() {
+        let entry = parse_entry(
+            "# hello\n## Meaning\na **greeting**\n## Pronunciation\n/həˈləʊ/\n## Origin\nfrom _hollo_",
+        );
+        let mapping = FieldMapping::new("Word")
+            .with_section("Meaning", "Meaning")
+            .with_section("pronunciation", "Pronunciation")
+            .with_section("Origin", "Etymology");
+
+        let content = mapping.extract(&entry).unwrap();
+
+        assert_eq!(content.word, "hello");
+        assert_eq!(
+            content.field("Meaning"),
+            Some("a <b>greeting</b>")
+        );
+        assert_eq!(
+            mapping
+                .field_values(&content, &vocabulary_model())
+                .unwrap(),
+            vec![
+                "hello",
+                "a <b>greeting</b>",
+                "/həˈləʊ/",
+                "from <i>hollo</i>"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_field_mapping_errors() {
+        let entry = parse_entry("# hello\n## Synonyms\nhi");
+        let mapping = FieldMapping::new("Word");
+        assert!(mapping.extract(&entry).is_err());
+
+        let mapping = FieldMapping::new("Word")
+            .with_section("Synonyms", "Synonyms");
+        let content = mapping.extract(&entry).unwrap();
+        assert!(mapping
+            .field_values(&content, &vocabulary_model())
+            .is_err());
+    }
+
+    #[test]
+    fn test_note_cards() {
+        let entry = parse_entry("# hello\n## Meaning\na greeting");
+        let mapping = FieldMapping::new("Word")
+            .with_section("Meaning", "Meaning");
+        let content = mapping.extract(&entry).unwrap();
+        let mut model = vocabulary_model();
+        model.default_deck_id = 7;
+
+        let bundle =
+            NoteCards::new(&content, &model, &[0, 1]).unwrap();
+
+        assert_eq!(bundle.note.model_id, 1);
+        assert_eq!(
+            bundle.note.fields,
+            vec!["hello", "a greeting", "", ""]
+        );
+        assert_eq!(bundle.note.checksum, 0xaaf4c61d);
+        assert_eq!(bundle.note.sort_field, "hello");
+        // Without a file the model and the word identify the note
+        assert_eq!(
+            bundle.note.global_id,
+            GuidKey::explicit("Vocabulary\x1fhello").guid()
+        );
+        assert_eq!(bundle.cards.len(), 2);
+        assert_eq!(bundle.cards[1].ordinal, 1);
+        assert_eq!(bundle.cards[1].deck_id, 7);
+        // The Origin card would be empty without an etymology
+        assert_eq!(
+            NoteCards::new(&content, &model, &[0, 2])
+                .unwrap()
+                .cards
+                .len(),
+            1
+        );
+        assert!(NoteCards::new(&content, &model, &[2]).is_err());
+        assert_eq!(
+            NoteCards::from_templates(&content, &model)
+                .unwrap()
+                .cards
+                .len(),
+            2
+        );
+
+        let mut bundle = bundle;
+        bundle.assign_ids(&mut IdAllocator::starting_at(100));
+        assert_eq!(bundle.note.id, 100);
+        assert_eq!(
+            bundle
+                .cards
+                .iter()
+                .map(|card| (card.id, card.note_id))
+                .collect::<Vec<_>>(),
+            vec![(101, 100), (102, 100)]
+        );
+
+        let key = GuidKey::heading(
+            Path::new("words.md"),
+            &["hello".to_string()],
+        );
+        let keyed = InformationContent {
+            guid_key: Some(key.clone()),
+            ..content
+        };
+        let bundle = NoteCards::new(&keyed, &model, &[0]).unwrap();
+        assert_eq!(bundle.note.global_id, key.guid());
+    }
+
+    #[test]
+    fn test_extract_senses() {
+        let entry = parse_entry(
+            "# bank\n## Meaning\n1. (finance) a place that keeps money\n   - I went to the bank.\n   - The bank closed.\n2. the side of a river\n",
+        );
+        let content =
+            FieldMapping::default().extract(&entry).unwrap();
+
+        assert_eq!(
+            content.senses,
+            vec![
+                Sense::new("a place that keeps money")
+                    .with_label("finance")
+                    .with_example("I went to the bank.")
+                    .with_example("The bank closed."),
+                Sense::new("the side of a river"),
+            ]
+        );
+        assert_eq!(content.sense_field.as_deref(), Some("Back"));
+        assert_eq!(
+            content.field("Back"),
+            Some("<ol><li><i>finance</i> a place that keeps money<ul><li>I went to the bank.</li><li>The bank closed.</li></ul></li><li>the side of a river</li></ol>")
+        );
+
+        let second = content.for_sense(1).unwrap();
+        assert_eq!(second.field("Back"), Some("the side of a river"));
+        assert!(content.for_sense(2).is_none());
+    }
+
+    #[test]
+    fn test_extract_single_sense_paragraph() {
+        let entry =
+            parse_entry("# hello\n## Meaning\na **greeting**");
+        let content =
+            FieldMapping::default().extract(&entry).unwrap();
+
+        assert_eq!(
+            content.senses,
+            vec![Sense::new("a <b>greeting</b>")]
+        );
+        assert_eq!(content.field("Back"), Some("a <b>greeting</b>"));
+    }
+
+    #[test]
+    fn test_extract_invalid_senses() {
+        let invalid = [
+            "# bank\n## Meaning\n- a place\n- a river side\n",
+            "# bank\n## Meaning\n1. a place\n   1. nested\n",
+            "# bank\n## Meaning\nintro\n1. a place\n",
+            "# bank\n## Meaning\n1. (finance)\n",
+        ];
+        for markdown in invalid {
+            let entry = parse_entry(markdown);
+            assert!(
+                FieldMapping::default().extract(&entry).is_err(),
+                "{markdown:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_document_tags() {
+        let markdown = "# Verbs\n## Irregular verbs\n### go #motion\n#### Meaning\nto move #common\n### be\n# hello\n## Meaning\na greeting #informal #Common\n";
+        let document = Node::parse_document(
+            &mut pulldown_cmark::Parser::new(markdown),
+        )
+        .unwrap();
+
+        let entries = FieldMapping::default()
+            .extract_document(&document)
+            .unwrap();
+
+        let tags = |entry: &InformationContent| {
+            entry
+                .tags
+                .iter()
+                .map(|tag| tag.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].word, "go");
+        assert_eq!(entries[0].field("Front"), Some("go"));
+        assert_eq!(entries[0].field("Back"), Some("to move"));
+        assert_eq!(
+            tags(&entries[0]),
+            vec!["Verbs::Irregular_verbs", "motion", "common"]
+        );
+        assert_eq!(entries[1].word, "be");
+        assert_eq!(tags(&entries[1]), vec!["Verbs::Irregular_verbs"]);
+        assert_eq!(entries[2].senses, vec![Sense::new("a greeting")]);
+        assert_eq!(tags(&entries[2]), vec!["informal", "Common"]);
+    }
+
+    #[test]
+    fn test_extract_file_guid_keys() {
+        let markdown =
+            "# Verbs\n## go #motion\n### Meaning\nto move\n";
+        let document = Node::parse_document(
+            &mut pulldown_cmark::Parser::new(markdown),
+        )
+        .unwrap();
+        let mapping = FieldMapping::default();
+
+        let entries = mapping
+            .extract_file(&document, Path::new("verbs.md"))
+            .unwrap();
+
+        let headings = vec!["Verbs".to_string(), "go".to_string()];
+        assert_eq!(
+            entries[0].guid_key,
+            Some(GuidKey::heading(Path::new("verbs.md"), &headings))
+        );
+        let other = mapping
+            .extract_file(&document, Path::new("other.md"))
+            .unwrap();
+        assert_ne!(entries[0].guid_key, other[0].guid_key);
+        let unkeyed = mapping.extract_document(&document).unwrap();
+        assert_eq!(unkeyed[0].guid_key, None);
     }
 }
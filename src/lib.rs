@@ -1,5 +1,10 @@
 pub mod db_model;
 pub mod deck;
+pub mod generator;
+pub mod guid;
 pub mod information;
 pub mod markdown;
+pub mod package;
+pub mod tags;
+pub mod template;
 pub mod util;
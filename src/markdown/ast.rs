@@ -1,4 +1,6 @@
-use std::{cell::RefCell, rc::Rc, usize};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::util::escape_html;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Text {
@@ -30,6 +32,32 @@ impl Text {
             Self::HardBrake => "\\\n".to_string(),
         }
     }
+
+    pub fn to_plain(&self) -> String {
+        match self {
+            Self::Plain(txt)
+            | Self::Italic(txt)
+            | Self::Bold(txt)
+            | Self::Strikethrough(txt) => txt.to_string(),
+            Self::SoftBrake => " ".to_string(),
+            Self::HardBrake => "\n".to_string(),
+        }
+    }
+
+    pub fn to_html(&self) -> String {
+        match self {
+            Self::Plain(txt) => escape_html(txt),
+            Self::Italic(txt) => {
+                format!("<i>{}</i>", escape_html(txt))
+            }
+            Self::Bold(txt) => format!("<b>{}</b>", escape_html(txt)),
+            Self::Strikethrough(txt) => {
+                format!("<s>{}</s>", escape_html(txt))
+            }
+            Self::SoftBrake => " ".to_string(),
+            Self::HardBrake => "<br>".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -38,6 +66,8 @@ pub enum NodeType {
     Text(Text),
     Paragraph,
     Heading { level: usize, content: Vec<Text> },
+    List { ordered: bool },
+    Item,
 }
 
 #[derive(Debug, Clone)]
@@ -46,7 +76,7 @@ pub struct Node {
     subnodes: Vec<Rc<RefCell<Node>>>,
 }
 
-type SharedNode = Rc<RefCell<Node>>;
+pub type SharedNode = Rc<RefCell<Node>>;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Tag {
@@ -55,6 +85,8 @@ enum Tag {
     Strikethrough,
     Paragraph,
     Heading(usize),
+    List(bool),
+    Item,
 }
 
 impl Tag {
@@ -67,6 +99,10 @@ impl Tag {
                 Self::Heading(level as usize)
             }
             pulldown_cmark::Tag::Paragraph => Self::Paragraph,
+            pulldown_cmark::Tag::List(start) => {
+                Self::List(start.is_some())
+            }
+            pulldown_cmark::Tag::Item => Self::Item,
             _ => todo!(),
         }
     }
@@ -82,12 +118,64 @@ impl Tag {
                 Self::Heading(level as usize)
             }
             pulldown_cmark::TagEnd::Paragraph => Self::Paragraph,
+            pulldown_cmark::TagEnd::List(ordered) => {
+                Self::List(ordered)
+            }
+            pulldown_cmark::TagEnd::Item => Self::Item,
             _ => todo!(),
         }
     }
 }
 
 impl Node {
+    pub fn node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+
+    pub fn subnodes(&self) -> &[SharedNode] {
+        &self.subnodes
+    }
+
+    /// Plain text of a heading, or of the text nodes below the node.
+    pub fn plain_text(&self) -> String {
+        match &self.node_type {
+            NodeType::Text(txt) => txt.to_plain(),
+            NodeType::Heading { content, .. } => {
+                content.iter().map(Text::to_plain).collect()
+            }
+            _ => self
+                .subnodes
+                .iter()
+                .map(|node| node.borrow().plain_text())
+                .collect(),
+        }
+    }
+
+    /// HTML of a heading, or of the text nodes below the node.
+    pub fn html_text(&self) -> String {
+        match &self.node_type {
+            NodeType::Text(txt) => txt.to_html(),
+            NodeType::Heading { content, .. } => {
+                content.iter().map(Text::to_html).collect()
+            }
+            NodeType::List { ordered } => {
+                let tag = if *ordered { "ol" } else { "ul" };
+                format!("<{tag}>{}</{tag}>", self.subnodes_html())
+            }
+            NodeType::Item => {
+                format!("<li>{}</li>", self.subnodes_html())
+            }
+            _ => self.subnodes_html(),
+        }
+    }
+
+    pub fn subnodes_html(&self) -> String {
+        self.subnodes
+            .iter()
+            .map(|node| node.borrow().html_text())
+            .collect()
+    }
+
     fn parse_text_event(
         events: &mut dyn Iterator<Item = pulldown_cmark::Event>,
         tag: Tag,
@@ -200,6 +288,53 @@ impl Node {
         })
     }
 
+    // Lists and items nest, so their end is found by counting the
+    // matching start and end events
+    fn parse_container(
+        events: &mut dyn Iterator<Item = pulldown_cmark::Event>,
+        tag: Tag,
+    ) -> Result<Self, &'static str> {
+        let mut depth = 0;
+        let inner_events = events
+            .take_while(|event| match event {
+                pulldown_cmark::Event::Start(start) => {
+                    if Tag::from_start(start.clone()) == tag {
+                        depth += 1;
+                    }
+                    true
+                }
+                pulldown_cmark::Event::End(tag_end) => {
+                    if Tag::from_end(*tag_end) != tag {
+                        return true;
+                    }
+                    depth -= 1;
+                    depth >= 0
+                }
+                _ => true,
+            })
+            .collect::<Vec<_>>();
+        let subnodes =
+            Self::parse_nodes(&mut inner_events.into_iter())?;
+
+        let node_type = match tag {
+            Tag::List(ordered) => {
+                if subnodes.iter().any(|node| {
+                    node.borrow().node_type != NodeType::Item
+                }) {
+                    return Err("Non item node was found in a list");
+                }
+                NodeType::List { ordered }
+            }
+            Tag::Item => NodeType::Item,
+            _ => return Err("Not a container tag"),
+        };
+
+        Ok(Self {
+            node_type,
+            subnodes,
+        })
+    }
+
     fn parse_tag(
         events: &mut dyn Iterator<Item = pulldown_cmark::Event>,
         tag: Tag,
@@ -210,6 +345,9 @@ impl Node {
             }
             Tag::Paragraph => Self::parse_paragraph(events),
             Tag::Heading(_) => Self::parse_heading(events, tag),
+            Tag::List(_) | Tag::Item => {
+                Self::parse_container(events, tag)
+            }
         }
     }
 
@@ -240,7 +378,7 @@ impl Node {
             }
         }
 
-        if open_headings.len() == 0 {
+        if open_headings.is_empty() {
             nodes.push(rc_node);
         } else {
             open_headings
@@ -252,7 +390,7 @@ impl Node {
         };
 
         if push_level.is_some() {
-            if open_headings.len() == 0 {
+            if open_headings.is_empty() {
                 open_headings.push(
                     nodes.last().ok_or("Should have pushed")?.clone(),
                 );
@@ -281,6 +419,22 @@ impl Node {
 
         while let Some(event) = events.next() {
             let node = match event {
+                // front matter is read separately, see util::front_matter
+                pulldown_cmark::Event::Start(
+                    pulldown_cmark::Tag::MetadataBlock(_),
+                ) => {
+                    events
+                        .take_while(|event| {
+                            !matches!(
+                                event,
+                                pulldown_cmark::Event::End(
+                                    pulldown_cmark::TagEnd::MetadataBlock(_)
+                                )
+                            )
+                        })
+                        .for_each(drop);
+                    continue;
+                }
                 pulldown_cmark::Event::Start(tag) => {
                     Self::parse_tag(events, Tag::from_start(tag))?
                 }
@@ -290,6 +444,14 @@ impl Node {
                     )),
                     subnodes: vec![],
                 },
+                pulldown_cmark::Event::SoftBreak => Self {
+                    node_type: NodeType::Text(Text::SoftBrake),
+                    subnodes: vec![],
+                },
+                pulldown_cmark::Event::HardBreak => Self {
+                    node_type: NodeType::Text(Text::HardBrake),
+                    subnodes: vec![],
+                },
                 _ => todo!(),
             };
             (nodes, open_headings) =
@@ -344,4 +506,49 @@ mod tests {
 
         assert_eq!(paragraph.subnodes.len(), 5);
     }
+
+    #[test]
+    fn test_parse_skips_front_matter() {
+        let markdown = "---\ngenerator: cloze\n---\n# Heading\n";
+        let parser = pulldown_cmark::Parser::new_ext(
+            markdown,
+            pulldown_cmark::Options::ENABLE_YAML_STYLE_METADATA_BLOCKS,
+        );
+
+        let nodes =
+            Node::parse_nodes(&mut parser.into_iter()).unwrap();
+
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].borrow().plain_text(), "Heading");
+    }
+
+    #[test]
+    fn test_parse_lists() {
+        let markdown = "# hello\n1. a greeting\n   - hello there\n   - hello you\n2. a call\n";
+        let parser = pulldown_cmark::Parser::new(markdown);
+
+        let nodes =
+            Node::parse_nodes(&mut parser.into_iter()).unwrap();
+
+        let heading = nodes[0].borrow();
+        assert_eq!(heading.subnodes.len(), 1);
+        let list = heading.subnodes[0].borrow();
+        assert_eq!(list.node_type, NodeType::List { ordered: true });
+        assert_eq!(list.subnodes.len(), 2);
+
+        let item = list.subnodes[0].borrow();
+        assert_eq!(item.node_type, NodeType::Item);
+        assert_eq!(item.subnodes.len(), 2);
+        let examples = item.subnodes[1].borrow();
+        assert_eq!(
+            examples.node_type,
+            NodeType::List { ordered: false }
+        );
+        assert_eq!(examples.subnodes.len(), 2);
+        assert_eq!(examples.plain_text(), "hello therehello you");
+        assert_eq!(
+            examples.html_text(),
+            "<ul><li>hello there</li><li>hello you</li></ul>"
+        );
+    }
 }
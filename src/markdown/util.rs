@@ -1,4 +1,6 @@
-use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use pulldown_cmark::{
+    Event, MetadataBlockKind, Options, Parser, Tag, TagEnd,
+};
 
 pub fn log_markdown_events(events: &mut dyn Iterator<Item = Event>) {
     let mut width = 0;
@@ -28,7 +30,35 @@ pub fn check_matching_tags(tag: &Tag, tag_end: &TagEnd) -> bool {
         _ => todo!(),
     };
 
-    expected_end == tag_end.clone()
+    expected_end == *tag_end
+}
+
+/// `key: value` pairs of a leading `---` delimited front matter block.
+pub fn front_matter(text: &str) -> Vec<(String, String)> {
+    let mut events = Parser::new_ext(
+        text,
+        Options::ENABLE_YAML_STYLE_METADATA_BLOCKS,
+    );
+    match events.next() {
+        Some(Event::Start(Tag::MetadataBlock(
+            MetadataBlockKind::YamlStyle,
+        ))) => (),
+        _ => return vec![],
+    }
+
+    events
+        .take_while(|event| !matches!(event, Event::End(_)))
+        .filter_map(|event| match event {
+            Event::Text(txt) => Some(txt.to_string()),
+            _ => None,
+        })
+        .collect::<String>()
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| {
+            (key.trim().to_string(), value.trim().to_string())
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -40,4 +70,18 @@ mod tests {
         let text = "# Hello\n## World";
         log_markdown_str(text);
     }
+
+    #[test]
+    fn test_front_matter() {
+        let text =
+            "---\ngenerator: reversed\ndeck: Spanish\n---\n# hola";
+        assert_eq!(
+            front_matter(text),
+            vec![
+                ("generator".to_string(), "reversed".to_string()),
+                ("deck".to_string(), "Spanish".to_string()),
+            ]
+        );
+        assert!(front_matter("# hola").is_empty());
+    }
 }
@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use prost::Message;
+use sha1::{Digest, Sha1};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::db_model::card::Card;
+use crate::db_model::collection::Collection;
+use crate::db_model::database::{
+    read_collection, read_review_logs, write_collection_version,
+    write_review_logs, SchemaVersion,
+};
+use crate::db_model::note::Note;
+use crate::db_model::revlog::ReviewLog;
+
+pub const COLLECTION_FILE: &str = "collection.anki2";
+// Written next to a placeholder collection.anki2 by Anki 2.1 exports
+pub const COLLECTION_21_FILE: &str = "collection.anki21";
+// Zstd compressed schema 18 database of the latest package format
+pub const COLLECTION_21B_FILE: &str = "collection.anki21b";
+pub const MEDIA_FILE: &str = "media";
+pub const META_FILE: &str = "meta";
+
+// PackageMetadata.Version of Anki's import_export.proto
+const LEGACY_VERSION: i32 = 1;
+const LATEST_VERSION: i32 = 3;
+
+/// Layout of an exported package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PackageFormat {
+    /// Schema 11 `collection.anki2` and a JSON media map, readable by
+    /// every Anki version.
+    #[default]
+    Legacy,
+    /// Zstd compressed schema 18 `collection.anki21b`, a protobuf media
+    /// map and compressed media, as exported by Anki 2.1.50 and later.
+    Latest,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PackageMetadata {
+    #[prost(int32, tag = "1")]
+    version: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MediaEntries {
+    #[prost(message, repeated, tag = "1")]
+    entries: Vec<MediaEntry>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MediaEntry {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(uint32, tag = "2")]
+    size: u32,
+    #[prost(bytes = "vec", tag = "3")]
+    sha1: Vec<u8>,
+}
+
+/// A file referenced by name from note fields, e.g. `<img src="a.png">`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl MediaFile {
+    pub fn new(name: &str, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            data,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| {
+                format!("Invalid media path {}", path.display())
+            })?;
+        let data = fs::read(path).map_err(|err| err.to_string())?;
+        Ok(Self::new(name, data))
+    }
+}
+
+/// Everything stored in an `.apkg` file.
+#[derive(Debug, Clone, Default)]
+pub struct Package {
+    pub collection: Collection,
+    pub notes: Vec<Note>,
+    pub cards: Vec<Card>,
+    pub review_logs: Vec<ReviewLog>,
+    pub media: Vec<MediaFile>,
+}
+
+impl Package {
+    pub fn new(
+        collection: Collection,
+        notes: Vec<Note>,
+        cards: Vec<Card>,
+    ) -> Self {
+        Self {
+            collection,
+            notes,
+            cards,
+            review_logs: vec![],
+            media: vec![],
+        }
+    }
+
+    // Contents of the media entry
+    fn media_map(&self) -> Result<String, String> {
+        for (index, media) in self.media.iter().enumerate() {
+            if self.media[..index]
+                .iter()
+                .any(|other| other.name == media.name)
+            {
+                return Err(format!(
+                    "Duplicate media file {}",
+                    media.name
+                ));
+            }
+        }
+        serde_json::to_string(&self.media_names())
+            .map_err(|err| err.to_string())
+    }
+
+    /// Reads an `.apkg` or `.colpkg` file in either format. Legacy
+    /// packages exported by Anki 2.1 have their collection in
+    /// `collection.anki21`, preferred over the placeholder
+    /// `collection.anki2`.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| err.to_string())?;
+        let mut zip =
+            ZipArchive::new(file).map_err(|err| err.to_string())?;
+
+        let version = if zip.index_for_name(META_FILE).is_some() {
+            PackageMetadata::decode(
+                read_entry(&mut zip, META_FILE)?.as_slice(),
+            )
+            .map_err(|err| err.to_string())?
+            .version
+        } else {
+            LEGACY_VERSION
+        };
+        let format = if version >= LATEST_VERSION {
+            PackageFormat::Latest
+        } else {
+            PackageFormat::Legacy
+        };
+
+        let collection_data = match format {
+            PackageFormat::Latest => decompress(&read_entry(
+                &mut zip,
+                COLLECTION_21B_FILE,
+            )?)?,
+            PackageFormat::Legacy
+                if zip
+                    .index_for_name(COLLECTION_21_FILE)
+                    .is_some() =>
+            {
+                read_entry(&mut zip, COLLECTION_21_FILE)?
+            }
+            PackageFormat::Legacy => {
+                read_entry(&mut zip, COLLECTION_FILE)?
+            }
+        };
+        let dir =
+            tempfile::tempdir().map_err(|err| err.to_string())?;
+        let collection_path = dir.path().join(COLLECTION_FILE);
+        fs::write(&collection_path, collection_data)
+            .map_err(|err| err.to_string())?;
+        let (collection, notes, cards) =
+            read_collection(&collection_path)?;
+        let review_logs = read_review_logs(&collection_path)?;
+
+        let media = if zip.index_for_name(MEDIA_FILE).is_none() {
+            vec![]
+        } else {
+            match format {
+                PackageFormat::Legacy => read_legacy_media(&mut zip)?,
+                PackageFormat::Latest => read_latest_media(&mut zip)?,
+            }
+        };
+
+        Ok(Self {
+            collection,
+            notes,
+            cards,
+            review_logs,
+            media,
+        })
+    }
+
+    /// Media map of the package, numbered zip entry name to file name.
+    pub fn media_names(&self) -> BTreeMap<String, String> {
+        self.media
+            .iter()
+            .enumerate()
+            .map(|(index, media)| {
+                (index.to_string(), media.name.clone())
+            })
+            .collect()
+    }
+
+    pub fn media_file(&self, name: &str) -> Option<&MediaFile> {
+        self.media.iter().find(|media| media.name == name)
+    }
+
+    /// Writes the package as an `.apkg` zip: the collection database,
+    /// the `media` JSON map and the media files named by their index.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        self.write_format(path, PackageFormat::Legacy)
+    }
+
+    /// Writes the package as an `.apkg` zip in the given format.
+    pub fn write_format(
+        &self,
+        path: &Path,
+        format: PackageFormat,
+    ) -> Result<(), String> {
+        let media_map = self.media_map()?;
+
+        let mut entries = vec![];
+        match format {
+            PackageFormat::Legacy => {
+                entries.push((
+                    COLLECTION_FILE.to_string(),
+                    self.collection_data(SchemaVersion::V11)?,
+                ));
+                entries.push((
+                    MEDIA_FILE.to_string(),
+                    media_map.into_bytes(),
+                ));
+                for (index, media) in self.media.iter().enumerate() {
+                    entries.push((
+                        index.to_string(),
+                        media.data.clone(),
+                    ));
+                }
+            }
+            PackageFormat::Latest => {
+                // Older Anki versions only look at collection.anki2,
+                // an empty one keeps them from importing anything
+                let placeholder =
+                    Package::new(Collection::new(), vec![], vec![]);
+                entries.push((
+                    COLLECTION_FILE.to_string(),
+                    placeholder
+                        .collection_data(SchemaVersion::V11)?,
+                ));
+                entries.push((
+                    COLLECTION_21B_FILE.to_string(),
+                    compress(
+                        &self.collection_data(SchemaVersion::V18)?,
+                    )?,
+                ));
+                let metadata = PackageMetadata {
+                    version: LATEST_VERSION,
+                };
+                entries.push((
+                    META_FILE.to_string(),
+                    metadata.encode_to_vec(),
+                ));
+                entries.push((
+                    MEDIA_FILE.to_string(),
+                    compress(&self.media_entries().encode_to_vec())?,
+                ));
+                for (index, media) in self.media.iter().enumerate() {
+                    entries.push((
+                        index.to_string(),
+                        compress(&media.data)?,
+                    ));
+                }
+            }
+        }
+
+        let file =
+            File::create(path).map_err(|err| err.to_string())?;
+        let mut zip = ZipWriter::new(file);
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated);
+        for (name, data) in entries {
+            zip.start_file(name, options)
+                .map_err(|err| err.to_string())?;
+            zip.write_all(&data).map_err(|err| err.to_string())?;
+        }
+        zip.finish().map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    /// Writes the package as a `.colpkg`, a whole collection backup,
+    /// which Anki only accepts in the latest format.
+    pub fn write_colpkg(&self, path: &Path) -> Result<(), String> {
+        self.write_format(path, PackageFormat::Latest)
+    }
+
+    // Collection database file contents in the given schema
+    fn collection_data(
+        &self,
+        version: SchemaVersion,
+    ) -> Result<Vec<u8>, String> {
+        let dir =
+            tempfile::tempdir().map_err(|err| err.to_string())?;
+        let collection_path = dir.path().join(COLLECTION_FILE);
+        write_collection_version(
+            &collection_path,
+            &self.collection,
+            &self.notes,
+            &self.cards,
+            version,
+        )?;
+        write_review_logs(&collection_path, &self.review_logs)?;
+        fs::read(&collection_path).map_err(|err| err.to_string())
+    }
+
+    fn media_entries(&self) -> MediaEntries {
+        MediaEntries {
+            entries: self
+                .media
+                .iter()
+                .map(|media| MediaEntry {
+                    name: media.name.clone(),
+                    size: media.data.len() as u32,
+                    sha1: Sha1::digest(&media.data).to_vec(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>, String> {
+    zstd::encode_all(data, 0).map_err(|err| err.to_string())
+}
+
+fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    zstd::decode_all(data).map_err(|err| err.to_string())
+}
+
+// Media of the JSON map, numbered zip entry name to file name
+fn read_legacy_media(
+    zip: &mut ZipArchive<File>,
+) -> Result<Vec<MediaFile>, String> {
+    let media_map: BTreeMap<String, String> =
+        serde_json::from_slice(&read_entry(zip, MEDIA_FILE)?)
+            .map_err(|err| err.to_string())?;
+    let mut media = media_map
+        .into_iter()
+        .map(|(entry, name)| {
+            let index = entry
+                .parse::<usize>()
+                .map_err(|err| err.to_string())?;
+            Ok((
+                index,
+                MediaFile::new(&name, read_entry(zip, &entry)?),
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    media.sort_by_key(|(index, _)| *index);
+    Ok(media.into_iter().map(|(_, media)| media).collect())
+}
+
+// Media of the protobuf list, whose position is the zip entry name
+fn read_latest_media(
+    zip: &mut ZipArchive<File>,
+) -> Result<Vec<MediaFile>, String> {
+    let entries = MediaEntries::decode(
+        decompress(&read_entry(zip, MEDIA_FILE)?)?.as_slice(),
+    )
+    .map_err(|err| err.to_string())?;
+    entries
+        .entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let data =
+                decompress(&read_entry(zip, &index.to_string())?)?;
+            if data.len() != entry.size as usize {
+                return Err(format!(
+                    "Media file {} has {} bytes, expected {}",
+                    entry.name,
+                    data.len(),
+                    entry.size
+                ));
+            }
+            Ok(MediaFile::new(&entry.name, data))
+        })
+        .collect()
+}
+
+fn read_entry(
+    zip: &mut ZipArchive<File>,
+    name: &str,
+) -> Result<Vec<u8>, String> {
+    let mut entry = zip
+        .by_name(name)
+        .map_err(|err| format!("{}: {}", name, err))?;
+    let mut data = vec![];
+    entry
+        .read_to_end(&mut data)
+        .map_err(|err| err.to_string())?;
+    Ok(data)
+}
@@ -0,0 +1,146 @@
+use crate::db_model::note::NoteTag;
+
+/// Hierarchical tag for the headings above an entry, joined with "::"
+/// the way Anki nests tags, e.g. `Verbs::Irregular`.
+pub fn heading_path_tag(
+    path: &[String],
+) -> Result<Option<NoteTag>, String> {
+    let components = path
+        .iter()
+        .map(|heading| NoteTag::repair(heading).map(|tag| tag.name))
+        .collect::<Result<Vec<_>, _>>()?;
+    if components.is_empty() {
+        return Ok(None);
+    }
+    NoteTag::repair(&components.join("::")).map(Some)
+}
+
+fn is_tag_char(c: char) -> bool {
+    !c.is_whitespace()
+        && !matches!(c, '#' | '<' | '>' | ',' | '.' | ';')
+}
+
+// Elements that can wrap a hashtag inside a line of text
+const INLINE_ELEMENTS: [&str; 13] = [
+    "a", "b", "code", "del", "em", "i", "ins", "mark", "s", "span",
+    "strong", "sub", "u",
+];
+
+// Start and name of the inline element opened at the end of the HTML
+fn trailing_inline_element(html: &str) -> Option<(usize, &str)> {
+    let opening = html.strip_suffix('>')?;
+    let start = opening.rfind('<')?;
+    let name =
+        opening[start + 1..].split(char::is_whitespace).next()?;
+    INLINE_ELEMENTS
+        .iter()
+        .any(|inline| inline.eq_ignore_ascii_case(name))
+        .then_some((start, name))
+}
+
+/// Removes `#hashtags` from the text, returning the text without them
+/// and the hashtag names. Only a `#` at the start of the text, after
+/// whitespace or after an HTML tag starts a hashtag, so HTML entities
+/// are left alone. A hashtag goes with one of the separators around
+/// it and with the inline elements it leaves empty, the rest of the
+/// text is kept as is.
+pub fn strip_hashtags(text: &str) -> (String, Vec<String>) {
+    let mut stripped = String::with_capacity(text.len());
+    let mut hashtags = vec![];
+    let mut position = 0;
+    let mut at_word_start = true;
+
+    while let Some(c) = text[position..].chars().next() {
+        let rest = &text[position + c.len_utf8()..];
+        let length = rest
+            .find(|next| !is_tag_char(next))
+            .unwrap_or(rest.len());
+        if c != '#' || !at_word_start || length == 0 {
+            stripped.push(c);
+            at_word_start = c.is_whitespace() || c == '>';
+            position += c.len_utf8();
+            continue;
+        }
+        hashtags.push(rest[..length].to_string());
+        position += c.len_utf8() + length;
+
+        while let Some((start, name)) =
+            trailing_inline_element(&stripped)
+        {
+            let closing = format!("</{}>", name);
+            if !text[position..].starts_with(&closing) {
+                break;
+            }
+            stripped.truncate(start);
+            position += closing.len();
+        }
+        match text[position..].chars().next() {
+            Some(next) if next.is_whitespace() => {
+                position += next.len_utf8();
+            }
+            _ => {
+                if stripped.ends_with(char::is_whitespace) {
+                    stripped.pop();
+                }
+            }
+        }
+        at_word_start = true;
+    }
+
+    (stripped, hashtags)
+}
+
+/// Adds the tag unless a tag with the same name, ignoring case, is
+/// already present.
+pub fn push_tag(tags: &mut Vec<NoteTag>, tag: NoteTag) {
+    if !tags
+        .iter()
+        .any(|existing| existing.name.eq_ignore_ascii_case(&tag.name))
+    {
+        tags.push(tag);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heading_path_tag() {
+        let path =
+            vec!["Verbs".to_string(), "Irregular  verbs".to_string()];
+        assert_eq!(
+            heading_path_tag(&path).unwrap(),
+            Some(NoteTag::new("Verbs::Irregular_verbs").unwrap())
+        );
+        assert_eq!(heading_path_tag(&[]).unwrap(), None);
+        assert!(heading_path_tag(&[" ".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_strip_hashtags() {
+        let (text, hashtags) = strip_hashtags(
+            "#greeting used in <i>#informal</i> speech &#39;",
+        );
+        assert_eq!(text, "used in speech &#39;");
+        assert_eq!(hashtags, vec!["greeting", "informal"]);
+
+        let (text, hashtags) = strip_hashtags(
+            "<pre>a  b</pre>\n  <b><i>#x</i></b> keeps  spaces #y",
+        );
+        assert_eq!(text, "<pre>a  b</pre>\n  keeps  spaces");
+        assert_eq!(hashtags, vec!["x", "y"]);
+
+        let (text, hashtags) = strip_hashtags("# not a tag #");
+        assert_eq!(text, "# not a tag #");
+        assert!(hashtags.is_empty());
+    }
+
+    #[test]
+    fn test_push_tag() {
+        let mut tags = vec![NoteTag::new("Verbs").unwrap()];
+        push_tag(&mut tags, NoteTag::new("verbs").unwrap());
+        push_tag(&mut tags, NoteTag::new("nouns").unwrap());
+        assert_eq!(tags.len(), 2);
+    }
+}
@@ -0,0 +1,1168 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::db_model::model::{Model, ModelType};
+use crate::db_model::note::Note;
+use crate::util::{escape_html, field_checksum, strip_html};
+
+/// Field of the answer template holding the rendered question.
+pub const FRONT_SIDE: &str = "FrontSide";
+
+const TYPE_ANSWER_PREFIX: &str = "[[type:";
+const CLOZE_PREFIX: &str = "{{c";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CardSide {
+    Question,
+    Answer,
+}
+
+/// A template parsed into text, replacements and conditional sections.
+#[derive(Debug, Clone, PartialEq)]
+enum TemplateNode {
+    Text(String),
+    // {{filter:...:Field}}, filters are applied right to left
+    Replacement {
+        field: String,
+        filters: Vec<String>,
+    },
+    // {{#Field}}...{{/Field}} or, negated, {{^Field}}...{{/Field}}
+    Conditional {
+        field: String,
+        negated: bool,
+        children: Vec<TemplateNode>,
+    },
+}
+
+// Field and negation of an open conditional, None for the template
+// itself, with the nodes so far
+type Section = (Option<(String, bool)>, Vec<TemplateNode>);
+
+fn parse_template(
+    template: &str,
+) -> Result<Vec<TemplateNode>, String> {
+    // Open sections, the outermost is the template itself
+    let mut sections: Vec<Section> = vec![(None, vec![])];
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .ok_or_else(|| {
+                format!("Unclosed '{{{{' in '{}'", template)
+            })?;
+        if start > 0 {
+            sections
+                .last_mut()
+                .unwrap()
+                .1
+                .push(TemplateNode::Text(rest[..start].to_string()));
+        }
+        let tag = rest[start + 2..end].trim();
+        rest = &rest[end + 2..];
+
+        if let Some(field) = tag.strip_prefix('#') {
+            sections.push((
+                Some((field.trim().to_string(), false)),
+                vec![],
+            ));
+        } else if let Some(field) = tag.strip_prefix('^') {
+            sections.push((
+                Some((field.trim().to_string(), true)),
+                vec![],
+            ));
+        } else if let Some(field) = tag.strip_prefix('/') {
+            let field = field.trim();
+            let (open, children) = sections.pop().unwrap();
+            let Some((open, negated)) = open else {
+                return Err(format!(
+                    "'{{{{/{}}}}}' was never opened",
+                    field
+                ));
+            };
+            if open != field {
+                return Err(format!(
+                    "'{{{{/{}}}}}' closes '{}' instead",
+                    field, open
+                ));
+            }
+            sections.last_mut().unwrap().1.push(
+                TemplateNode::Conditional {
+                    field: open,
+                    negated,
+                    children,
+                },
+            );
+        } else {
+            let mut parts = tag
+                .split(':')
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            let field =
+                parts.pop().unwrap_or_default().trim().to_string();
+            sections.last_mut().unwrap().1.push(
+                TemplateNode::Replacement {
+                    field,
+                    filters: parts,
+                },
+            );
+        }
+    }
+    if !rest.is_empty() {
+        sections
+            .last_mut()
+            .unwrap()
+            .1
+            .push(TemplateNode::Text(rest.to_string()));
+    }
+
+    let (open, nodes) = sections.pop().unwrap();
+    match open {
+        Some((field, _)) => {
+            Err(format!("'{{{{#{}}}}}' is never closed", field))
+        }
+        None => Ok(nodes),
+    }
+}
+
+/// Anki treats fields with only whitespace or markup as empty.
+fn is_field_empty(value: &str) -> bool {
+    strip_html(value).trim().is_empty()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClozeNode {
+    Text(String),
+    // {{c<number>::text::hint}}
+    Deletion {
+        number: u64,
+        children: Vec<ClozeNode>,
+        hint: Option<String>,
+    },
+}
+
+// Deletions may nest, e.g. `{{c1::uno {{c2::dos}}}}`
+fn parse_cloze(text: &str) -> Vec<ClozeNode> {
+    struct Open {
+        number: u64,
+        children: Vec<ClozeNode>,
+        hint: Option<String>,
+    }
+    fn push_text(nodes: &mut Vec<ClozeNode>, text: &str) {
+        match nodes.last_mut() {
+            Some(ClozeNode::Text(last)) => last.push_str(text),
+            _ => nodes.push(ClozeNode::Text(text.to_string())),
+        }
+    }
+
+    let mut root = vec![];
+    let mut open: Vec<Open> = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let opening =
+            rest.strip_prefix(CLOZE_PREFIX).and_then(|after| {
+                let digits = after
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(after.len());
+                let number = after[..digits].parse::<u64>().ok()?;
+                after[digits..]
+                    .strip_prefix("::")
+                    .map(|after| (number, after))
+            });
+        if let Some((number, after)) = opening {
+            open.push(Open {
+                number,
+                children: vec![],
+                hint: None,
+            });
+            rest = after;
+            continue;
+        }
+        if let Some(current) = open.last_mut() {
+            if let Some(after) = rest.strip_prefix("}}") {
+                let closed = open.pop().unwrap();
+                let node = ClozeNode::Deletion {
+                    number: closed.number,
+                    children: closed.children,
+                    hint: closed.hint,
+                };
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => root.push(node),
+                }
+                rest = after;
+                continue;
+            }
+            if current.hint.is_none() {
+                if let Some(after) = rest.strip_prefix("::") {
+                    current.hint = Some(String::new());
+                    rest = after;
+                    continue;
+                }
+            }
+            match current.hint.as_mut() {
+                Some(hint) => hint.push(c),
+                None => {
+                    push_text(&mut current.children, &c.to_string())
+                }
+            }
+        } else {
+            push_text(&mut root, &c.to_string());
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    // Unclosed deletions are left as they were written
+    for unclosed in open {
+        let mut text =
+            format!("{}{}::", CLOZE_PREFIX, unclosed.number);
+        text.push_str(&render_cloze_nodes(
+            &unclosed.children,
+            0,
+            CardSide::Answer,
+        ));
+        if let Some(hint) = unclosed.hint {
+            text.push_str("::");
+            text.push_str(&hint);
+        }
+        push_text(&mut root, &text);
+    }
+    root
+}
+
+fn render_cloze_nodes(
+    nodes: &[ClozeNode],
+    number: u64,
+    side: CardSide,
+) -> String {
+    nodes
+        .iter()
+        .map(|node| match node {
+            ClozeNode::Text(text) => text.clone(),
+            ClozeNode::Deletion {
+                number: deletion,
+                children,
+                hint,
+            } if *deletion == number => match side {
+                CardSide::Question => format!(
+                    "<span class=\"cloze\" data-ordinal=\"{}\">[{}]</span>",
+                    deletion,
+                    hint.as_deref().unwrap_or("...")
+                ),
+                CardSide::Answer => format!(
+                    "<span class=\"cloze\" data-ordinal=\"{}\">{}</span>",
+                    deletion,
+                    render_cloze_nodes(children, number, side)
+                ),
+            },
+            ClozeNode::Deletion {
+                number: deletion,
+                children,
+                ..
+            } => format!(
+                "<span class=\"cloze-inactive\" data-ordinal=\"{}\">{}</span>",
+                deletion,
+                render_cloze_nodes(children, number, side)
+            ),
+        })
+        .collect()
+}
+
+fn cloze_answers(
+    nodes: &[ClozeNode],
+    number: u64,
+    answers: &mut Vec<String>,
+) {
+    for node in nodes {
+        if let ClozeNode::Deletion {
+            number: deletion,
+            children,
+            ..
+        } = node
+        {
+            if *deletion == number {
+                answers.push(strip_html(&render_cloze_nodes(
+                    children,
+                    number,
+                    CardSide::Answer,
+                )));
+            } else {
+                cloze_answers(children, number, answers);
+            }
+        }
+    }
+}
+
+/// Text with its cloze deletions rendered for the card with the cloze
+/// number, `{{c1::...}}` is number 1. The question hides the deletions
+/// of the number behind `[...]` or their hint.
+pub fn render_cloze(
+    text: &str,
+    number: u64,
+    side: CardSide,
+) -> String {
+    render_cloze_nodes(&parse_cloze(text), number, side)
+}
+
+fn hint_html(field: &str, text: &str) -> String {
+    if is_field_empty(text) {
+        return String::new();
+    }
+    let id = format!("{:x}", field_checksum(text));
+    format!(
+        "<a class=\"hint\" href=\"#\" onclick=\"this.style.display='none';\
+         document.getElementById('hint{id}').style.display='block';\
+         return false;\" draggable=\"false\">{field}</a>\
+         <div id=\"hint{id}\" class=\"hint\" style=\"display: none\">\
+         {text}</div>"
+    )
+}
+
+/// Renders Anki card templates for one card of a note.
+struct Renderer<'a> {
+    fields: &'a HashMap<String, String>,
+    // Cloze number of the card, its ordinal + 1
+    cloze_number: u64,
+    side: CardSide,
+    front_side: &'a str,
+}
+
+impl Renderer<'_> {
+    fn render(
+        &self,
+        nodes: &[TemplateNode],
+    ) -> Result<String, String> {
+        let mut rendered = String::new();
+        for node in nodes {
+            match node {
+                TemplateNode::Text(text) => rendered.push_str(text),
+                TemplateNode::Replacement { field, filters } => {
+                    rendered
+                        .push_str(&self.replacement(field, filters)?)
+                }
+                TemplateNode::Conditional {
+                    field,
+                    negated,
+                    children,
+                } => {
+                    let empty = self
+                        .fields
+                        .get(field)
+                        .is_none_or(|value| is_field_empty(value));
+                    if empty == *negated {
+                        rendered.push_str(&self.render(children)?);
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+
+    fn replacement(
+        &self,
+        field: &str,
+        filters: &[String],
+    ) -> Result<String, String> {
+        if field == FRONT_SIDE && filters.is_empty() {
+            return Ok(match self.side {
+                CardSide::Question => String::new(),
+                CardSide::Answer => self.front_side.to_string(),
+            });
+        }
+        // The type answer box is filled in once the side is rendered
+        if filters.first().is_some_and(|filter| filter == "type") {
+            let mut marker = TYPE_ANSWER_PREFIX.to_string();
+            for filter in &filters[1..] {
+                marker.push_str(filter);
+                marker.push(':');
+            }
+            marker.push_str(field);
+            marker.push_str("]]");
+            return Ok(marker);
+        }
+
+        let mut text =
+            self.fields.get(field).cloned().ok_or_else(|| {
+                format!("Unknown field '{}'", field)
+            })?;
+        for filter in filters.iter().rev() {
+            text = match filter.as_str() {
+                "text" => strip_html(&text),
+                "hint" => hint_html(field, &text),
+                "cloze" => {
+                    render_cloze(&text, self.cloze_number, self.side)
+                }
+                // Filters of add-ons and of the desktop client, such as
+                // furigana or tts, leave the text as it is
+                _ => text,
+            };
+        }
+        Ok(text)
+    }
+
+    // Anki shows one type answer box per card, further ones are dropped
+    fn type_answer(&self, rendered: &str) -> String {
+        let Some(start) = rendered.find(TYPE_ANSWER_PREFIX) else {
+            return rendered.to_string();
+        };
+        let Some(end) =
+            rendered[start..].find("]]").map(|end| start + end)
+        else {
+            return rendered.to_string();
+        };
+        let spec = &rendered[start + TYPE_ANSWER_PREFIX.len()..end];
+        let (is_cloze, field) = match spec.strip_prefix("cloze:") {
+            Some(field) => (true, field),
+            None => (false, spec),
+        };
+        let replacement = match self.side {
+            CardSide::Question => "<center><input type=\"text\" id=\"typeans\" \
+                                   onkeypress=\"_typeAnsPress();\"></center>"
+                .to_string(),
+            CardSide::Answer => {
+                let value = self.fields.get(field).cloned().unwrap_or_default();
+                let expected = if is_cloze {
+                    let mut answers = vec![];
+                    cloze_answers(
+                        &parse_cloze(&value),
+                        self.cloze_number,
+                        &mut answers,
+                    );
+                    answers.join(", ")
+                } else {
+                    strip_html(&value)
+                };
+                format!(
+                    "<center><code id=\"typeans\">{}</code></center>",
+                    escape_html(&expected)
+                )
+            }
+        };
+
+        let mut result = rendered[..start].to_string();
+        result.push_str(&replacement);
+        let mut rest = &rendered[end + 2..];
+        while let Some(start) = rest.find(TYPE_ANSWER_PREFIX) {
+            let Some(end) = rest[start..].find("]]") else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            rest = &rest[start + end + 2..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+/// Both sides of a rendered card with the CSS of its model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedCard {
+    pub ordinal: u64,
+    pub question: String,
+    pub answer: String,
+    pub css: String,
+}
+
+impl RenderedCard {
+    pub fn question_html(&self) -> String {
+        self.page(&self.question)
+    }
+
+    pub fn answer_html(&self) -> String {
+        self.page(&self.answer)
+    }
+
+    // A page laid out the way Anki's reviewer shows a card
+    fn page(&self, body: &str) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <style>\n{}\n</style>\n</head>\n<body>\n\
+             <div class=\"card card{}\">\n{}\n</div>\n</body>\n</html>\n",
+            self.css,
+            self.ordinal + 1,
+            body
+        )
+    }
+}
+
+/// Field values of the note by field name, with the special fields
+/// `Tags`, `Type` (the model name) and `Card` (the template name).
+fn note_fields(
+    model: &Model,
+    note: &Note,
+    template_name: &str,
+) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    fields.insert(
+        "Tags".to_string(),
+        note.tags
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    fields.insert("Type".to_string(), model.name.clone());
+    fields.insert("Card".to_string(), template_name.to_string());
+    for field in &model.fields {
+        fields.insert(
+            field.name.clone(),
+            note.fields
+                .get(field.ordinal)
+                .cloned()
+                .unwrap_or_default(),
+        );
+    }
+    fields
+}
+
+/// Renders the question and the answer of the note's card with the
+/// ordinal. Cloze models render every card with their one template.
+pub fn render_card(
+    model: &Model,
+    note: &Note,
+    ordinal: u64,
+) -> Result<RenderedCard, String> {
+    let template = match model.model_type {
+        ModelType::FrontBack => model.templates.get(ordinal as usize),
+        ModelType::Cloze => model.templates.first(),
+    }
+    .ok_or_else(|| {
+        format!("Model {} has no template {}", model.name, ordinal)
+    })?;
+    let fields = note_fields(model, note, &template.name);
+
+    let question = Renderer {
+        fields: &fields,
+        cloze_number: ordinal + 1,
+        side: CardSide::Question,
+        front_side: "",
+    };
+    let front_side = question
+        .render(&parse_template(&template.question_format)?)?;
+    let answer = Renderer {
+        side: CardSide::Answer,
+        front_side: &front_side,
+        ..question
+    };
+    let back_side =
+        answer.render(&parse_template(&template.answer_template)?)?;
+
+    Ok(RenderedCard {
+        ordinal,
+        question: question.type_answer(&front_side),
+        answer: answer.type_answer(&back_side),
+        css: model.css.clone(),
+    })
+}
+
+/// Whether a template renders something from the fields, Anki's test
+/// for an empty card. Text and the special fields don't count.
+fn renders_with_fields(
+    nodes: &[TemplateNode],
+    nonempty: &HashSet<&str>,
+) -> bool {
+    nodes.iter().any(|node| match node {
+        TemplateNode::Text(_) => false,
+        TemplateNode::Replacement { field, .. } => {
+            nonempty.contains(field.as_str())
+        }
+        TemplateNode::Conditional {
+            field,
+            negated,
+            children,
+        } => {
+            nonempty.contains(field.as_str()) != *negated
+                && renders_with_fields(children, nonempty)
+        }
+    })
+}
+
+// Fields the nodes put through the cloze filter
+fn cloze_fields<'a>(
+    nodes: &'a [TemplateNode],
+    fields: &mut Vec<&'a str>,
+) {
+    for node in nodes {
+        match node {
+            TemplateNode::Replacement { field, filters }
+                if filters.iter().any(|filter| filter == "cloze") =>
+            {
+                fields.push(field)
+            }
+            TemplateNode::Conditional { children, .. } => {
+                cloze_fields(children, fields)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_cloze_numbers(
+    nodes: &[ClozeNode],
+    numbers: &mut BTreeSet<u64>,
+) {
+    for node in nodes {
+        if let ClozeNode::Deletion {
+            number, children, ..
+        } = node
+        {
+            if *number > 0 {
+                numbers.insert(*number);
+            }
+            collect_cloze_numbers(children, numbers);
+        }
+    }
+}
+
+/// Cloze numbers `n` of every `{{cn::...}}` deletion in the text,
+/// nested ones included.
+pub fn cloze_numbers(text: &str) -> BTreeSet<u64> {
+    let mut numbers = BTreeSet::new();
+    collect_cloze_numbers(&parse_cloze(text), &mut numbers);
+    numbers
+}
+
+/// Ordinals of the cards the note makes: the templates whose question
+/// is not empty, or for cloze models one card per cloze number in the
+/// fields the question template clozes, ordinal 0 for `c1`.
+pub fn card_ordinals(
+    model: &Model,
+    note: &Note,
+) -> Result<Vec<u64>, String> {
+    let fields = model
+        .fields
+        .iter()
+        .map(|field| {
+            (
+                field.name.as_str(),
+                note.fields
+                    .get(field.ordinal)
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    match model.model_type {
+        ModelType::FrontBack => {
+            let nonempty = fields
+                .iter()
+                .filter(|(_, value)| !is_field_empty(value))
+                .map(|(name, _)| *name)
+                .collect::<HashSet<_>>();
+            let mut ordinals = vec![];
+            for (ordinal, template) in
+                model.templates.iter().enumerate()
+            {
+                let nodes =
+                    parse_template(&template.question_format)?;
+                if renders_with_fields(&nodes, &nonempty) {
+                    ordinals.push(ordinal as u64);
+                }
+            }
+            Ok(ordinals)
+        }
+        ModelType::Cloze => {
+            let template =
+                model.templates.first().ok_or_else(|| {
+                    format!("Model {} has no template", model.name)
+                })?;
+            let nodes = parse_template(&template.question_format)?;
+            let mut clozed = vec![];
+            cloze_fields(&nodes, &mut clozed);
+            Ok(fields
+                .iter()
+                .filter(|(name, _)| clozed.contains(name))
+                .flat_map(|(_, value)| cloze_numbers(value))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(|number| number - 1)
+                .collect())
+        }
+    }
+}
+
+fn write_template(nodes: &[TemplateNode]) -> String {
+    nodes
+        .iter()
+        .map(|node| match node {
+            TemplateNode::Text(text) => text.clone(),
+            TemplateNode::Replacement { field, filters } => {
+                let mut tag = String::from("{{");
+                for filter in filters {
+                    tag.push_str(filter);
+                    tag.push(':');
+                }
+                tag.push_str(field);
+                tag.push_str("}}");
+                tag
+            }
+            TemplateNode::Conditional {
+                field,
+                negated,
+                children,
+            } => format!(
+                "{{{{{}{}}}}}{}{{{{/{}}}}}",
+                if *negated { '^' } else { '#' },
+                field,
+                write_template(children),
+                field
+            ),
+        })
+        .collect()
+}
+
+fn rename_and_remove(
+    nodes: Vec<TemplateNode>,
+    fields: &HashMap<String, Option<String>>,
+) -> Vec<TemplateNode> {
+    let mut result = vec![];
+    for node in nodes {
+        match node {
+            TemplateNode::Replacement { field, filters } => {
+                match fields.get(&field) {
+                    Some(Some(renamed)) => {
+                        result.push(TemplateNode::Replacement {
+                            field: renamed.clone(),
+                            filters,
+                        })
+                    }
+                    Some(None) => {}
+                    None => result.push(TemplateNode::Replacement {
+                        field,
+                        filters,
+                    }),
+                }
+            }
+            TemplateNode::Conditional {
+                field,
+                negated,
+                children,
+            } => {
+                let children = rename_and_remove(children, fields);
+                match fields.get(&field) {
+                    Some(Some(renamed)) => {
+                        result.push(TemplateNode::Conditional {
+                            field: renamed.clone(),
+                            negated,
+                            children,
+                        })
+                    }
+                    // The section stays without its condition
+                    Some(None) => result.extend(children),
+                    None => result.push(TemplateNode::Conditional {
+                        field,
+                        negated,
+                        children,
+                    }),
+                }
+            }
+            text => result.push(text),
+        }
+    }
+    result
+}
+
+/// Template with the references to fields renamed, to `Some` new
+/// name, or removed, for `None`, as Anki updates the templates when
+/// the fields of a notetype change.
+pub fn rename_and_remove_fields(
+    template: &str,
+    fields: &HashMap<String, Option<String>>,
+) -> Result<String, String> {
+    Ok(write_template(&rename_and_remove(
+        parse_template(template)?,
+        fields,
+    )))
+}
+
+/// Legacy `req` of the model, used by old clients to pick the cards of
+/// a note: per template, the field ordinals that must `all` be filled,
+/// or of which `any` must be, or `none` when the template never
+/// renders a field. Cloze models have none.
+pub fn card_requirements(
+    model: &Model,
+) -> Result<Vec<(usize, String, Vec<usize>)>, String> {
+    if model.model_type == ModelType::Cloze {
+        return Ok(vec![]);
+    }
+    let mut fields = model
+        .fields
+        .iter()
+        .map(|field| (field.ordinal, field.name.as_str()))
+        .collect::<Vec<_>>();
+    fields.sort();
+
+    let mut requirements = vec![];
+    for (ordinal, template) in model.templates.iter().enumerate() {
+        let nodes = parse_template(&template.question_format)?;
+        let all_fields = fields
+            .iter()
+            .map(|(_, name)| *name)
+            .collect::<HashSet<_>>();
+        if !renders_with_fields(&nodes, &all_fields) {
+            requirements.push((ordinal, "none".to_string(), vec![]));
+            continue;
+        }
+
+        // Fields without which the template is empty
+        let required = fields
+            .iter()
+            .filter(|(_, name)| {
+                let mut others = all_fields.clone();
+                others.remove(name);
+                !renders_with_fields(&nodes, &others)
+            })
+            .map(|(field_ordinal, _)| *field_ordinal)
+            .collect::<Vec<_>>();
+        if !required.is_empty() {
+            requirements.push((ordinal, "all".to_string(), required));
+            continue;
+        }
+
+        // Fields enough on their own
+        let sufficient = fields
+            .iter()
+            .filter(|(_, name)| {
+                renders_with_fields(&nodes, &HashSet::from([*name]))
+            })
+            .map(|(field_ordinal, _)| *field_ordinal)
+            .collect();
+        requirements.push((ordinal, "any".to_string(), sufficient));
+    }
+    Ok(requirements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db_model::model::{ModelField, ModelTemplate};
+    use crate::db_model::note::NoteTag;
+
+    fn create_model(
+        model_type: ModelType,
+        fields: &[&str],
+        templates: &[(&str, &str)],
+    ) -> Model {
+        Model::new(
+            None,
+            fields
+                .iter()
+                .enumerate()
+                .map(|(ordinal, name)| {
+                    ModelField::new(name.to_string(), ordinal)
+                })
+                .collect(),
+            1,
+            None,
+            None,
+            "Test".to_string(),
+            0,
+            templates
+                .iter()
+                .enumerate()
+                .map(|(index, (question, answer))| {
+                    ModelTemplate::new(
+                        format!("Card {}", index + 1),
+                        question.to_string(),
+                        answer.to_string(),
+                    )
+                })
+                .collect(),
+            model_type,
+        )
+    }
+
+    fn create_note(fields: &[&str]) -> Note {
+        Note::new(
+            1,
+            String::new(),
+            1,
+            0,
+            0,
+            vec![NoteTag::new("verbs").unwrap()],
+            fields.iter().map(|field| field.to_string()).collect(),
+            String::new(),
+            0,
+        )
+    }
+
+    #[test]
+    fn test_render_fields_and_front_side() {
+        let model = create_model(
+            ModelType::FrontBack,
+            &["Front", "Back"],
+            &[
+                ("{{Front}}", "{{FrontSide}}<hr id=answer>{{Back}}"),
+                ("{{Back}} ({{Card}}, {{Tags}})", "{{Front}}"),
+            ],
+        );
+        let note = create_note(&["hola", "<b>hello</b>"]);
+
+        let card = render_card(&model, &note, 0).unwrap();
+        assert_eq!(card.question, "hola");
+        assert_eq!(card.answer, "hola<hr id=answer><b>hello</b>");
+        let card = render_card(&model, &note, 1).unwrap();
+        assert_eq!(card.question, "<b>hello</b> (Card 2, verbs)");
+        assert!(render_card(&model, &note, 2).is_err());
+    }
+
+    #[test]
+    fn test_render_conditionals() {
+        let model = create_model(
+            ModelType::FrontBack,
+            &["Front", "Extra"],
+            &[(
+                "{{Front}}{{#Extra}} [{{Extra}}]{{/Extra}}\
+                 {{^Extra}} (no extra){{/Extra}}",
+                "",
+            )],
+        );
+
+        let card =
+            render_card(&model, &create_note(&["hola", "hi"]), 0)
+                .unwrap();
+        assert_eq!(card.question, "hola [hi]");
+        let card =
+            render_card(&model, &create_note(&["hola", "<br> "]), 0)
+                .unwrap();
+        assert_eq!(card.question, "hola (no extra)");
+    }
+
+    #[test]
+    fn test_parse_template_errors() {
+        assert!(parse_template("{{#Front}}x").is_err());
+        assert!(parse_template("{{/Front}}").is_err());
+        assert!(parse_template("{{#Front}}x{{/Back}}").is_err());
+        assert!(parse_template("{{Front").is_err());
+
+        let model = create_model(
+            ModelType::FrontBack,
+            &["Front"],
+            &[("{{Missing}}", "")],
+        );
+        assert!(
+            render_card(&model, &create_note(&["hola"]), 0).is_err()
+        );
+    }
+
+    #[test]
+    fn test_render_filters() {
+        let model = create_model(
+            ModelType::FrontBack,
+            &["Front", "Back"],
+            &[(
+                "{{text:Front}}|{{hint:Back}}|{{furigana:Front}}",
+                "",
+            )],
+        );
+        let note = create_note(&["<i>hola</i>", "hello"]);
+
+        let card = render_card(&model, &note, 0).unwrap();
+        let parts = card.question.split('|').collect::<Vec<_>>();
+        assert_eq!(parts[0], "hola");
+        assert!(parts[1].starts_with("<a class=\"hint\""));
+        assert!(parts[1].contains(">Back</a>"));
+        assert!(parts[1].ends_with("hello</div>"));
+        assert_eq!(parts[2], "<i>hola</i>");
+    }
+
+    #[test]
+    fn test_render_type_answer() {
+        let model = create_model(
+            ModelType::FrontBack,
+            &["Front", "Back"],
+            &[(
+                "{{Front}} {{type:Back}}",
+                "{{FrontSide}}<hr id=answer>{{type:Back}}",
+            )],
+        );
+        let note = create_note(&["hola", "hello & bye"]);
+
+        let card = render_card(&model, &note, 0).unwrap();
+        assert_eq!(
+            card.question,
+            "hola <center><input type=\"text\" id=\"typeans\" \
+             onkeypress=\"_typeAnsPress();\"></center>"
+        );
+        assert_eq!(
+            card.answer,
+            "hola <center><code id=\"typeans\">hello &amp; bye</code>\
+             </center><hr id=answer>"
+        );
+    }
+
+    #[test]
+    fn test_render_cloze() {
+        assert_eq!(
+            render_cloze(
+                "{{c1::hola}} {{c2::mundo::world}}",
+                1,
+                CardSide::Question
+            ),
+            "<span class=\"cloze\" data-ordinal=\"1\">[...]</span> \
+             <span class=\"cloze-inactive\" data-ordinal=\"2\">mundo</span>"
+        );
+        assert_eq!(
+            render_cloze(
+                "{{c2::mundo::world}}",
+                2,
+                CardSide::Question
+            ),
+            "<span class=\"cloze\" data-ordinal=\"2\">[world]</span>"
+        );
+        assert_eq!(
+            render_cloze("{{c1::uno {{c2::dos}}}}", 2, CardSide::Answer),
+            "<span class=\"cloze-inactive\" data-ordinal=\"1\">uno \
+             <span class=\"cloze\" data-ordinal=\"2\">dos</span></span>"
+        );
+        assert_eq!(
+            render_cloze("{{c1::open", 1, CardSide::Question),
+            "{{c1::open"
+        );
+    }
+
+    #[test]
+    fn test_render_cloze_card() {
+        let model = create_model(
+            ModelType::Cloze,
+            &["Text"],
+            &[(
+                "{{cloze:Text}}",
+                "{{cloze:Text}}<br>{{type:cloze:Text}}",
+            )],
+        );
+        let note = create_note(&["{{c1::hola}} {{c2::mundo}}"]);
+
+        let card = render_card(&model, &note, 1).unwrap();
+        assert_eq!(
+            card.question,
+            "<span class=\"cloze-inactive\" data-ordinal=\"1\">hola</span> \
+             <span class=\"cloze\" data-ordinal=\"2\">[...]</span>"
+        );
+        assert!(card.answer.ends_with(
+            "<br><center><code id=\"typeans\">mundo</code></center>"
+        ));
+        let html = card.question_html();
+        assert!(html.contains("<div class=\"card card2\">"));
+        assert!(html.contains(&model.css));
+    }
+
+    #[test]
+    fn test_cloze_numbers() {
+        let numbers = cloze_numbers(
+            "{{c1::hola}} {{c3::mundo::hint}} {{c1::x}}",
+        );
+        assert_eq!(
+            numbers.into_iter().collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(
+            cloze_numbers("{{c1::uno {{c2::dos}}}}")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(cloze_numbers("{{Front}} {{c0::x}}").is_empty());
+    }
+
+    #[test]
+    fn test_card_ordinals() {
+        let model = create_model(
+            ModelType::FrontBack,
+            &["Front", "Back", "Extra"],
+            &[
+                ("{{Front}}", ""),
+                ("{{Back}}", ""),
+                ("{{#Extra}}{{Front}}{{/Extra}}", ""),
+                ("{{^Extra}}{{Back}}{{/Extra}}", ""),
+                ("{{Tags}} {{Card}}", ""),
+            ],
+        );
+
+        assert_eq!(
+            card_ordinals(&model, &create_note(&["hola", "", ""]))
+                .unwrap(),
+            vec![0]
+        );
+        assert_eq!(
+            card_ordinals(
+                &model,
+                &create_note(&["hola", "hi", "<br>"])
+            )
+            .unwrap(),
+            vec![0, 1, 3]
+        );
+        assert_eq!(
+            card_ordinals(&model, &create_note(&["hola", "hi", "x"]))
+                .unwrap(),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_card_ordinals_cloze() {
+        let model = create_model(
+            ModelType::Cloze,
+            &["Text", "Extra"],
+            &[("{{cloze:Text}}", "{{cloze:Text}}{{Extra}}")],
+        );
+
+        let note =
+            create_note(&["{{c1::uno}} {{c3::tres}}", "{{c2::x}}"]);
+        assert_eq!(card_ordinals(&model, &note).unwrap(), vec![0, 2]);
+        let note = create_note(&["uno", ""]);
+        assert!(card_ordinals(&model, &note).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_card_requirements() {
+        let model = create_model(
+            ModelType::FrontBack,
+            &["Front", "Back", "Extra"],
+            &[
+                ("{{Front}}", ""),
+                ("{{Front}} {{Back}}", ""),
+                ("{{#Extra}}{{Front}}{{/Extra}}", ""),
+                ("{{Tags}}", ""),
+            ],
+        );
+
+        assert_eq!(
+            card_requirements(&model).unwrap(),
+            vec![
+                (0, "all".to_string(), vec![0]),
+                (1, "any".to_string(), vec![0, 1]),
+                (2, "all".to_string(), vec![0, 2]),
+                (3, "none".to_string(), vec![]),
+            ]
+        );
+        let cloze = create_model(
+            ModelType::Cloze,
+            &["Text"],
+            &[("{{cloze:Text}}", "")],
+        );
+        assert!(card_requirements(&cloze).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rename_and_remove_fields() {
+        let fields = HashMap::from([
+            ("Front".to_string(), Some("Question".to_string())),
+            ("Extra".to_string(), None),
+        ]);
+
+        assert_eq!(
+            rename_and_remove_fields(
+                "{{text:Front}} {{Extra}}{{#Extra}}<i>{{Back}}</i>\
+                 {{/Extra}}{{^Front}}none{{/Front}}",
+                &fields
+            )
+            .unwrap(),
+            "{{text:Question}} <i>{{Back}}</i>\
+             {{^Question}}none{{/Question}}"
+        );
+        assert!(
+            rename_and_remove_fields("{{#Front}}", &fields).is_err()
+        );
+    }
+}
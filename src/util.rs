@@ -1,31 +1,186 @@
+use sha1::Sha1;
 use sha2::{Digest, Sha256};
 
-const BASE91: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+use crate::guid::base91;
 
+/// Base91 of the first 8 bytes of the SHA-256 of the strings joined
+/// with "__", in Anki's base91 alphabet.
+#[deprecated(note = "use guid::GuidKey or guid::base91")]
 pub fn base91_encode(data: &[String]) -> String {
-    let hash_str = data
+    let hash = Sha256::digest(data.join("__").as_bytes());
+    base91(
+        hash[..8]
+            .iter()
+            .fold(0, |number, byte| (number << 8) + *byte as u64),
+    )
+}
+
+// Anki note checksum: the first 8 hex digits of the sha1 of the field
+pub fn field_checksum(field: &str) -> i64 {
+    let hash = Sha1::digest(field.as_bytes());
+    hash[..4]
         .iter()
-        .map(|s| s.as_str())
-        .collect::<Vec<&str>>()
-        .join("__");
-
-    let mut hash = Sha256::new();
-    hash.update(hash_str.as_bytes());
-
-    let hash_bytes = hash.finalize()[..8].to_vec();
-    let mut hash_int = 0;
-    for byte in hash_bytes {
-        hash_int = hash_int << 8;
-        hash_int += byte as u64;
+        .fold(0, |checksum, byte| (checksum << 8) + *byte as i64)
+}
+
+// Value of an attribute of a tag, such as the src of an img
+fn tag_attribute(tag: &str, attribute: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let start =
+        lower.find(&format!("{}=", attribute))? + attribute.len() + 1;
+    let value = &tag[start..];
+    let (value, end) = match value.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let value = &value[1..];
+            (value, value.find(quote)?)
+        }
+        _ => (
+            value,
+            value
+                .find(|c: char| {
+                    c.is_whitespace() || c == '>' || c == '/'
+                })
+                .unwrap_or(value.len()),
+        ),
+    };
+    Some(value[..end].to_string())
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "nbsp" => Some(' '),
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
     }
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
 
-    let mut encoded = String::new();
+/// Field text without HTML, as Anki computes the sort field and the
+/// checksum: comments, styles and scripts are dropped, images are
+/// replaced by their file name and entities are decoded.
+pub fn strip_html(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
 
-    while hash_int > 0 {
-        let remainder = (hash_int % 91) as usize;
-        hash_int /= 91;
-        encoded.push(BASE91.chars().nth(remainder).unwrap());
+    while let Some(c) = rest.chars().next() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment
+                .find("-->")
+                .map_or("", |end| &comment[end + 3..]);
+        } else if c == '<' {
+            let Some(end) = rest.find('>') else {
+                stripped.push_str(rest);
+                break;
+            };
+            let tag = &rest[1..end];
+            let name = tag
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            rest = &rest[end + 1..];
+            match name.as_str() {
+                "style" | "script" => {
+                    let close = format!("</{}", name);
+                    rest = rest
+                        .to_ascii_lowercase()
+                        .find(&close)
+                        .and_then(|start| {
+                            rest[start..]
+                                .find('>')
+                                .map(|end| start + end + 1)
+                        })
+                        .map_or("", |end| &rest[end..]);
+                }
+                "img" => {
+                    if let Some(source) = tag_attribute(tag, "src") {
+                        stripped.push_str(&format!(" {} ", source));
+                    }
+                }
+                _ => {}
+            }
+        } else if c == '&' {
+            let entity = rest[1..]
+                .find(';')
+                .filter(|end| *end <= 8)
+                .and_then(|end| {
+                    decode_entity(&rest[1..end + 1])
+                        .map(|c| (c, end + 2))
+                });
+            match entity {
+                Some((decoded, length)) => {
+                    stripped.push(decoded);
+                    rest = &rest[length..];
+                }
+                None => {
+                    stripped.push('&');
+                    rest = &rest[1..];
+                }
+            }
+        } else {
+            stripped.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
     }
 
-    encoded.chars().rev().collect::<String>()
+    stripped.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_base91_encode() {
+        let data = ["hola".to_string(), "hello".to_string()];
+        let hash = Sha256::digest(b"hola__hello");
+        assert_eq!(
+            base91_encode(&data),
+            base91(u64::from_be_bytes(hash[..8].try_into().unwrap()))
+        );
+        assert_ne!(base91_encode(&data), base91_encode(&data[..1]));
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html("<b>hola</b>&nbsp;&amp; <i>adiós</i>"),
+            "hola & adiós"
+        );
+        assert_eq!(
+            strip_html(
+                "<style>.a {}</style>see<!-- note --> \
+                 <img src=\"hola.png\">"
+            ),
+            "see  hola.png"
+        );
+        assert_eq!(
+            strip_html("a &unknown; &#39;b&#x27;"),
+            "a &unknown; 'b'"
+        );
+        assert_eq!(strip_html("1 < 2"), "1 < 2");
+    }
+
+    #[test]
+    fn test_field_checksum() {
+        // sha1("hola") starts with 99800b85
+        assert_eq!(field_checksum("hola"), 0x99800b85);
+    }
 }
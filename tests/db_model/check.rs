@@ -0,0 +1,135 @@
+use ankimdown::db_model::card::{CardQueue, CardType};
+use ankimdown::db_model::check::*;
+use ankimdown::db_model::grave::GraveType;
+use ankimdown::db_model::id::IdAllocator;
+
+use crate::db_model::{
+    create_test_card, create_test_collection, create_test_note,
+};
+
+#[test]
+fn test_check_valid_collection() {
+    let collection = create_test_collection();
+    let notes = vec![create_test_note(1, &["hola", "hello"])];
+    let cards = vec![create_test_card(2, 1, 1)];
+
+    assert!(collection.check(&notes, &cards).is_ok());
+}
+
+#[test]
+fn test_check_problems() {
+    let collection = create_test_collection();
+    let mut notes = vec![
+        create_test_note(1, &["hola", "hello"]),
+        create_test_note(1, &["hola", "hello"]),
+        create_test_note(3, &["adiós"]),
+        create_test_note(4, &["sí"]),
+    ];
+    let mut cards = vec![
+        create_test_card(10, 1, 1),
+        create_test_card(10, 4, 1),
+        create_test_card(11, 99, 1),
+        create_test_card(12, 1, 42),
+        create_test_card(13, 1, 1),
+        create_test_card(14, 1, 1),
+    ];
+    notes[2].model_id = 8;
+    cards[4].ordinal = 1;
+    cards[5].card_type = CardType::Review;
+
+    let report = collection.check(&notes, &cards);
+
+    assert_eq!(
+        report.problems,
+        vec![
+            CheckProblem::DuplicateNoteId(1),
+            CheckProblem::DuplicateCardId(10),
+            CheckProblem::MissingModel {
+                note_id: 3,
+                model_id: 8
+            },
+            CheckProblem::FieldCount {
+                note_id: 4,
+                expected: 2,
+                found: 1
+            },
+            CheckProblem::OrphanCard {
+                card_id: 11,
+                note_id: 99
+            },
+            CheckProblem::MissingDeck {
+                card_id: 12,
+                deck_id: 42
+            },
+            CheckProblem::InvalidOrdinal {
+                card_id: 13,
+                ordinal: 1
+            },
+            CheckProblem::InvalidQueue {
+                card_id: 14,
+                card_type: CardType::Review,
+                queue: CardQueue::New
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_check_and_fix() {
+    let mut collection = create_test_collection();
+    let mut notes = vec![
+        create_test_note(1, &["hola", "hello"]),
+        create_test_note(1, &["hola", "hello"]),
+        create_test_note(3, &["adiós"]),
+        create_test_note(4, &["sí"]),
+        create_test_note(5, &["<b>uno</b>", "one", "un"]),
+    ];
+    let mut cards = vec![
+        create_test_card(10, 1, 1),
+        create_test_card(10, 4, 1),
+        create_test_card(11, 99, 1),
+        create_test_card(12, 1, 42),
+        create_test_card(13, 1, 1),
+        create_test_card(14, 1, 1),
+        create_test_card(15, 3, 1),
+    ];
+    notes[2].model_id = 8;
+    cards[4].ordinal = 1;
+    cards[5].card_type = CardType::Review;
+    let mut ids = IdAllocator::starting_at(100);
+
+    let report = collection
+        .check_and_fix(&mut notes, &mut cards, &mut ids, 5)
+        .unwrap();
+
+    assert!(!report.is_ok());
+    let graves = report
+        .graves
+        .iter()
+        .map(|grave| (grave.object_id, grave.grave_type))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        graves,
+        vec![
+            (3, GraveType::Note),
+            (11, GraveType::Card),
+            (13, GraveType::Card),
+            (15, GraveType::Card),
+        ]
+    );
+    assert_eq!(
+        notes.iter().map(|note| note.id).collect::<Vec<_>>(),
+        vec![1, 4, 5]
+    );
+    assert_eq!(notes[1].fields, vec!["sí", ""]);
+    assert_eq!(notes[2].fields, vec!["<b>uno</b>", "one; un"]);
+    assert_eq!(notes[2].sort_field, "uno");
+    assert_eq!(
+        cards.iter().map(|card| card.id).collect::<Vec<_>>(),
+        vec![10, 100, 12, 14]
+    );
+    let recovered = collection.deck_id(RECOVERED_DECK).unwrap();
+    assert_eq!(cards[2].deck_id, recovered);
+    assert_eq!(cards[3].queue, CardQueue::Review);
+    assert!(collection.check(&notes, &cards).is_ok());
+}
@@ -0,0 +1,380 @@
+use ankimdown::db_model::card::*;
+use ankimdown::db_model::collection::*;
+use ankimdown::db_model::database::*;
+use ankimdown::db_model::deck::{Deck, DeckConfig};
+use ankimdown::db_model::model::{
+    Model, ModelField, ModelTemplate, ModelType,
+};
+use ankimdown::db_model::note::{Note, NoteTag};
+use rusqlite::Connection;
+
+use crate::db_model::create_test_card;
+
+fn create_spanish_collection() -> Collection {
+    let model = Model::new(
+        None,
+        vec![
+            ModelField::new("Front".to_string(), 0),
+            ModelField::new("Back".to_string(), 1),
+        ],
+        1342697561419,
+        None,
+        None,
+        "Basic".to_string(),
+        0,
+        vec![],
+        ModelType::FrontBack,
+    );
+    let mut deck = Deck::new("Spanish".to_string());
+    deck.id = 1;
+
+    Collection {
+        id: 1,
+        created: 1700000000,
+        modified: 1700000000123,
+        scheme_mod_time: 1700000000456,
+        version: 11,
+        dirty: 0,
+        update_seq_number: 0,
+        last_sync_time: 0,
+        config: CollectionConfig::new(),
+        models: vec![(1342697561419, model)],
+        decks: vec![(1, deck)],
+        deck_configs: vec![(
+            1,
+            DeckConfig::new("Default".to_string()),
+        )],
+        tags: String::new(),
+    }
+}
+
+fn create_spanish_note() -> Note {
+    Note::new(
+        1700000000001,
+        "f#Wq[d3B:s".to_string(),
+        1342697561419,
+        1700000000,
+        -1,
+        vec![
+            NoteTag::new("Verbs::Irregular").unwrap(),
+            NoteTag::new("common").unwrap(),
+        ],
+        vec!["hola".to_string(), "hello".to_string()],
+        "hola".to_string(),
+        2856405342,
+    )
+}
+
+fn review_card(id: i64, ordinal: u64) -> Card {
+    let mut card = create_test_card(id, 1700000000001, 1);
+    card.ordinal = ordinal;
+    card.modified = 1700000000;
+    card.card_type = CardType::Review;
+    card.queue = CardQueue::Review;
+    card.due = 120;
+    card.interval = 10;
+    card.factor = 2500;
+    card.reviews = 4;
+    card.lapses = 1;
+    card.flags = CardFlag::Green;
+    card
+}
+
+#[test]
+fn test_write_collection() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("collection.anki2");
+    let collection = create_spanish_collection();
+    let note = create_spanish_note();
+    let cards = vec![
+        review_card(1700000000002, 0),
+        review_card(1700000000003, 1),
+    ];
+
+    write_collection(&path, &collection, &[note], &cards).unwrap();
+
+    let connection = Connection::open(&path).unwrap();
+
+    let tables = connection
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' \
+             ORDER BY name",
+        )
+        .unwrap()
+        .query_map([], |row| row.get::<_, String>(0))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        tables,
+        vec!["cards", "col", "graves", "notes", "revlog"]
+    );
+    let indexes: i64 = connection
+        .query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'index' \
+             AND name LIKE 'ix_%'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(indexes, 7);
+
+    let col = connection
+        .query_row(
+            "SELECT id, crt, mod, scm, ver, dty, usn, ls, conf, models, \
+             decks, dconf, tags FROM col",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, i64>(6)?,
+                    row.get::<_, i64>(7)?,
+                    row.get::<_, String>(8)?,
+                    row.get::<_, String>(9)?,
+                    row.get::<_, String>(10)?,
+                    row.get::<_, String>(11)?,
+                    row.get::<_, String>(12)?,
+                ))
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        (col.0, col.1, col.2, col.3, col.4, col.5, col.6, col.7),
+        (1, 1700000000, 1700000000123, 1700000000456, 11, 0, 0, 0)
+    );
+    let config: CollectionConfig =
+        serde_json::from_str(&col.8).unwrap();
+    assert_eq!(config, collection.config);
+    let models: serde_json::Value =
+        serde_json::from_str(&col.9).unwrap();
+    let model: Model =
+        serde_json::from_value(models["1342697561419"].clone())
+            .unwrap();
+    assert_eq!(model, collection.models[0].1);
+    let decks: serde_json::Value =
+        serde_json::from_str(&col.10).unwrap();
+    let deck: Deck =
+        serde_json::from_value(decks["1"].clone()).unwrap();
+    assert_eq!(deck, collection.decks[0].1);
+    let dconf: serde_json::Value =
+        serde_json::from_str(&col.11).unwrap();
+    let deck_config: DeckConfig =
+        serde_json::from_value(dconf["1"].clone()).unwrap();
+    assert_eq!(deck_config, collection.deck_configs[0].1);
+    assert_eq!(col.12, "{}");
+
+    let note = connection
+        .query_row(
+            "SELECT id, guid, mid, mod, usn, tags, flds, sfld, csum, \
+             flags, data FROM notes",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                    row.get::<_, String>(7)?,
+                    row.get::<_, i64>(8)?,
+                    row.get::<_, i64>(9)?,
+                    row.get::<_, String>(10)?,
+                ))
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        note,
+        (
+            1700000000001,
+            "f#Wq[d3B:s".to_string(),
+            1342697561419,
+            1700000000,
+            -1,
+            " Verbs::Irregular common ".to_string(),
+            "hola\x1fhello".to_string(),
+            "hola".to_string(),
+            2856405342,
+            0,
+            String::new(),
+        )
+    );
+
+    let rows = connection
+        .prepare(
+            "SELECT id, nid, did, ord, mod, usn, type, queue, due, ivl, \
+             factor, reps, lapses, left, odue, odid, flags, data \
+             FROM cards ORDER BY id",
+        )
+        .unwrap()
+        .query_map([], |row| {
+            (0..17)
+                .map(|index| row.get::<_, i64>(index))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|values| (values, row.get::<_, String>(17).unwrap()))
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    for (ordinal, (values, data)) in rows.into_iter().enumerate() {
+        assert_eq!(
+            values,
+            vec![
+                1700000000002 + ordinal as i64,
+                1700000000001,
+                1,
+                ordinal as i64,
+                1700000000,
+                -1,
+                2,
+                2,
+                120,
+                10,
+                2500,
+                4,
+                1,
+                0,
+                0,
+                0,
+                3,
+            ]
+        );
+        assert_eq!(data, "");
+    }
+
+    for table in ["revlog", "graves"] {
+        let count: i64 = connection
+            .query_row(
+                &format!("SELECT count(*) FROM {table}"),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
+
+#[test]
+fn test_write_collection_updates_requirements() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut collection = create_spanish_collection();
+    collection.models[0].1.templates = vec![ModelTemplate::new(
+        "Card 1".to_string(),
+        "{{Front}}".to_string(),
+        "{{Back}}".to_string(),
+    )];
+
+    // Both schemas write the requirements of the templates
+    for version in [SchemaVersion::V11, SchemaVersion::V18] {
+        let path = dir.path().join(format!("{:?}.anki2", version));
+        write_collection_version(
+            &path,
+            &collection,
+            &[],
+            &[],
+            version,
+        )
+        .unwrap();
+
+        let (written, _, _) = read_collection(&path).unwrap();
+        assert_eq!(
+            written.models[0].1.req,
+            vec![(0, "all".to_string(), vec![0])]
+        );
+    }
+
+    collection.models[0].1.templates[0].question_format =
+        "{{#Front}}".to_string();
+    let path = dir.path().join("invalid.anki2");
+    assert!(write_collection(&path, &collection, &[], &[]).is_err());
+}
+
+#[test]
+fn test_write_collection_existing_file() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    assert!(write_collection(
+        file.path(),
+        &create_spanish_collection(),
+        &[],
+        &[]
+    )
+    .is_err());
+}
+
+#[test]
+fn test_read_collection() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("collection.anki2");
+    let collection = create_spanish_collection();
+    let note = create_spanish_note();
+    let cards = vec![
+        review_card(1700000000002, 0),
+        review_card(1700000000003, 1),
+    ];
+    write_collection(
+        &path,
+        &collection,
+        std::slice::from_ref(&note),
+        &cards,
+    )
+    .unwrap();
+
+    let (read, notes, read_cards) = read_collection(&path).unwrap();
+
+    // an empty tag registry is written as an empty JSON object
+    assert_eq!(
+        read,
+        Collection {
+            tags: "{}".to_string(),
+            ..collection
+        }
+    );
+    assert_eq!(notes, vec![note]);
+    assert_eq!(read_cards, cards);
+}
+
+#[test]
+fn test_read_notes_split_columns() {
+    let connection = Connection::open_in_memory().unwrap();
+    create_schema(&connection).unwrap();
+    let collection = create_spanish_collection();
+    insert_collection(&connection, &collection).unwrap();
+    connection
+        .execute(
+            "INSERT INTO notes VALUES (1, 'guid', 1342697561419, 0, 0, \
+             '  Verbs::Irregular   common ', 'hola\x1fhello\x1f', \
+             'hello', 12, 0, '')",
+            [],
+        )
+        .unwrap();
+
+    let notes = select_notes(&connection).unwrap();
+
+    assert_eq!(notes.len(), 1);
+    assert_eq!(
+        notes[0].tags,
+        vec![
+            NoteTag::new("Verbs::Irregular").unwrap(),
+            NoteTag::new("common").unwrap(),
+        ]
+    );
+    assert_eq!(notes[0].fields, vec!["hola", "hello", ""]);
+    assert_eq!(notes[0].sort_field, "hello");
+    assert_eq!(notes[0].checksum, 12);
+}
+
+#[test]
+fn test_read_collection_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(
+        read_collection(&dir.path().join("missing.anki2")).is_err()
+    );
+}
@@ -89,49 +89,50 @@ fn test_deck_deserialize() {
 #[test]
 fn test_lapse_config_init() {
     let lapse_config = LapseConfig::new();
-    assert_eq!(lapse_config.delays, vec![0]);
-    assert_eq!(lapse_config.leech_action, 0);
-    assert_eq!(lapse_config.leech_fails, 0);
-    assert_eq!(lapse_config.min_interval, 0);
-    assert_eq!(lapse_config.interval_increase, 0);
+    assert_eq!(lapse_config.delays, vec![10.0]);
+    assert_eq!(lapse_config.leech_action, LeechAction::TagOnly);
+    assert_eq!(lapse_config.leech_fails, 8);
+    assert_eq!(lapse_config.min_interval, 1);
+    assert_eq!(lapse_config.interval_increase, 0.0);
 }
 
 #[test]
 fn test_new_config_init() {
     let new_config = NewConfig::new();
     assert!(!new_config.bury);
-    assert_eq!(new_config.delays, vec![0]);
-    assert_eq!(new_config.initial_factor, 0);
-    assert_eq!(new_config.intervals, vec![0]);
-    assert_eq!(new_config.order, NewCardOrder::Random);
-    assert_eq!(new_config.per_day, 0);
-    assert!(!new_config.separate);
+    assert_eq!(new_config.delays, vec![1.0, 10.0]);
+    assert_eq!(new_config.initial_factor, 2500);
+    assert_eq!(new_config.intervals, vec![1, 4]);
+    assert_eq!(new_config.order, NewCardOrder::Due);
+    assert_eq!(new_config.per_day, 20);
+    assert!(new_config.separate);
 }
 
 #[test]
 fn test_review_config_init() {
     let review_config = ReviewConfig::new();
     assert!(!review_config.bury);
-    assert_eq!(review_config.ease_factor, 0);
-    assert_eq!(review_config.fuzz, 0);
-    assert_eq!(review_config.interval_factor, 0);
-    assert_eq!(review_config.max_interval, 0);
-    assert_eq!(review_config.min_space, 0);
-    assert_eq!(review_config.cards_daily, 0);
+    assert_eq!(review_config.ease_factor, 1.3);
+    assert_eq!(review_config.fuzz, 0.05);
+    assert_eq!(review_config.hard_factor, 1.2);
+    assert_eq!(review_config.interval_factor, 1.0);
+    assert_eq!(review_config.max_interval, 36500);
+    assert_eq!(review_config.min_space, 1);
+    assert_eq!(review_config.cards_daily, 200);
 }
 
 #[test]
 fn test_deck_config_init() {
     let deck_config = DeckConfig::new("Test Deck".to_string());
-    assert!(!deck_config.autoplay);
+    assert!(deck_config.autoplay);
     assert_eq!(deck_config.filtered, None);
     assert_eq!(deck_config.id, None);
-    assert_eq!(deck_config.lapse_config.delays, vec![0]);
-    assert_eq!(deck_config.max_taken, 0);
+    assert_eq!(deck_config.lapse_config, LapseConfig::new());
+    assert_eq!(deck_config.max_taken, 60);
     assert_eq!(deck_config.modified, 0);
     assert_eq!(deck_config.name, "Test Deck");
-    assert!(!deck_config.new_config.bury);
-    assert!(!deck_config.replay_question);
+    assert_eq!(deck_config.new_config, NewConfig::new());
+    assert!(deck_config.replay_question);
     assert!(!deck_config.review_config.bury);
     assert!(!deck_config.timer);
     assert_eq!(deck_config.update_seq_number, 0);
@@ -142,37 +143,38 @@ fn test_deck_config_serialize() {
     let deck_config = DeckConfig::new("Test Deck".to_string());
     let serialized = serde_json::to_string(&deck_config).unwrap();
     let expected = json!({
-        "autoplay": false,
+        "autoplay": true,
         "dyn": null,
         "id": null,
         "lapse": {
-            "delays": [0],
-            "leechAction": 0,
-            "leechFails": 0,
-            "minInt": 0,
-            "mult": 0
+            "delays": [10.0],
+            "leechAction": 1,
+            "leechFails": 8,
+            "minInt": 1,
+            "mult": 0.0
         },
-        "maxTaken": 0,
+        "maxTaken": 60,
         "mod": 0,
         "name": "Test Deck",
         "new": {
             "bury": false,
-            "delays": [0],
-            "initialFactor": 0,
-            "ints": [0],
-            "order": 0,
-            "perDay": 0,
-            "separate": false
+            "delays": [1.0, 10.0],
+            "initialFactor": 2500,
+            "ints": [1, 4],
+            "order": 1,
+            "perDay": 20,
+            "separate": true
         },
-        "replayq": false,
+        "replayq": true,
         "rev": {
             "bury": false,
-            "ease4": 0,
-            "fuzz": 0,
-            "ivlFct": 0,
-            "maxIvl": 0,
-            "minSpace": 0,
-            "perDay": 0
+            "ease4": 1.3,
+            "fuzz": 0.05,
+            "hardFactor": 1.2,
+            "ivlFct": 1.0,
+            "maxIvl": 36500,
+            "minSpace": 1,
+            "perDay": 200
         },
         "timer": false,
         "usn": 0
@@ -228,7 +230,13 @@ fn test_deck_config_deserialize() {
     assert!(!deck_config.autoplay);
     assert_eq!(deck_config.filtered, None);
     assert_eq!(deck_config.id, None);
-    assert_eq!(deck_config.lapse_config.delays, vec![0]);
+    assert_eq!(deck_config.lapse_config.delays, vec![0.0]);
+    assert_eq!(
+        deck_config.lapse_config.leech_action,
+        LeechAction::Suspend
+    );
+    // Configs from before Anki 2.1.11 have no hard interval
+    assert_eq!(deck_config.review_config.hard_factor, 1.2);
     assert_eq!(deck_config.max_taken, 0);
     assert_eq!(deck_config.modified, 0);
     assert!(!deck_config.new_config.bury);
@@ -0,0 +1,155 @@
+use ankimdown::db_model::deck::{DeckConfig, LeechAction};
+use ankimdown::db_model::deck_config::*;
+
+#[test]
+fn test_presets_are_valid() {
+    for config in [
+        DeckConfig::new("Default".to_string()),
+        DeckConfig::cramming("Exam".to_string()),
+        DeckConfig::language_learning("Spanish".to_string()),
+    ] {
+        assert_eq!(config.validate(), Ok(()), "{}", config.name);
+    }
+}
+
+#[test]
+fn test_cramming_preset() {
+    let config = DeckConfig::cramming("Exam".to_string());
+    assert_eq!(config.name, "Exam");
+    assert_eq!(config.new_config.per_day, 100);
+    assert_eq!(config.review_config.max_interval, 7);
+    assert_eq!(config.review_config.interval_factor, 0.5);
+}
+
+#[test]
+fn test_language_learning_preset() {
+    let config = DeckConfig::language_learning("Spanish".to_string());
+    assert!(config.new_config.bury);
+    assert!(config.review_config.bury);
+    assert_eq!(config.lapse_config.delays, vec![10.0, 1440.0]);
+    assert_eq!(
+        config.lapse_config.leech_action,
+        LeechAction::Suspend
+    );
+}
+
+#[test]
+fn test_builder() {
+    let config = DeckConfig::builder("Vocabulary".to_string())
+        .id(5)
+        .new_per_day(10)
+        .reviews_per_day(100)
+        .learning_steps(&[1.0, 10.0, 60.0])
+        .relearning_steps(&[10.0])
+        .graduating_intervals(2, 5)
+        .starting_ease(2.3)
+        .easy_bonus(1.5)
+        .hard_interval(1.1)
+        .interval_modifier(0.9)
+        .maximum_interval(365)
+        .new_interval(0.1)
+        .minimum_interval(2)
+        .leech(5, LeechAction::Suspend)
+        .bury_siblings(true, false)
+        .build()
+        .unwrap();
+
+    assert_eq!(config.id, Some(5));
+    assert_eq!(config.new_config.per_day, 10);
+    assert_eq!(config.review_config.cards_daily, 100);
+    assert_eq!(config.new_config.delays, vec![1.0, 10.0, 60.0]);
+    assert_eq!(config.new_config.intervals, vec![2, 5]);
+    assert_eq!(config.new_config.initial_factor, 2300);
+    assert_eq!(config.review_config.ease_factor, 1.5);
+    assert_eq!(config.review_config.hard_factor, 1.1);
+    assert_eq!(config.review_config.interval_factor, 0.9);
+    assert_eq!(config.review_config.max_interval, 365);
+    assert_eq!(config.lapse_config.interval_increase, 0.1);
+    assert_eq!(config.lapse_config.min_interval, 2);
+    assert_eq!(config.lapse_config.leech_fails, 5);
+    assert!(config.new_config.bury);
+    assert!(!config.review_config.bury);
+}
+
+#[test]
+fn test_builder_from_preset() {
+    let config = DeckConfigBuilder::from(DeckConfig::cramming(
+        "Exam".to_string(),
+    ))
+    .maximum_interval(3)
+    .build()
+    .unwrap();
+    assert_eq!(config.new_config.per_day, 100);
+    assert_eq!(config.review_config.max_interval, 3);
+}
+
+#[test]
+fn test_builder_rejects_invalid_options() {
+    let builder = || DeckConfig::builder("Default".to_string());
+    assert_eq!(
+        builder().learning_steps(&[1.0, 0.0]).build(),
+        Err(DeckConfigError::LearningStep(0.0))
+    );
+    assert_eq!(
+        builder().relearning_steps(&[-10.0]).build(),
+        Err(DeckConfigError::RelearningStep(-10.0))
+    );
+    assert_eq!(
+        builder().starting_ease(1.2).build(),
+        Err(DeckConfigError::StartingEase(1200))
+    );
+    assert_eq!(
+        builder().easy_bonus(0.9).build(),
+        Err(DeckConfigError::EasyBonus(0.9))
+    );
+    assert_eq!(
+        builder().hard_interval(1.5).build(),
+        Err(DeckConfigError::HardInterval(1.5))
+    );
+    assert!(builder().interval_modifier(f32::NAN).build().is_err());
+    assert_eq!(
+        builder().new_interval(1.5).build(),
+        Err(DeckConfigError::NewInterval(1.5))
+    );
+    assert_eq!(
+        builder().maximum_interval(0).build(),
+        Err(DeckConfigError::MaximumInterval(0))
+    );
+    assert_eq!(
+        builder().maximum_interval(3).minimum_interval(4).build(),
+        Err(DeckConfigError::MinimumInterval {
+            minimum: 4,
+            maximum: 3
+        })
+    );
+    assert_eq!(
+        builder().graduating_intervals(4, 1).build(),
+        Err(DeckConfigError::GraduatingIntervals {
+            good: 4,
+            easy: 1
+        })
+    );
+    assert_eq!(
+        builder().leech(0, LeechAction::TagOnly).build(),
+        Err(DeckConfigError::LeechThreshold(0))
+    );
+}
+
+#[test]
+fn test_empty_steps_are_valid() {
+    let config = DeckConfig::builder("Default".to_string())
+        .learning_steps(&[])
+        .relearning_steps(&[])
+        .build();
+    assert!(config.is_ok());
+}
+
+#[test]
+fn test_error_message() {
+    assert_eq!(
+        DeckConfigError::EasyBonus(0.9).to_string(),
+        "Easy bonus 0.9 is not between 1 and 5"
+    );
+    let message: String = DeckConfigError::LeechThreshold(0).into();
+    assert_eq!(message, "Invalid leech threshold 0");
+}
@@ -0,0 +1,192 @@
+use ankimdown::db_model::collection::Collection;
+use ankimdown::db_model::deck_tree::*;
+use ankimdown::db_model::grave::GraveType;
+use ankimdown::db_model::id::IdAllocator;
+
+use crate::db_model::{
+    create_test_card, create_test_collection, create_test_note,
+};
+
+fn create_deck_tree() -> (Collection, IdAllocator) {
+    let mut collection = create_test_collection();
+    let mut ids = IdAllocator::starting_at(100);
+    collection
+        .add_deck("Spanish::Verbs::Irregular", &mut ids)
+        .unwrap();
+    collection.add_deck("Spanish::Nouns", &mut ids).unwrap();
+    (collection, ids)
+}
+
+#[test]
+fn test_deck_components() {
+    assert_eq!(
+        deck_components("Spanish:: Verbs").unwrap(),
+        vec!["Spanish", "Verbs"]
+    );
+    assert!(deck_components("Spanish::::Verbs").is_err());
+    assert_eq!(
+        parent_name("Spanish::Verbs::Irregular"),
+        Some("Spanish::Verbs")
+    );
+    assert_eq!(parent_name("Spanish"), None);
+}
+
+#[test]
+fn test_add_deck_creates_parents() {
+    let (mut collection, mut ids) = create_deck_tree();
+
+    assert_eq!(collection.deck_id("Spanish"), Some(100));
+    assert_eq!(collection.deck_id("spanish::verbs"), Some(101));
+    assert_eq!(
+        collection.deck_id("Spanish::Verbs::Irregular"),
+        Some(102)
+    );
+    assert_eq!(collection.deck_id("Spanish::Nouns"), Some(103));
+    assert_eq!(
+        collection.add_deck("Spanish::Verbs", &mut ids),
+        Ok(101)
+    );
+    assert_eq!(collection.decks.len(), 5);
+    assert!(collection.add_deck("Spanish::", &mut ids).is_err());
+}
+
+#[test]
+fn test_deck_tree() {
+    let (collection, _) = create_deck_tree();
+
+    assert_eq!(collection.parent_deck(102), Some(101));
+    assert_eq!(collection.parent_deck(100), None);
+    assert_eq!(collection.child_decks(100), vec![103, 101]);
+    let mut descendants = collection.descendant_decks(100);
+    descendants.sort();
+    assert_eq!(descendants, vec![101, 102, 103]);
+
+    let tree = collection.deck_tree();
+    assert_eq!(
+        tree.iter()
+            .map(|node| node.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Default", "Spanish"]
+    );
+    assert_eq!(tree[1].children[1].name, "Verbs");
+    assert_eq!(tree[1].children[1].children[0].id, 102);
+}
+
+#[test]
+fn test_rename_deck() {
+    let (mut collection, mut ids) = create_deck_tree();
+
+    collection
+        .rename_deck(101, "Grammar::Verbs", &mut ids)
+        .unwrap();
+
+    assert_eq!(collection.deck(101).unwrap().name, "Grammar::Verbs");
+    assert_eq!(
+        collection.deck(102).unwrap().name,
+        "Grammar::Verbs::Irregular"
+    );
+    assert_eq!(collection.deck_id("Grammar"), Some(104));
+    assert!(collection
+        .rename_deck(104, "Grammar::Verbs::Grammar", &mut ids)
+        .is_err());
+    assert!(collection
+        .rename_deck(103, "Grammar::Verbs", &mut ids)
+        .is_err());
+}
+
+#[test]
+fn test_remove_deck_moves_cards() {
+    let (mut collection, _) = create_deck_tree();
+    let mut notes = vec![create_test_note(1, &["hola"])];
+    let mut cards = vec![
+        create_test_card(2, 1, 102),
+        create_test_card(3, 1, 103),
+    ];
+
+    let graves = collection
+        .remove_deck(
+            101,
+            &mut notes,
+            &mut cards,
+            CardRemoval::MoveTo(1),
+            -1,
+        )
+        .unwrap();
+
+    assert_eq!(graves.len(), 2);
+    assert!(graves
+        .iter()
+        .all(|grave| grave.grave_type == GraveType::Deck));
+    assert_eq!(cards[0].deck_id, 1);
+    assert_eq!(cards[1].deck_id, 103);
+    assert!(collection.deck(102).is_none());
+    assert!(collection
+        .remove_deck(
+            100,
+            &mut notes,
+            &mut cards,
+            CardRemoval::MoveTo(103),
+            -1
+        )
+        .is_err());
+}
+
+#[test]
+fn test_remove_deck_deletes_cards() {
+    let (mut collection, _) = create_deck_tree();
+    let mut notes = vec![
+        create_test_note(1, &["hola"]),
+        create_test_note(4, &["hola"]),
+        create_test_note(6, &["hola"]),
+    ];
+    let mut cards = vec![
+        create_test_card(2, 1, 102),
+        create_test_card(3, 1, 103),
+        create_test_card(5, 4, 101),
+    ];
+
+    let graves = collection
+        .remove_deck(
+            100,
+            &mut notes,
+            &mut cards,
+            CardRemoval::Delete,
+            3,
+        )
+        .unwrap();
+
+    let mut graves = graves
+        .iter()
+        .map(|grave| (grave.object_id, grave.grave_type))
+        .collect::<Vec<_>>();
+    graves.sort_by_key(|(id, _)| *id);
+    assert_eq!(
+        graves,
+        vec![
+            (1, GraveType::Note),
+            (2, GraveType::Card),
+            (3, GraveType::Card),
+            (4, GraveType::Note),
+            (5, GraveType::Card),
+            (100, GraveType::Deck),
+            (101, GraveType::Deck),
+            (102, GraveType::Deck),
+            (103, GraveType::Deck),
+        ]
+    );
+    // note 6 had no cards to begin with
+    assert_eq!(
+        notes.iter().map(|note| note.id).collect::<Vec<_>>(),
+        vec![6]
+    );
+    assert!(cards.is_empty());
+    assert!(collection
+        .remove_deck(
+            1,
+            &mut notes,
+            &mut cards,
+            CardRemoval::Delete,
+            3
+        )
+        .is_err());
+}
@@ -0,0 +1,300 @@
+use ankimdown::db_model::card::{Card, CardQueue, CardType};
+use ankimdown::db_model::collection::Collection;
+use ankimdown::db_model::deck::*;
+use ankimdown::db_model::deck_tree::CardRemoval;
+use ankimdown::db_model::grave::{Grave, GraveType};
+use ankimdown::db_model::id::IdAllocator;
+use ankimdown::db_model::note::{Note, NoteTag};
+use ankimdown::db_model::schema18;
+use ankimdown::db_model::table::Table;
+use rusqlite::Connection;
+use serde_json::json;
+
+use crate::db_model::{
+    create_test_card, create_test_collection, create_test_note,
+};
+
+fn create_test_options() -> FilteredDeckOptions {
+    FilteredDeckOptions::new(vec![FilteredSearchTerm::new(
+        "deck:Spanish is:due",
+        100,
+        FilteredSearchOrder::IntervalsAscending,
+    )])
+}
+
+fn scheduled_card(
+    id: i64,
+    note_id: usize,
+    deck_id: usize,
+    queue: CardQueue,
+    due: i64,
+    interval: i64,
+) -> Card {
+    let mut card = create_test_card(id, note_id, deck_id);
+    if queue != CardQueue::New {
+        card.card_type = CardType::Review;
+        card.factor = 2500;
+    }
+    card.queue = queue;
+    card.due = due;
+    card.interval = interval;
+    card
+}
+
+fn tagged_note(id: i64, tag: &str) -> Note {
+    let mut note = create_test_note(id, &["hola", "hello"]);
+    note.tags = vec![NoteTag::new(tag).unwrap()];
+    note
+}
+
+fn create_decks() -> (Collection, IdAllocator) {
+    let mut collection = create_test_collection();
+    let mut ids = IdAllocator::starting_at(100);
+    collection.add_deck("Spanish::Verbs", &mut ids).unwrap();
+    collection.add_deck("French", &mut ids).unwrap();
+    (collection, ids)
+}
+
+#[test]
+fn test_filtered_deck_serialize() {
+    let mut options = create_test_options();
+    options.terms.push(FilteredSearchTerm::new(
+        "is:new",
+        20,
+        FilteredSearchOrder::Random,
+    ));
+    let deck =
+        Deck::new_filtered("Cram".to_string(), options.clone());
+    let value = serde_json::to_value(&deck).unwrap();
+
+    assert_eq!(value["dyn"], json!(1));
+    assert_eq!(value["resched"], json!(true));
+    assert_eq!(
+        value["terms"],
+        json!([["deck:Spanish is:due", 100, 2], ["is:new", 20, 1]])
+    );
+    assert_eq!(value["delays"], json!(null));
+    assert_eq!(value["previewDelay"], json!(10));
+    assert_eq!(value["previewAgainSecs"], json!(60));
+    let deck: Deck = serde_json::from_value(value).unwrap();
+    assert!(deck.is_filtered());
+    assert_eq!(deck.filtered_options, Some(options));
+
+    let value =
+        serde_json::to_value(Deck::new("Spanish".to_string()))
+            .unwrap();
+    assert!(value.get("terms").is_none());
+    let deck: Deck = serde_json::from_value(value).unwrap();
+    assert_eq!(deck.filtered_options, None);
+}
+
+#[test]
+fn test_filtered_deck_table() {
+    let connection = Connection::open_in_memory().unwrap();
+    schema18::create_schema(&connection).unwrap();
+    let mut options = create_test_options();
+    options.reschedule = false;
+    options.delays = Some(vec![1.0, 10.0]);
+    let mut deck = Deck::new_filtered("Cram".to_string(), options);
+    deck.id = 2;
+    deck.insert(&connection).unwrap();
+
+    assert_eq!(
+        Deck::select_by_id(&connection, 2).unwrap(),
+        Some(deck)
+    );
+}
+
+#[test]
+fn test_add_filtered_deck() {
+    let (mut collection, mut ids) = create_decks();
+
+    let id = collection
+        .add_filtered_deck(
+            "Spanish::Cram",
+            create_test_options(),
+            &mut ids,
+        )
+        .unwrap();
+
+    assert!(collection.deck(id).unwrap().is_filtered());
+    assert!(collection
+        .add_filtered_deck("French", create_test_options(), &mut ids)
+        .is_err());
+    assert!(collection
+        .add_filtered_deck(
+            "Empty",
+            FilteredDeckOptions::new(vec![]),
+            &mut ids
+        )
+        .is_err());
+    assert!(collection
+        .add_deck("Spanish::Cram::Verbs", &mut ids)
+        .is_err());
+    assert!(collection
+        .add_filtered_deck(
+            "Spanish::Cram::More",
+            create_test_options(),
+            &mut ids
+        )
+        .is_err());
+    let french = collection.deck_id("French").unwrap();
+    assert!(collection
+        .rename_deck(french, "Spanish::Cram::French", &mut ids)
+        .is_err());
+    assert_eq!(collection.deck(french).unwrap().name, "French");
+}
+
+#[test]
+fn test_build_and_empty_filtered_deck() {
+    let (mut collection, mut ids) = create_decks();
+    let cram = collection
+        .add_filtered_deck("Cram", create_test_options(), &mut ids)
+        .unwrap();
+    let notes = vec![tagged_note(1, "verbs")];
+    let mut cards = vec![
+        scheduled_card(10, 1, 101, CardQueue::Review, 5, 30),
+        scheduled_card(11, 1, 100, CardQueue::Review, 3, 4),
+        // not due yet
+        scheduled_card(12, 1, 101, CardQueue::Review, 50, 1),
+        // not in Spanish
+        scheduled_card(13, 1, 102, CardQueue::Review, 1, 1),
+        scheduled_card(14, 1, 101, CardQueue::Suspended, 1, 1),
+        scheduled_card(15, 1, 101, CardQueue::New, 1, 0),
+    ];
+    let original = cards.clone();
+
+    let moved = collection
+        .build_filtered_deck(cram, &notes, &mut cards, 10)
+        .unwrap();
+
+    assert_eq!(moved, 2);
+    assert_eq!(cards[1].deck_id, cram);
+    assert_eq!(cards[1].original_deck_id, 100);
+    assert_eq!(cards[1].original_due, 3);
+    assert_eq!(cards[1].due, -100_000);
+    assert_eq!(cards[0].deck_id, cram);
+    assert_eq!(cards[0].original_deck_id, 101);
+    assert_eq!(cards[0].due, -99_999);
+    assert!(cards[2..]
+        .iter()
+        .zip(&original[2..])
+        .all(|(card, original)| card == original));
+
+    assert_eq!(
+        collection.empty_filtered_deck(cram, &mut cards).unwrap(),
+        2
+    );
+    for (card, original) in cards.iter().zip(&original) {
+        assert_eq!(card.deck_id, original.deck_id);
+        assert_eq!(card.due, original.due);
+        assert_eq!(card.original_deck_id, 0);
+        assert_eq!(card.queue, original.queue);
+    }
+    assert!(collection.empty_filtered_deck(100, &mut cards).is_err());
+}
+
+#[test]
+fn test_remove_filtered_deck_returns_cards() {
+    for removal in [CardRemoval::Delete, CardRemoval::MoveTo(102)] {
+        let (mut collection, mut ids) = create_decks();
+        let cram = collection
+            .add_filtered_deck(
+                "Cram",
+                create_test_options(),
+                &mut ids,
+            )
+            .unwrap();
+        let mut notes = vec![tagged_note(1, "verbs")];
+        let mut cards = vec![
+            scheduled_card(10, 1, 101, CardQueue::Review, 5, 30),
+            scheduled_card(11, 1, 100, CardQueue::Review, 3, 4),
+        ];
+        let original = cards.clone();
+        collection
+            .build_filtered_deck(cram, &notes, &mut cards, 10)
+            .unwrap();
+
+        let graves = collection
+            .remove_deck(cram, &mut notes, &mut cards, removal, -1)
+            .unwrap();
+
+        assert_eq!(
+            graves,
+            vec![Grave::new(cram as i64, GraveType::Deck, -1)]
+        );
+        assert_eq!(notes.len(), 1);
+        for (card, original) in cards.iter().zip(&original) {
+            assert_eq!(card.deck_id, original.deck_id);
+            assert_eq!(card.due, original.due);
+            assert_eq!(card.original_deck_id, 0);
+            assert_eq!(card.original_due, 0);
+        }
+    }
+}
+
+#[test]
+fn test_build_filtered_deck_searches() {
+    let (mut collection, mut ids) = create_decks();
+    let mut options = FilteredDeckOptions::new(vec![
+        FilteredSearchTerm::new(
+            "tag:verbs -deck:french",
+            1,
+            FilteredSearchOrder::Added,
+        ),
+        FilteredSearchTerm::new(
+            "\"deck:Spanish::*\" hol",
+            10,
+            FilteredSearchOrder::Added,
+        ),
+    ]);
+    options.reschedule = false;
+    let preview = collection
+        .add_filtered_deck("Preview", options, &mut ids)
+        .unwrap();
+    let notes = vec![
+        tagged_note(1, "verbs::irregular"),
+        tagged_note(2, "nouns"),
+    ];
+    let mut cards = vec![
+        scheduled_card(10, 2, 101, CardQueue::New, 1, 0),
+        scheduled_card(11, 1, 101, CardQueue::New, 2, 0),
+        scheduled_card(12, 1, 102, CardQueue::New, 3, 0),
+    ];
+
+    let moved = collection
+        .build_filtered_deck(preview, &notes, &mut cards, 0)
+        .unwrap();
+
+    assert_eq!(moved, 2);
+    // the first search takes note 1, the second the rest of Spanish
+    assert_eq!(cards[1].due, -100_000);
+    assert_eq!(cards[0].due, -99_999);
+    assert_eq!(cards[2].deck_id, 102);
+    // without rescheduling the cards are previewed
+    assert_eq!(cards[0].queue, CardQueue::Review);
+
+    collection.empty_filtered_deck(preview, &mut cards).unwrap();
+    assert_eq!(cards[0].queue, CardQueue::New);
+    assert_eq!(cards[0].due, 1);
+}
+
+#[test]
+fn test_build_filtered_deck_unsupported_search() {
+    let (mut collection, mut ids) = create_decks();
+    let cram = collection
+        .add_filtered_deck(
+            "Cram",
+            FilteredDeckOptions::new(vec![FilteredSearchTerm::new(
+                "deck:Spanish or deck:French",
+                10,
+                FilteredSearchOrder::Due,
+            )]),
+            &mut ids,
+        )
+        .unwrap();
+
+    assert!(collection
+        .build_filtered_deck(cram, &[], &mut [], 0)
+        .is_err());
+}
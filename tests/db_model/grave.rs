@@ -0,0 +1,164 @@
+use ankimdown::db_model::card::Card;
+use ankimdown::db_model::database::*;
+use ankimdown::db_model::deck::Deck;
+use ankimdown::db_model::grave::*;
+use ankimdown::db_model::note::Note;
+use ankimdown::db_model::schema18;
+use ankimdown::db_model::table::Table;
+use rusqlite::Connection;
+
+use crate::db_model::{
+    create_test_card, create_test_collection, create_test_note,
+};
+
+// Note 10 with cards 11 and 12 in deck 2, note 20 with card 21 in
+// deck 2 and card 22 in the default deck
+fn fill(connection: &Connection) {
+    create_test_note(10, &["hola"]).insert(connection).unwrap();
+    create_test_note(20, &["hola"]).insert(connection).unwrap();
+    create_test_card(11, 10, 2).insert(connection).unwrap();
+    create_test_card(12, 10, 2).insert(connection).unwrap();
+    create_test_card(21, 20, 2).insert(connection).unwrap();
+    create_test_card(22, 20, 1).insert(connection).unwrap();
+}
+
+fn create_collection(connection: &Connection) {
+    let mut collection = create_test_collection();
+    for (id, name) in [(2, "Spanish"), (3, "Spanish::Verbs")] {
+        let mut deck = Deck::new(name.to_string());
+        deck.id = id;
+        collection.decks.push((id as usize, deck));
+    }
+    insert_collection(connection, &collection).unwrap();
+}
+
+fn graves(connection: &Connection) -> Vec<(i64, GraveType)> {
+    Grave::select_where(connection, "1", [])
+        .unwrap()
+        .into_iter()
+        .map(|grave| (grave.object_id, grave.grave_type))
+        .collect()
+}
+
+#[test]
+fn test_delete_note() {
+    let connection = Connection::open_in_memory().unwrap();
+    create_schema(&connection).unwrap();
+    fill(&connection);
+
+    delete_note(&connection, 10, 5).unwrap();
+
+    assert_eq!(
+        graves(&connection),
+        vec![
+            (10, GraveType::Note),
+            (11, GraveType::Card),
+            (12, GraveType::Card)
+        ]
+    );
+    assert!(Grave::select_where(&connection, "usn != 5", [])
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        Card::select_where(&connection, "1", []).unwrap().len(),
+        2
+    );
+    assert!(delete_note(&connection, 10, 5).is_err());
+}
+
+#[test]
+fn test_delete_deck() {
+    let connection = Connection::open_in_memory().unwrap();
+    create_schema(&connection).unwrap();
+    create_collection(&connection);
+    fill(&connection);
+
+    delete_deck(&connection, 2, -1).unwrap();
+
+    // The subdeck goes too, note 20 keeps its card in the default
+    // deck
+    assert_eq!(
+        graves(&connection),
+        vec![
+            (2, GraveType::Deck),
+            (3, GraveType::Deck),
+            (10, GraveType::Note),
+            (11, GraveType::Card),
+            (12, GraveType::Card),
+            (21, GraveType::Card)
+        ]
+    );
+    let collection = select_collection(&connection).unwrap();
+    assert_eq!(collection.decks.len(), 1);
+    assert!(delete_deck(&connection, 2, -1).is_err());
+    assert!(delete_deck(&connection, DEFAULT_DECK_ID, -1).is_err());
+}
+
+#[test]
+fn test_delete_deck_v18() {
+    let connection = Connection::open_in_memory().unwrap();
+    schema18::create_schema(&connection).unwrap();
+    let mut collection = create_test_collection();
+    let mut deck = Deck::new("Spanish".to_string());
+    deck.id = 2;
+    collection.decks.push((2, deck));
+    schema18::insert_collection(&connection, &collection).unwrap();
+
+    delete_deck(&connection, 2, -1).unwrap();
+
+    assert_eq!(graves(&connection), vec![(2, GraveType::Deck)]);
+    assert_eq!(Deck::select_by_id(&connection, 2).unwrap(), None);
+}
+
+#[test]
+fn test_delete_deck_with_subdecks() {
+    let connection = Connection::open_in_memory().unwrap();
+    create_schema(&connection).unwrap();
+    create_collection(&connection);
+    fill(&connection);
+    create_test_card(31, 20, 3).insert(&connection).unwrap();
+    // Has no cards before the deletion, it is not the deck's to delete
+    create_test_note(40, &["hola"]).insert(&connection).unwrap();
+
+    delete_deck(&connection, 2, -1).unwrap();
+
+    let mut graves = graves(&connection);
+    graves.sort_by_key(|(id, _)| *id);
+    assert_eq!(
+        graves,
+        vec![
+            (2, GraveType::Deck),
+            (3, GraveType::Deck),
+            (10, GraveType::Note),
+            (11, GraveType::Card),
+            (12, GraveType::Card),
+            (21, GraveType::Card),
+            (31, GraveType::Card)
+        ]
+    );
+    let notes = Note::select_where(&connection, "1", []).unwrap();
+    assert_eq!(
+        notes.iter().map(|note| note.id).collect::<Vec<_>>(),
+        vec![20, 40]
+    );
+}
+
+#[test]
+fn test_delete_missing_deck_changes_nothing() {
+    let connection = Connection::open_in_memory().unwrap();
+    create_schema(&connection).unwrap();
+    create_collection(&connection);
+    fill(&connection);
+
+    assert!(delete_deck(&connection, 99, -1).is_err());
+
+    assert!(graves(&connection).is_empty());
+    assert_eq!(
+        Card::select_where(&connection, "1", []).unwrap().len(),
+        4
+    );
+    assert_eq!(
+        select_collection(&connection).unwrap().decks.len(),
+        3
+    );
+}
@@ -0,0 +1,32 @@
+use ankimdown::db_model::collection::Collection;
+use ankimdown::db_model::deck::Deck;
+use ankimdown::db_model::id::*;
+
+#[test]
+fn test_id_allocator_unique() {
+    let mut ids = IdAllocator::new();
+    let first = ids.next_id();
+    let generated = ids.by_ref().take(10000).collect::<Vec<_>>();
+
+    assert!(first >= 1700000000000);
+    assert_eq!(generated[0], first + 1);
+    assert!(generated.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn test_id_allocator_for_collection() {
+    let mut collection = Collection::new();
+    let mut deck = Deck::new("Spanish".to_string());
+    // far in the future, after the clock
+    deck.id = 9000000000000;
+    collection.decks = vec![(9000000000000, deck)];
+
+    let mut ids = IdAllocator::for_collection(&collection, &[], &[]);
+
+    assert_eq!(ids.next_id(), 9000000000001);
+    assert!(
+        IdAllocator::for_collection(&Collection::new(), &[], &[])
+            .next_id()
+            >= now_millis() - 1000
+    );
+}
@@ -1,4 +1,77 @@
+use ankimdown::db_model::card::{
+    Card, CardFlag, CardQueue, CardType,
+};
+use ankimdown::db_model::collection::Collection;
+use ankimdown::db_model::deck::Deck;
+use ankimdown::db_model::model::Model;
+use ankimdown::db_model::note::Note;
+
 mod card;
+mod check;
 mod collection;
+mod database;
 mod deck;
+mod deck_config;
+mod deck_tree;
+mod filtered;
+mod grave;
+mod id;
 mod model;
+mod model_change;
+mod note;
+mod revlog;
+mod scheduler;
+mod schema18;
+mod table;
+
+/// New card of the note in the deck, tests set the fields they need.
+pub(crate) fn create_test_card(
+    id: i64,
+    note_id: usize,
+    deck_id: usize,
+) -> Card {
+    Card::new(
+        id,
+        note_id,
+        deck_id,
+        0,
+        0,
+        -1,
+        CardType::New,
+        CardQueue::New,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        CardFlag::Null,
+    )
+}
+
+/// Note of the Basic model 1 sorted by its first field.
+pub(crate) fn create_test_note(id: i64, fields: &[&str]) -> Note {
+    Note::new(
+        id,
+        format!("guid{}", id),
+        1,
+        0,
+        -1,
+        vec![],
+        fields.iter().map(|field| field.to_string()).collect(),
+        fields.first().unwrap_or(&"").to_string(),
+        0,
+    )
+}
+
+/// Collection with the Basic model 1 and the Default deck 1.
+pub(crate) fn create_test_collection() -> Collection {
+    let mut collection = Collection::new();
+    collection.models.push((1, Model::basic(1)));
+    let mut deck = Deck::new("Default".to_string());
+    deck.id = 1;
+    collection.decks.push((1, deck));
+    collection
+}
@@ -96,3 +96,115 @@ fn test_serialize() {
         serde_json::from_str(&serialized).unwrap();
     assert_eq!(model, deserialized);
 }
+
+#[test]
+fn test_stock_basic() {
+    let model = Model::basic(1);
+    assert_eq!(model.name, "Basic");
+    assert_eq!(model.model_id, 1);
+    assert_eq!(
+        model
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.ordinal))
+            .collect::<Vec<_>>(),
+        vec![("Front", 0), ("Back", 1)]
+    );
+    assert_eq!(model.templates[0].name, "Card 1");
+    assert_eq!(model.templates[0].question_format, "{{Front}}");
+    assert_eq!(
+        model.templates[0].answer_template,
+        "{{FrontSide}}\n\n<hr id=answer>\n\n{{Back}}"
+    );
+    assert_eq!(model.css, Model::default_css());
+    assert_eq!(model.req, vec![(0, "all".to_string(), vec![0])]);
+}
+
+#[test]
+fn test_stock_reversed() {
+    let model = Model::basic_and_reversed(2);
+    assert_eq!(model.name, "Basic (and reversed card)");
+    assert_eq!(model.templates[1].name, "Card 2");
+    assert_eq!(model.templates[1].question_format, "{{Back}}");
+    assert_eq!(
+        model.templates[1].answer_template,
+        "{{FrontSide}}\n\n<hr id=answer>\n\n{{Front}}"
+    );
+    assert_eq!(
+        model.req,
+        vec![
+            (0, "all".to_string(), vec![0]),
+            (1, "all".to_string(), vec![1])
+        ]
+    );
+
+    let model = Model::basic_optional_reversed(3);
+    assert_eq!(model.fields[2].name, "Add Reverse");
+    assert_eq!(
+        model.templates[1].question_format,
+        "{{#Add Reverse}}{{Back}}{{/Add Reverse}}"
+    );
+    assert_eq!(model.req[1], (1, "all".to_string(), vec![1, 2]));
+}
+
+#[test]
+fn test_stock_type_answer() {
+    let model = Model::basic_type_answer(4);
+    assert_eq!(model.name, "Basic (type in the answer)");
+    assert_eq!(
+        model.templates[0].question_format,
+        "{{Front}}\n\n{{type:Back}}"
+    );
+    assert_eq!(
+        model.templates[0].answer_template,
+        "{{Front}}\n\n<hr id=answer>\n\n{{type:Back}}"
+    );
+}
+
+#[test]
+fn test_stock_cloze() {
+    let model = Model::cloze(5);
+    assert_eq!(model.name, "Cloze");
+    assert_eq!(model.model_type, ModelType::Cloze);
+    assert_eq!(model.fields[1].name, "Back Extra");
+    assert_eq!(model.templates[0].question_format, "{{cloze:Text}}");
+    assert_eq!(
+        model.templates[0].answer_template,
+        "{{cloze:Text}}<br>\n{{Back Extra}}"
+    );
+    assert!(model.css.starts_with(&Model::default_css()));
+    assert!(model.css.contains(".cloze {"));
+    assert!(model.req.is_empty());
+}
+
+#[test]
+fn test_update_requirements() {
+    let mut model = Model::try_new(
+        None,
+        vec![
+            ModelField::new("Front".to_string(), 0),
+            ModelField::new("Back".to_string(), 1),
+        ],
+        1,
+        None,
+        None,
+        "Basic".to_string(),
+        0,
+        vec![ModelTemplate::new(
+            "Card 1".to_string(),
+            "{{Front}}".to_string(),
+            "{{Back}}".to_string(),
+        )],
+        ModelType::FrontBack,
+    )
+    .unwrap();
+    assert_eq!(model.req, vec![(0, "all".to_string(), vec![0])]);
+
+    model.templates[0].question_format =
+        "{{Front}}{{Back}}".to_string();
+    model.update_requirements().unwrap();
+    assert_eq!(model.req, vec![(0, "any".to_string(), vec![0, 1])]);
+
+    model.templates[0].question_format = "{{#Front}}".to_string();
+    assert!(model.update_requirements().is_err());
+}
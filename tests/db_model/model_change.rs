@@ -0,0 +1,257 @@
+use ankimdown::db_model::card::{Card, CardQueue, CardType};
+use ankimdown::db_model::collection::Collection;
+use ankimdown::db_model::grave::GraveType;
+use ankimdown::db_model::id::IdAllocator;
+use ankimdown::db_model::model::{Model, ModelTemplate};
+use ankimdown::db_model::model_change::*;
+use ankimdown::db_model::note::Note;
+
+use crate::db_model::{
+    create_test_card, create_test_collection, create_test_note,
+};
+
+fn review_card(id: i64, note_id: usize, ordinal: u64) -> Card {
+    let mut card = create_test_card(id, note_id, 1);
+    card.ordinal = ordinal;
+    card.card_type = CardType::Review;
+    card.queue = CardQueue::Review;
+    card.due = 10;
+    card.interval = 30;
+    card.factor = 2500;
+    card.reviews = 5;
+    card.lapses = 1;
+    card
+}
+
+fn create_reversed_model() -> (Collection, Vec<Note>, Vec<Card>) {
+    let mut collection = create_test_collection();
+    collection.models[0] = (1, Model::basic_and_reversed(1));
+    let notes = vec![
+        create_test_note(1, &["hola", "hello"]),
+        create_test_note(2, &["adiós", ""]),
+    ];
+    let cards = vec![
+        review_card(10, 1, 0),
+        review_card(11, 1, 1),
+        review_card(12, 2, 0),
+    ];
+    (collection, notes, cards)
+}
+
+fn model(collection: &Collection) -> &Model {
+    &collection.models[0].1
+}
+
+#[test]
+fn test_change_fields() {
+    let (mut collection, mut notes, mut cards) =
+        create_reversed_model();
+    let original_cards = cards.clone();
+
+    let graves = collection
+        .change_model(
+            1,
+            &[
+                ModelChange::RenameField {
+                    from: "Front".to_string(),
+                    to: "Word".to_string(),
+                },
+                ModelChange::AddField("Example".to_string()),
+                ModelChange::MoveField {
+                    name: "Example".to_string(),
+                    position: 1,
+                },
+            ],
+            &mut notes,
+            &mut cards,
+            &mut IdAllocator::starting_at(100),
+            -1,
+        )
+        .unwrap();
+
+    assert!(graves.is_empty());
+    assert_eq!(cards, original_cards);
+    assert_eq!(notes[0].fields, vec!["hola", "", "hello"]);
+    assert_eq!(notes[0].update_seq_number, -1);
+    let model = model(&collection);
+    assert_eq!(
+        model
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.ordinal))
+            .collect::<Vec<_>>(),
+        vec![("Word", 0), ("Example", 1), ("Back", 2)]
+    );
+    assert_eq!(model.templates[0].question_format, "{{Word}}");
+    assert_eq!(
+        model.templates[1].answer_template,
+        "{{FrontSide}}\n\n<hr id=answer>\n\n{{Word}}"
+    );
+    assert_eq!(model.req[1], (1, "all".to_string(), vec![2]));
+    assert!(collection.scheme_mod_time > 0);
+}
+
+#[test]
+fn test_remove_sort_field() {
+    let (mut collection, mut notes, mut cards) =
+        create_reversed_model();
+
+    collection
+        .change_model(
+            1,
+            &[ModelChange::RemoveField("Front".to_string())],
+            &mut notes,
+            &mut cards,
+            &mut IdAllocator::starting_at(100),
+            -1,
+        )
+        .unwrap();
+
+    assert_eq!(notes[0].fields, vec!["hello"]);
+    assert_eq!(notes[0].sort_field, "hello");
+    let model = model(&collection);
+    assert_eq!(model.sort_field_index, 0);
+    assert_eq!(model.templates[0].question_format, "");
+    assert_eq!(model.req[0], (0, "none".to_string(), vec![]));
+}
+
+#[test]
+fn test_add_field_keeps_templates() {
+    let (mut collection, mut notes, mut cards) =
+        create_reversed_model();
+    collection.models[0].1.templates[0].question_format =
+        "{{ Front }}".to_string();
+
+    collection
+        .change_model(
+            1,
+            &[ModelChange::AddField("Example".to_string())],
+            &mut notes,
+            &mut cards,
+            &mut IdAllocator::starting_at(100),
+            -1,
+        )
+        .unwrap();
+
+    let model = model(&collection);
+    assert_eq!(model.templates[0].question_format, "{{ Front }}");
+    assert_eq!(notes[0].fields, vec!["hola", "hello", ""]);
+}
+
+#[test]
+fn test_change_templates() {
+    let (mut collection, mut notes, mut cards) =
+        create_reversed_model();
+
+    let graves = collection
+        .change_model(
+            1,
+            &[
+                ModelChange::RemoveTemplate("Card 1".to_string()),
+                ModelChange::AddTemplate(ModelTemplate::new(
+                    "Card 3".to_string(),
+                    "{{#Back}}{{Front}}?{{/Back}}".to_string(),
+                    "{{Back}}".to_string(),
+                )),
+                ModelChange::MoveTemplate {
+                    name: "Card 3".to_string(),
+                    position: 0,
+                },
+                ModelChange::RenameTemplate {
+                    from: "Card 2".to_string(),
+                    to: "Reverse".to_string(),
+                },
+            ],
+            &mut notes,
+            &mut cards,
+            &mut IdAllocator::starting_at(100),
+            4,
+        )
+        .unwrap();
+
+    assert_eq!(
+        graves
+            .iter()
+            .map(|grave| (grave.object_id, grave.grave_type))
+            .collect::<Vec<_>>(),
+        vec![(10, GraveType::Card), (12, GraveType::Card)]
+    );
+    // The reverse card keeps its review history
+    assert_eq!(cards[0].id, 11);
+    assert_eq!(cards[0].ordinal, 1);
+    assert_eq!(cards[0].reviews, 5);
+    assert_eq!(cards[0].lapses, 1);
+    // Only note 1 has a Back for the new template
+    assert_eq!(cards.len(), 2);
+    assert_eq!((cards[1].id, cards[1].note_id), (100, 1));
+    assert_eq!(cards[1].ordinal, 0);
+    assert_eq!(cards[1].queue, CardQueue::New);
+    let model = model(&collection);
+    assert_eq!(
+        model
+            .templates
+            .iter()
+            .map(|template| template.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Card 3", "Reverse"]
+    );
+}
+
+#[test]
+fn test_change_model_errors() {
+    let (mut collection, mut notes, mut cards) =
+        create_reversed_model();
+    collection.models.push((8, Model::cloze(8)));
+    let mut ids = IdAllocator::starting_at(100);
+    let mut change = |model_id, changes: &[ModelChange]| {
+        collection.change_model(
+            model_id, changes, &mut notes, &mut cards, &mut ids, -1,
+        )
+    };
+
+    assert!(change(9, &[]).is_err());
+    assert_eq!(change(1, &[]), Ok(vec![]));
+    for name in ["a:b", "{a}", "\"a\"", "#a", " ^a", "/a"] {
+        assert!(change(
+            1,
+            &[ModelChange::AddField(name.to_string())]
+        )
+        .is_err());
+        assert!(change(
+            1,
+            &[ModelChange::RenameField {
+                from: "Front".to_string(),
+                to: name.to_string(),
+            }]
+        )
+        .is_err());
+    }
+    assert!(change(
+        8,
+        &[ModelChange::RemoveTemplate("Cloze".to_string())]
+    )
+    .is_err());
+    assert!(change(1, &[ModelChange::AddField("back".to_string())])
+        .is_err());
+    assert!(change(
+        1,
+        &[ModelChange::MoveField {
+            name: "Front".to_string(),
+            position: 2,
+        }]
+    )
+    .is_err());
+    // Nothing is changed when a later change fails
+    assert!(change(
+        1,
+        &[
+            ModelChange::RemoveField("Front".to_string()),
+            ModelChange::RemoveField("Back".to_string()),
+        ]
+    )
+    .is_err());
+    assert_eq!(notes[0].fields, vec!["hola", "hello"]);
+    assert_eq!(collection.models[0].1, Model::basic_and_reversed(1));
+    assert_eq!(collection.scheme_mod_time, 0);
+    assert_eq!(notes[0].modified, 0);
+}
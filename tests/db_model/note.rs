@@ -0,0 +1,69 @@
+use ankimdown::db_model::model::{Model, ModelField, ModelType};
+use ankimdown::db_model::note::*;
+
+#[test]
+fn test_note_tag_new() {
+    assert_eq!(NoteTag::new("verbs").unwrap().name, "verbs");
+    assert!(NoteTag::new("").is_err());
+    assert!(NoteTag::new("irregular verbs").is_err());
+    assert!(NoteTag::new("irregular__verbs").is_err());
+    assert!(NoteTag::new(&"a".repeat(256)).is_err());
+}
+
+#[test]
+fn test_note_tag_repair() {
+    assert_eq!(
+        NoteTag::repair(" irregular  verbs ").unwrap().name,
+        "irregular_verbs"
+    );
+    assert_eq!(
+        NoteTag::repair("irregular___verbs").unwrap().name,
+        "irregular_verbs"
+    );
+    assert_eq!(
+        NoteTag::repair("Verbs::Irregular").unwrap().name,
+        "Verbs::Irregular"
+    );
+    assert!(NoteTag::repair("   ").is_err());
+}
+
+#[test]
+fn test_note_from_model() {
+    let model = Model::new(
+        None,
+        vec![
+            ModelField::new("Front".to_string(), 0),
+            ModelField::new("Back".to_string(), 1),
+        ],
+        1342697561419,
+        None,
+        None,
+        "Basic".to_string(),
+        1,
+        vec![],
+        ModelType::FrontBack,
+    );
+
+    let note = Note::from_model(
+        &model,
+        "guid".to_string(),
+        vec![
+            "<b>hola</b>".to_string(),
+            "hello&nbsp;<br>".to_string(),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(note.model_id, 1342697561419);
+    assert_eq!(note.sort_field, "hello");
+    // sha1("hola") starts with 99800b85
+    assert_eq!(note.checksum, 0x99800b85);
+    assert_eq!(note.update_seq_number, -1);
+
+    assert!(Note::from_model(
+        &model,
+        "guid".to_string(),
+        vec!["hola".to_string()]
+    )
+    .is_err());
+}
@@ -0,0 +1,67 @@
+use ankimdown::db_model::collection::Collection;
+use ankimdown::db_model::database::*;
+use ankimdown::db_model::revlog::*;
+
+fn create_test_review_log(id: i64, ease: ReviewEase) -> ReviewLog {
+    ReviewLog::new(
+        id,
+        1700000000002,
+        -1,
+        ease,
+        3,
+        1,
+        2500,
+        8000,
+        ReviewType::Review,
+    )
+}
+
+#[test]
+fn test_review_log_serialization() {
+    let review_log = create_test_review_log(1, ReviewEase::Hard);
+
+    let json = serde_json::to_value(&review_log).unwrap();
+
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "id": 1,
+            "cid": 1700000000002i64,
+            "usn": -1,
+            "ease": 2,
+            "ivl": 3,
+            "lastIvl": 1,
+            "factor": 2500,
+            "time": 8000,
+            "type": 1
+        })
+    );
+    assert_eq!(
+        serde_json::from_value::<ReviewLog>(json).unwrap(),
+        review_log
+    );
+    assert!(serde_json::from_value::<ReviewEase>(serde_json::json!(
+        5
+    ))
+    .is_err());
+}
+
+#[test]
+fn test_write_review_logs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("collection.anki2");
+    write_collection(&path, &Collection::new(), &[], &[]).unwrap();
+    let review_logs = vec![
+        create_test_review_log(1700000000010, ReviewEase::Again),
+        create_test_review_log(1700000000020, ReviewEase::Easy),
+    ];
+
+    write_review_logs(&path, &review_logs).unwrap();
+
+    assert_eq!(read_review_logs(&path).unwrap(), review_logs);
+    assert!(write_review_logs(
+        &dir.path().join("missing.anki2"),
+        &review_logs
+    )
+    .is_err());
+}
@@ -0,0 +1,333 @@
+use ankimdown::db_model::card::{Card, CardQueue, CardType};
+use ankimdown::db_model::deck::DeckConfig;
+use ankimdown::db_model::deck_config::DeckConfigError;
+use ankimdown::db_model::revlog::{
+    ReviewEase, ReviewLog, ReviewType,
+};
+use ankimdown::db_model::scheduler::*;
+
+use crate::db_model::create_test_card;
+
+const NOW: i64 = 1700000000000;
+const NOW_SECS: i64 = NOW / 1000;
+const TODAY: i64 = 100;
+
+fn scheduled_card(
+    card_type: CardType,
+    queue: CardQueue,
+    due: i64,
+    interval: i64,
+    left: i64,
+) -> Card {
+    let mut card = create_test_card(1700000000002, 1, 1);
+    card.factor = match card_type {
+        CardType::Review | CardType::Relearning => 2500,
+        _ => 0,
+    };
+    card.card_type = card_type;
+    card.queue = queue;
+    card.due = due;
+    card.interval = interval;
+    card.left = left;
+    card
+}
+
+fn new_card() -> Card {
+    scheduled_card(CardType::New, CardQueue::New, 1, 0, 0)
+}
+
+fn review_card(interval: i64, due: i64) -> Card {
+    scheduled_card(
+        CardType::Review,
+        CardQueue::Review,
+        due,
+        interval,
+        0,
+    )
+}
+
+fn answer(card: &mut Card, ease: ReviewEase) -> Answer {
+    let config = DeckConfig::new("Default".to_string());
+    Scheduler::new(&config, TODAY, NOW)
+        .unwrap()
+        .without_fuzz()
+        .answer(card, ease, 5000)
+        .unwrap()
+}
+
+#[test]
+fn test_new_card_good() {
+    let mut card = new_card();
+
+    let answer = answer(&mut card, ReviewEase::Good);
+
+    assert_eq!(card.card_type, CardType::Learning);
+    assert_eq!(card.queue, CardQueue::Learning);
+    assert_eq!(card.due, NOW_SECS + 600);
+    assert_eq!(card.left, 1);
+    assert_eq!(card.reviews, 1);
+    assert_eq!(card.modified, NOW_SECS);
+    assert_eq!(card.update_seq_number, -1);
+    assert_eq!(
+        answer,
+        Answer {
+            review_log: ReviewLog::new(
+                NOW,
+                card.id,
+                -1,
+                ReviewEase::Good,
+                -600,
+                0,
+                0,
+                5000,
+                ReviewType::Learning,
+            ),
+            leech: false,
+        }
+    );
+}
+
+#[test]
+fn test_new_card_again_and_hard() {
+    let mut card = new_card();
+    answer(&mut card, ReviewEase::Again);
+    assert_eq!((card.due, card.left), (NOW_SECS + 60, 2));
+
+    let mut card = new_card();
+    answer(&mut card, ReviewEase::Hard);
+    // Between the first two steps
+    assert_eq!((card.due, card.left), (NOW_SECS + 330, 2));
+}
+
+#[test]
+fn test_new_card_easy() {
+    let mut card = new_card();
+
+    answer(&mut card, ReviewEase::Easy);
+
+    assert_eq!(card.card_type, CardType::Review);
+    assert_eq!(card.queue, CardQueue::Review);
+    assert_eq!(card.interval, 4);
+    assert_eq!(card.due, TODAY + 4);
+    assert_eq!(card.factor, 2500);
+}
+
+#[test]
+fn test_learning_card_graduates() {
+    let mut card = scheduled_card(
+        CardType::Learning,
+        CardQueue::Learning,
+        NOW_SECS,
+        0,
+        1,
+    );
+
+    let answer = answer(&mut card, ReviewEase::Good);
+
+    assert_eq!(card.card_type, CardType::Review);
+    assert_eq!(card.queue, CardQueue::Review);
+    assert_eq!((card.interval, card.due), (1, TODAY + 1));
+    assert_eq!(card.factor, 2500);
+    assert_eq!(card.left, 0);
+    assert_eq!(answer.review_log.interval, 1);
+    assert_eq!(answer.review_log.last_interval, -600);
+    assert_eq!(answer.review_log.factor, 2500);
+}
+
+#[test]
+fn test_day_long_step() {
+    let config = DeckConfig::builder("Default".to_string())
+        .learning_steps(&[1440.0])
+        .build()
+        .unwrap();
+    let mut card = new_card();
+
+    let answer = Scheduler::new(&config, TODAY, NOW)
+        .unwrap()
+        .answer(&mut card, ReviewEase::Again, 5000)
+        .unwrap();
+
+    assert_eq!(card.queue, CardQueue::InLearning);
+    assert_eq!(card.due, TODAY + 1);
+    assert_eq!(answer.review_log.interval, 1);
+}
+
+#[test]
+fn test_review_card_passing_answers() {
+    let mut card = review_card(20, TODAY);
+    answer(&mut card, ReviewEase::Hard);
+    assert_eq!((card.interval, card.factor), (24, 2350));
+    assert_eq!(card.due, TODAY + 24);
+
+    let mut card = review_card(20, TODAY);
+    let answer_log = answer(&mut card, ReviewEase::Good).review_log;
+    assert_eq!((card.interval, card.factor), (50, 2500));
+    assert_eq!(answer_log.last_interval, 20);
+    assert_eq!(answer_log.review_type, ReviewType::Review);
+
+    let mut card = review_card(20, TODAY);
+    answer(&mut card, ReviewEase::Easy);
+    assert_eq!((card.interval, card.factor), (65, 2650));
+}
+
+#[test]
+fn test_late_review_counts_the_delay() {
+    let mut card = review_card(10, TODAY - 4);
+
+    answer(&mut card, ReviewEase::Good);
+
+    assert_eq!(card.interval, 30);
+}
+
+#[test]
+fn test_review_card_lapse() {
+    let mut card = review_card(10, TODAY);
+
+    let answer = answer(&mut card, ReviewEase::Again);
+
+    assert_eq!(card.card_type, CardType::Relearning);
+    assert_eq!(card.queue, CardQueue::Learning);
+    assert_eq!(card.due, NOW_SECS + 600);
+    assert_eq!(card.left, 1);
+    assert_eq!(card.lapses, 1);
+    assert_eq!(card.factor, 2300);
+    assert_eq!(card.interval, 1);
+    assert_eq!(
+        answer.review_log,
+        ReviewLog::new(
+            NOW,
+            card.id,
+            -1,
+            ReviewEase::Again,
+            -600,
+            10,
+            2300,
+            5000,
+            ReviewType::Review,
+        )
+    );
+    assert!(!answer.leech);
+}
+
+#[test]
+fn test_relearning_card_graduates() {
+    let relearning_card = || {
+        let mut card = scheduled_card(
+            CardType::Relearning,
+            CardQueue::Learning,
+            NOW_SECS,
+            3,
+            1,
+        );
+        card.factor = 2300;
+        card
+    };
+
+    let mut card = relearning_card();
+    let answer_log = answer(&mut card, ReviewEase::Good).review_log;
+    assert_eq!(card.card_type, CardType::Review);
+    assert_eq!((card.interval, card.due), (3, TODAY + 3));
+    assert_eq!(card.factor, 2300);
+    assert_eq!(answer_log.review_type, ReviewType::Relearning);
+
+    let mut card = relearning_card();
+    answer(&mut card, ReviewEase::Easy);
+    assert_eq!(card.interval, 4);
+}
+
+#[test]
+fn test_leech() {
+    let mut card = review_card(10, TODAY);
+    card.lapses = 7;
+    let answer = answer(&mut card, ReviewEase::Again);
+    assert!(answer.leech);
+    // The default config only tags leeches
+    assert_eq!(card.queue, CardQueue::Learning);
+
+    // Suspends at 6 lapses, then every 3 more
+    let config = DeckConfig::language_learning("Spanish".to_string());
+    let scheduler = Scheduler::new(&config, TODAY, NOW).unwrap();
+    let mut card = review_card(10, TODAY);
+    card.lapses = 5;
+    let answer =
+        scheduler.answer(&mut card, ReviewEase::Again, 0).unwrap();
+    assert!(answer.leech);
+    assert_eq!(card.queue, CardQueue::Suspended);
+    assert_eq!(answer.review_log.interval, -600);
+
+    let mut card = review_card(10, TODAY);
+    card.lapses = 7;
+    let answer =
+        scheduler.answer(&mut card, ReviewEase::Again, 0).unwrap();
+    assert!(!answer.leech);
+}
+
+#[test]
+fn test_fuzz_is_deterministic() {
+    let config = DeckConfig::new("Default".to_string());
+    let interval = |seed: u64| {
+        let mut card = review_card(100, TODAY);
+        Scheduler::new(&config, TODAY, NOW)
+            .unwrap()
+            .with_fuzz_seed(seed)
+            .answer(&mut card, ReviewEase::Good, 0)
+            .unwrap();
+        card.interval
+    };
+
+    assert_eq!(interval(1), interval(1));
+    let intervals = (0..20).map(interval).collect::<Vec<_>>();
+    assert!(intervals
+        .iter()
+        .all(|interval| (236..=264).contains(interval)));
+    assert!(intervals.iter().any(|other| *other != intervals[0]));
+}
+
+#[test]
+fn test_time_taken_is_capped() {
+    let config = DeckConfig::new("Default".to_string());
+    let mut card = new_card();
+
+    let answer = Scheduler::new(&config, TODAY, NOW)
+        .unwrap()
+        .answer(&mut card, ReviewEase::Good, 90000)
+        .unwrap();
+
+    assert_eq!(answer.review_log.time, 60000);
+}
+
+#[test]
+fn test_invalid_answers() {
+    let config = DeckConfig::new("Default".to_string());
+    let scheduler = Scheduler::new(&config, TODAY, NOW).unwrap();
+
+    let mut card = new_card();
+    assert!(scheduler
+        .answer(&mut card, ReviewEase::Manual, 0)
+        .is_err());
+    card.queue = CardQueue::Suspended;
+    assert!(scheduler
+        .answer(&mut card, ReviewEase::Good, 0)
+        .is_err());
+    let mut card = new_card();
+    card.original_deck_id = 2;
+    assert!(scheduler
+        .answer(&mut card, ReviewEase::Good, 0)
+        .is_err());
+    assert_eq!(card, {
+        let mut card = new_card();
+        card.original_deck_id = 2;
+        card
+    });
+}
+
+#[test]
+fn test_invalid_config() {
+    let mut config = DeckConfig::new("Default".to_string());
+    config.new_config.initial_factor = 0;
+
+    assert_eq!(
+        Scheduler::new(&config, TODAY, NOW),
+        Err(DeckConfigError::StartingEase(0))
+    );
+}
@@ -0,0 +1,160 @@
+use ankimdown::db_model::collection::*;
+use ankimdown::db_model::database::*;
+use ankimdown::db_model::deck::{Deck, DeckConfig};
+use ankimdown::db_model::model::{
+    Model, ModelField, ModelTemplate, ModelType,
+};
+use ankimdown::db_model::schema18;
+use rusqlite::Connection;
+
+fn create_v18_collection() -> Collection {
+    let mut template = ModelTemplate::new(
+        "Card 1".to_string(),
+        "{{Front}}".to_string(),
+        "{{FrontSide}}<hr id=answer>{{Back}}".to_string(),
+    );
+    template.default_deck_id = Some(2);
+    let model = Model::try_new(
+        None,
+        vec![
+            ModelField::new("Front".to_string(), 0),
+            ModelField::new("Back".to_string(), 1),
+        ],
+        1342697561419,
+        None,
+        None,
+        "Basic".to_string(),
+        1,
+        vec![template],
+        ModelType::FrontBack,
+    )
+    .unwrap();
+    let mut deck = Deck::new("Spanish::Verbs".to_string());
+    deck.id = 2;
+    deck.config_id = Some(1);
+    deck.extended_new_limit = Some(10);
+    deck.extended_review_limit = Some(50);
+    deck.description = "Irregular verbs".to_string();
+    let mut deck_config = DeckConfig::new("Default".to_string());
+    deck_config.id = Some(1);
+
+    Collection {
+        id: 1,
+        created: 1700000000,
+        modified: 1700000000123,
+        scheme_mod_time: 1700000000456,
+        version: 18,
+        dirty: 0,
+        update_seq_number: 0,
+        last_sync_time: 0,
+        config: CollectionConfig::new(),
+        models: vec![(1342697561419, model)],
+        decks: vec![(2, deck)],
+        deck_configs: vec![(1, deck_config)],
+        tags: r#"{"Verbs":0}"#.to_string(),
+    }
+}
+
+#[test]
+fn test_write_collection_v18() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("collection.anki21b");
+
+    write_collection_version(
+        &path,
+        &create_v18_collection(),
+        &[],
+        &[],
+        SchemaVersion::V18,
+    )
+    .unwrap();
+
+    let connection = Connection::open(&path).unwrap();
+    schema18::register_collations(&connection).unwrap();
+    let (ver, models): (i64, String) = connection
+        .query_row("SELECT ver, models FROM col", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((ver, models.as_str()), (18, ""));
+    let deck_name: String = connection
+        .query_row("SELECT name FROM decks WHERE id = 2", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(deck_name, "Spanish\x1fVerbs");
+    let fields = connection
+        .prepare("SELECT name FROM fields ORDER BY ord")
+        .unwrap()
+        .query_map([], |row| row.get::<_, String>(0))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(fields, vec!["Front", "Back"]);
+    let config: Vec<u8> = connection
+        .query_row(
+            "SELECT val FROM config WHERE KEY = 'newBury'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(config, b"true");
+}
+
+#[test]
+fn test_read_collection_v18() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("collection.anki21b");
+    let collection = create_v18_collection();
+    write_collection_version(
+        &path,
+        &collection,
+        &[],
+        &[],
+        SchemaVersion::V18,
+    )
+    .unwrap();
+
+    let (read, notes, cards) = read_collection(&path).unwrap();
+
+    assert_eq!(read, collection);
+    assert!(notes.is_empty());
+    assert!(cards.is_empty());
+}
+
+#[test]
+fn test_deck_config_conversion() {
+    let mut deck_config = DeckConfig::new("Default".to_string());
+    deck_config.id = Some(1);
+    deck_config.new_config.per_day = 30;
+    deck_config.lapse_config.leech_fails = 5;
+    deck_config.review_config.hard_factor = 1.1;
+
+    let config = schema18::DeckConfigConfig::from(&deck_config);
+
+    assert_eq!(config.new_per_day, 30);
+    assert_eq!(config.leech_threshold, 5);
+    assert_eq!(config.learn_steps, vec![1.0, 10.0]);
+    assert_eq!(config.easy_multiplier, 1.3);
+    assert_eq!(config.hard_multiplier, 1.1);
+    assert_eq!(config.graduating_interval_easy, 4);
+    assert_eq!(
+        config.to_deck_config(1, "Default", 0, 0),
+        deck_config
+    );
+}
+
+#[test]
+fn test_schema_version_from_number() {
+    assert_eq!(
+        SchemaVersion::from_number(11),
+        Ok(SchemaVersion::V11)
+    );
+    assert_eq!(
+        SchemaVersion::from_number(18),
+        Ok(SchemaVersion::V18)
+    );
+    assert!(SchemaVersion::from_number(12).is_err());
+    // Earlier Anki 2.1 layouts differ in the tags and graves tables
+    assert!(SchemaVersion::from_number(17).is_err());
+}
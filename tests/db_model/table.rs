@@ -0,0 +1,180 @@
+use ankimdown::db_model::card::{Card, CardFlag, CardQueue};
+use ankimdown::db_model::database::create_schema;
+use ankimdown::db_model::deck::Deck;
+use ankimdown::db_model::grave::{Grave, GraveType};
+use ankimdown::db_model::note::{Note, NoteTag};
+use ankimdown::db_model::revlog::{
+    ReviewEase, ReviewLog, ReviewType,
+};
+use ankimdown::db_model::schema18;
+use ankimdown::db_model::table::Table;
+use rusqlite::Connection;
+
+use crate::db_model::create_test_card;
+
+fn create_connection() -> Connection {
+    let connection = Connection::open_in_memory().unwrap();
+    create_schema(&connection).unwrap();
+    connection
+}
+
+#[test]
+fn test_card_table() {
+    let connection = create_connection();
+    let mut card = create_test_card(1, 10, 1);
+    card.insert(&connection).unwrap();
+    create_test_card(2, 10, 1).insert(&connection).unwrap();
+    create_test_card(3, 20, 1).insert(&connection).unwrap();
+
+    assert_eq!(card.table_name(), "cards");
+    assert_eq!(
+        Card::select_by_id(&connection, 1).unwrap(),
+        Some(card.clone())
+    );
+    assert_eq!(Card::select_by_id(&connection, 4).unwrap(), None);
+
+    card.queue = CardQueue::Suspended;
+    card.flags = CardFlag::Blue;
+    card.update(&connection).unwrap();
+    assert_eq!(
+        Card::select_by_id(&connection, 1).unwrap(),
+        Some(card)
+    );
+
+    let cards =
+        Card::select_where(&connection, "nid = ?1", [10]).unwrap();
+    assert_eq!(
+        cards.iter().map(|card| card.id).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+}
+
+#[test]
+fn test_update_missing_row() {
+    let connection = create_connection();
+    assert!(create_test_card(1, 10, 1).update(&connection).is_err());
+}
+
+#[test]
+fn test_note_table() {
+    let connection = create_connection();
+    let note = Note::new(
+        1,
+        "guid".to_string(),
+        2,
+        1700000000,
+        -1,
+        vec![NoteTag::new("Verbs").unwrap()],
+        vec!["hola".to_string(), "hello".to_string()],
+        "hello".to_string(),
+        12,
+    );
+    note.insert(&connection).unwrap();
+
+    let (tags, sort_field): (String, String) = connection
+        .query_row("SELECT tags, sfld FROM notes", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!(
+        (tags.as_str(), sort_field.as_str()),
+        (" Verbs ", "hello")
+    );
+    assert_eq!(
+        Note::select_by_id(&connection, 1).unwrap(),
+        Some(note)
+    );
+}
+
+#[test]
+fn test_note_table_numeric_sort_field() {
+    let connection = create_connection();
+    for (id, text) in [(1, "007"), (2, "1.50"), (3, "<b>42</b>")] {
+        let note = Note::new(
+            id,
+            format!("guid{}", id),
+            2,
+            1700000000,
+            -1,
+            vec![],
+            vec![text.to_string(), "back".to_string()],
+            text.replace("<b>", "").replace("</b>", ""),
+            12,
+        );
+        note.insert(&connection).unwrap();
+        assert_eq!(
+            Note::select_by_id(&connection, id).unwrap(),
+            Some(note)
+        );
+    }
+
+    connection
+        .execute("ALTER TABLE notes DROP COLUMN sfld", [])
+        .unwrap();
+    assert!(Note::select_by_id(&connection, 2).is_err());
+}
+
+#[test]
+fn test_review_log_table() {
+    let connection = create_connection();
+    let review = ReviewLog::new(
+        1700000000000,
+        1,
+        -1,
+        ReviewEase::Good,
+        1,
+        -600,
+        2500,
+        6000,
+        ReviewType::Learning,
+    );
+    review.insert(&connection).unwrap();
+
+    assert_eq!(
+        ReviewLog::select_where(&connection, "cid = ?1", [1])
+            .unwrap(),
+        vec![review]
+    );
+}
+
+#[test]
+fn test_deck_table() {
+    let connection = Connection::open_in_memory().unwrap();
+    schema18::create_schema(&connection).unwrap();
+    let mut deck = Deck::new("Spanish::Verbs".to_string());
+    deck.id = 2;
+    deck.config_id = Some(1);
+    deck.extended_new_limit = Some(0);
+    deck.extended_review_limit = Some(0);
+    deck.insert(&connection).unwrap();
+
+    assert_eq!(
+        Deck::select_by_id(&connection, 2).unwrap(),
+        Some(deck)
+    );
+}
+
+#[test]
+fn test_grave_table() {
+    let connection = create_connection();
+    let mut card_grave = Grave::new(5, GraveType::Card, -1);
+    let note_grave = Grave::new(5, GraveType::Note, -1);
+    card_grave.insert(&connection).unwrap();
+    note_grave.insert(&connection).unwrap();
+
+    card_grave.update_seq_number = 3;
+    card_grave.update(&connection).unwrap();
+    assert_eq!(
+        Grave::select_where(&connection, "oid = ?1", [5]).unwrap(),
+        vec![card_grave, note_grave]
+    );
+    assert!(Grave::new(6, GraveType::Card, 0)
+        .update(&connection)
+        .is_err());
+    assert!(Grave::select_by_id(&connection, 5).is_err());
+    assert!(Grave::delete_by_id(&connection, 5, -1).is_err());
+    assert_eq!(
+        Grave::select_where(&connection, "1", []).unwrap().len(),
+        2
+    );
+}
@@ -1 +1,2 @@
 mod db_model;
+mod package;
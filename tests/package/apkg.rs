@@ -0,0 +1,258 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+
+use ankimdown::db_model::collection::Collection;
+use ankimdown::db_model::database::read_collection;
+use ankimdown::db_model::revlog::{
+    ReviewEase, ReviewLog, ReviewType,
+};
+use ankimdown::package::*;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::db_model::{create_test_card, create_test_note};
+
+fn create_test_package() -> Package {
+    let note = create_test_note(
+        1700000000001,
+        &["hola", "<img src=\"hola.png\">"],
+    );
+    let card = create_test_card(1700000000002, 1700000000001, 1);
+    let mut package =
+        Package::new(Collection::new(), vec![note], vec![card]);
+    package.media = vec![
+        MediaFile::new("hola.png", vec![1, 2, 3]),
+        MediaFile::new("hola.mp3", vec![4, 5]),
+    ];
+    package
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Vec<u8> {
+    let mut data = vec![];
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+#[test]
+fn test_write_apkg() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("deck.apkg");
+    let package = create_test_package();
+
+    package.write(&path).unwrap();
+
+    let mut archive =
+        ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let mut names = archive.file_names().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["0", "1", "collection.anki2", "media"]);
+
+    let media: serde_json::Value =
+        serde_json::from_slice(&read_entry(&mut archive, MEDIA_FILE))
+            .unwrap();
+    assert_eq!(
+        media,
+        serde_json::json!({"0": "hola.png", "1": "hola.mp3"})
+    );
+    assert_eq!(read_entry(&mut archive, "0"), vec![1, 2, 3]);
+    assert_eq!(read_entry(&mut archive, "1"), vec![4, 5]);
+
+    let collection_path = dir.path().join(COLLECTION_FILE);
+    fs::write(
+        &collection_path,
+        read_entry(&mut archive, COLLECTION_FILE),
+    )
+    .unwrap();
+    let (_, notes, cards) =
+        read_collection(&collection_path).unwrap();
+    assert_eq!(notes, package.notes);
+    assert_eq!(cards, package.cards);
+}
+
+#[test]
+fn test_write_apkg_duplicate_media() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut package = create_test_package();
+    package.media.push(MediaFile::new("hola.png", vec![]));
+
+    assert!(package.write(&dir.path().join("deck.apkg")).is_err());
+}
+
+#[test]
+fn test_media_file_from_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hola.png");
+    fs::write(&path, [7, 8]).unwrap();
+
+    let media = MediaFile::from_path(&path).unwrap();
+
+    assert_eq!(media, MediaFile::new("hola.png", vec![7, 8]));
+    assert!(MediaFile::from_path(&dir.path().join("missing.png"))
+        .is_err());
+}
+
+#[test]
+fn test_read_apkg() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("deck.apkg");
+    let package = create_test_package();
+    package.write(&path).unwrap();
+
+    let read = Package::read(&path).unwrap();
+
+    assert_eq!(read.notes, package.notes);
+    assert_eq!(read.cards, package.cards);
+    assert_eq!(read.media, package.media);
+    assert_eq!(
+        read.media_names().get("1").map(String::as_str),
+        Some("hola.mp3")
+    );
+    assert_eq!(
+        read.media_file("hola.png").unwrap().data,
+        vec![1, 2, 3]
+    );
+    assert!(read.media_file("missing.png").is_none());
+}
+
+fn write_zip(path: &std::path::Path, entries: &[(&str, Vec<u8>)]) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn test_read_apkg_prefers_anki21() {
+    let dir = tempfile::tempdir().unwrap();
+    let placeholder = dir.path().join("placeholder.apkg");
+    Package::new(Collection::new(), vec![], vec![])
+        .write(&placeholder)
+        .unwrap();
+    let real = dir.path().join("real.apkg");
+    create_test_package().write(&real).unwrap();
+
+    let mut placeholder_zip =
+        ZipArchive::new(File::open(&placeholder).unwrap()).unwrap();
+    let mut real_zip =
+        ZipArchive::new(File::open(&real).unwrap()).unwrap();
+    let path = dir.path().join("anki21.apkg");
+    write_zip(
+        &path,
+        &[
+            (
+                COLLECTION_FILE,
+                read_entry(&mut placeholder_zip, COLLECTION_FILE),
+            ),
+            (
+                COLLECTION_21_FILE,
+                read_entry(&mut real_zip, COLLECTION_FILE),
+            ),
+        ],
+    );
+
+    let read = Package::read(&path).unwrap();
+
+    assert_eq!(read.notes.len(), 1);
+    assert!(read.media.is_empty());
+}
+
+#[test]
+fn test_read_apkg_missing_media_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let real = dir.path().join("real.apkg");
+    create_test_package().write(&real).unwrap();
+    let mut real_zip =
+        ZipArchive::new(File::open(&real).unwrap()).unwrap();
+    let path = dir.path().join("broken.apkg");
+    write_zip(
+        &path,
+        &[
+            (
+                COLLECTION_FILE,
+                read_entry(&mut real_zip, COLLECTION_FILE),
+            ),
+            (MEDIA_FILE, br#"{"0": "hola.png"}"#.to_vec()),
+        ],
+    );
+
+    assert!(Package::read(&path).is_err());
+    assert!(Package::read(&dir.path().join("missing.apkg")).is_err());
+}
+
+#[test]
+fn test_write_apkg_latest() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("deck.apkg");
+    let package = create_test_package();
+
+    package.write_format(&path, PackageFormat::Latest).unwrap();
+
+    let mut archive =
+        ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let mut names = archive.file_names().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "0",
+            "1",
+            "collection.anki2",
+            "collection.anki21b",
+            "media",
+            "meta"
+        ]
+    );
+    // PackageMetadata { version: 3 }
+    assert_eq!(read_entry(&mut archive, META_FILE), vec![8, 3]);
+    assert_eq!(
+        zstd::decode_all(read_entry(&mut archive, "1").as_slice())
+            .unwrap(),
+        vec![4, 5]
+    );
+
+    let collection_path = dir.path().join(COLLECTION_FILE);
+    fs::write(
+        &collection_path,
+        zstd::decode_all(
+            read_entry(&mut archive, COLLECTION_21B_FILE).as_slice(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let (collection, notes, _) =
+        read_collection(&collection_path).unwrap();
+    assert_eq!(collection.version, 18);
+    assert_eq!(notes, package.notes);
+}
+
+#[test]
+fn test_read_apkg_latest() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("collection.colpkg");
+    let mut package = create_test_package();
+    package.review_logs = vec![ReviewLog::new(
+        1700000000010,
+        1700000000002,
+        -1,
+        ReviewEase::Good,
+        1,
+        0,
+        2500,
+        5000,
+        ReviewType::Learning,
+    )];
+    package.write_colpkg(&path).unwrap();
+
+    let read = Package::read(&path).unwrap();
+
+    assert_eq!(read.notes, package.notes);
+    assert_eq!(read.cards, package.cards);
+    assert_eq!(read.review_logs, package.review_logs);
+    assert_eq!(read.media, package.media);
+}